@@ -21,8 +21,11 @@ fn main() -> Result<()> {
     info!("Commit message: {}", commit_message);
 
     let cwd = std::env::current_dir().unwrap();
+    let backend = ccver::backend::open(Default::default(), &cwd)?;
+    let config = ccver::config::CCVerConfig::from_path(&cwd)?;
     let next_version = ccver::peek(
-        &cwd,
+        &*backend,
+        &config,
         commit_message,
         &VersionFormat {
             v_prefix: false,
@@ -1,4 +1,6 @@
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::{backend::BackendKind, changelog_format::ChangeLogFormat};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -12,18 +14,44 @@ pub struct CCVerArgs {
     #[arg(long = "path", short = 'p')]
     pub path: Option<String>,
 
-    #[arg(long = "force-major")]
+    #[arg(
+        long = "force-major",
+        group = "force",
+        help = "Bump the major version of HEAD (or the peeked commit) regardless of its subject"
+    )]
     pub force_major: bool,
 
-    #[arg(long = "force-minor")]
+    #[arg(
+        long = "force-minor",
+        group = "force",
+        help = "Bump the minor version of HEAD (or the peeked commit) regardless of its subject"
+    )]
     pub force_minor: bool,
 
-    #[arg(long = "force-patch")]
+    #[arg(
+        long = "force-patch",
+        group = "force",
+        help = "Bump the patch version of HEAD (or the peeked commit) regardless of its subject"
+    )]
     pub force_patch: bool,
 
+    #[arg(
+        long = "graduate",
+        group = "force",
+        help = "Release 1.0.0 at HEAD (or the peeked commit) if the version is still 0.y.z"
+    )]
+    pub graduate: bool,
+
     #[arg(long = "format", short = 'f')]
     pub format: Option<String>,
 
+    #[arg(
+        long = "config",
+        short = 'c',
+        help = "Path to a ccver config file (defaults to ccver.toml, .ccver or Cargo.toml at the repo root)"
+    )]
+    pub config: Option<String>,
+
     #[arg(long = "no-pre")]
     pub no_pre: bool,
 
@@ -36,6 +64,51 @@ pub struct CCVerArgs {
 
     #[arg(long = "ci", help = "Throw an error if the repository is dirty")]
     pub ci: bool,
+
+    #[arg(
+        long = "output",
+        short = 'o',
+        global = true,
+        value_enum,
+        help = "Output format"
+    )]
+    pub output: Option<OutputFormat>,
+
+    #[arg(
+        long = "package",
+        global = true,
+        help = "Version only the commits touching this package from [packages] in the config"
+    )]
+    pub package: Option<String>,
+
+    #[arg(
+        long = "rev",
+        global = true,
+        help = "Version this commit-ish instead of HEAD (the default command, changelog and peek)"
+    )]
+    pub revision: Option<String>,
+
+    #[arg(
+        long = "branch",
+        global = true,
+        help = "Branch whose role --rev or HEAD takes (a detached HEAD defaults to GITHUB_REF_NAME or CI_COMMIT_BRANCH)"
+    )]
+    pub branch: Option<String>,
+
+    #[arg(
+        long = "git-backend",
+        global = true,
+        value_enum,
+        help = "How to read the repository: run git, read .git directly, or git when installed (default)"
+    )]
+    pub git_backend: Option<BackendKind>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
 }
 
 #[derive(Args, Debug)]
@@ -50,15 +123,154 @@ pub struct PeekArgs {
 pub struct TagArgs {
     #[arg(long = "all", short = 'a')]
     pub all: bool,
+
+    #[arg(
+        long = "dry-run",
+        short = 'n',
+        help = "Print what would be tagged without creating any tag"
+    )]
+    pub dry_run: bool,
+
+    #[arg(
+        long = "annotate",
+        help = "Create annotated tags whose message is the release's changelog"
+    )]
+    pub annotate: bool,
+
+    #[arg(
+        long = "sign",
+        short = 's',
+        help = "Sign the tags (implies --annotate; tag.gpgSign is also honoured)"
+    )]
+    pub sign: bool,
+
+    #[arg(
+        long = "message-template",
+        help = "Render tag messages with this changelog template file (relative to --path)"
+    )]
+    pub message_template: Option<String>,
+
+    #[arg(
+        long = "push",
+        value_name = "REMOTE",
+        num_args = 0..=1,
+        default_missing_value = "origin",
+        require_equals = true,
+        help = "Push the tags created by this run to REMOTE (origin by default)"
+    )]
+    pub push: Option<String>,
+}
+
+#[derive(Args, Debug)]
+#[command(about = "Print the changelog")]
+pub struct ChangeLogArgs {
+    #[arg(
+        long = "write",
+        short = 'w',
+        help = "Prepend sections for released versions missing from this file (relative to --path)"
+    )]
+    pub write: Option<String>,
+
+    #[arg(
+        long = "from",
+        conflicts_with = "all",
+        help = "Start after this version, tag or commit (exclusive)"
+    )]
+    pub from: Option<String>,
+
+    #[arg(
+        long = "to",
+        help = "End at this version, tag or commit (inclusive, defaults to HEAD)"
+    )]
+    pub to: Option<String>,
+
+    #[arg(long = "all", help = "One section per release in the full history")]
+    pub all: bool,
+
+    #[arg(
+        long = "changelog-format",
+        value_enum,
+        help = "Layout to render the changelog in"
+    )]
+    pub format: Option<ChangeLogFormat>,
+
+    #[arg(
+        long = "changelog-template",
+        conflicts_with = "format",
+        help = "Render the changelog with this template file (relative to --path)"
+    )]
+    pub template: Option<String>,
+
+    #[arg(
+        long = "tag-messages",
+        help = "Use the messages of annotated release tags as their sections"
+    )]
+    pub tag_messages: bool,
+}
+
+#[derive(Args, Debug)]
+#[command(about = "Explain how a commit's version was derived")]
+pub struct ExplainArgs {
+    #[arg(help = "Commit to explain (defaults to HEAD)")]
+    pub rev: Option<String>,
+
+    #[arg(long = "depth", short = 'd', help = "Maximum number of steps to show")]
+    pub depth: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+#[command(about = "Check a commit message against the conventional commit grammar")]
+pub struct LintArgs {
+    #[arg(help = "File holding the message, or `-` to read stdin")]
+    pub file: String,
+}
+
+#[derive(Args, Debug)]
+#[command(about = "Install commit-msg and prepare-commit-msg hooks")]
+pub struct InstallArgs {
+    #[arg(long = "force", help = "Replace hooks that already exist")]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+#[command(about = "Check that every commit since a revision is conventional")]
+pub struct CheckArgs {
+    #[arg(
+        long = "since",
+        help = "Check the commits reachable from HEAD but not from this revision"
+    )]
+    pub since: String,
+}
+
+#[derive(Args, Debug)]
+#[command(about = "Manage the versions cached in the git directory")]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub command: CacheCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    #[command(about = "Remove the cached versions")]
+    Clear,
+    #[command(about = "Check the cached versions against the whole history")]
+    Verify,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum CCVerSubCommand {
     #[command(about = "Tag git with calculated version")]
     Tag(TagArgs),
-    #[command(about = "Print the changelog")]
-    ChangeLog,
+    #[command(about = "Print the changelog", alias = "changelog")]
+    ChangeLog(ChangeLogArgs),
     #[command(about = "Print the git format string")]
     GitFormat,
     Peek(PeekArgs),
+    Explain(ExplainArgs),
+    Lint(LintArgs),
+    Install(InstallArgs),
+    Check(CheckArgs),
+    #[command(about = "List the configured packages with their current versions")]
+    Packages,
+    Cache(CacheArgs),
 }
@@ -0,0 +1,619 @@
+//! Walks a repository's history the way `git log --branches --source` does
+//! and prints it in the [`GIT_FORMAT_ARGS`](crate::logs::GIT_FORMAT_ARGS)
+//! format, for backends that don't run `git`.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use eyre::{Result, eyre};
+use tracing::debug;
+
+/// A commit object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commit {
+    pub hash: String,
+    pub tree: String,
+    pub parents: Vec<String>,
+    pub author: Signature,
+    pub committer: Signature,
+    pub message: String,
+}
+
+/// The `author` or `committer` line of a commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    /// Seconds since the epoch.
+    pub time: i64,
+    /// The signer's UTC offset in minutes.
+    pub offset: i32,
+}
+
+impl Signature {
+    /// Parses `Name <email> 1700000000 +0100`.
+    pub fn parse(line: &str) -> Result<Signature> {
+        let invalid = || eyre!("Invalid signature: {}", line);
+        let (name, rest) = line.split_once('<').ok_or_else(invalid)?;
+        let (email, rest) = rest.rsplit_once('>').ok_or_else(invalid)?;
+        let mut fields = rest.split_whitespace();
+        let time = fields
+            .next()
+            .and_then(|time| time.parse().ok())
+            .unwrap_or(0);
+        let offset = fields
+            .next()
+            .and_then(|offset| {
+                let sign = if offset.starts_with('-') { -1 } else { 1 };
+                let digits = offset.trim_start_matches(['+', '-']).parse::<i32>().ok()?;
+                Some(sign * (digits / 100 * 60 + digits % 100))
+            })
+            .unwrap_or(0);
+        Ok(Signature {
+            name: name.trim().to_string(),
+            email: email.to_string(),
+            time,
+            offset,
+        })
+    }
+
+    /// `%cI`: the time in strict ISO 8601, in the signer's time zone.
+    fn iso8601(&self) -> String {
+        let offset = chrono::FixedOffset::east_opt(self.offset * 60)
+            .unwrap_or_else(|| chrono::FixedOffset::east_opt(0).unwrap());
+        chrono::DateTime::from_timestamp(self.time, 0)
+            .unwrap_or_default()
+            .with_timezone(&offset)
+            .format("%Y-%m-%dT%H:%M:%S%:z")
+            .to_string()
+    }
+}
+
+impl std::fmt::Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.offset < 0 { '-' } else { '+' };
+        let offset = self.offset.abs();
+        write!(
+            f,
+            "{} <{}> {} {}{:02}{:02}",
+            self.name,
+            self.email,
+            self.time,
+            sign,
+            offset / 60,
+            offset % 60
+        )
+    }
+}
+
+impl Commit {
+    /// Parses the contents of the commit object `hash`.
+    pub fn parse(hash: &str, data: &[u8]) -> Result<Commit> {
+        let data = String::from_utf8_lossy(data);
+        let (headers, message) = data.split_once("\n\n").unwrap_or((&data, ""));
+        let mut tree = None;
+        let mut parents = vec![];
+        let (mut author, mut committer) = (None, None);
+        for header in headers.lines() {
+            match header.split_once(' ') {
+                Some(("tree", value)) => tree = Some(value.to_string()),
+                Some(("parent", value)) => parents.push(value.to_string()),
+                Some(("author", value)) => author = Some(Signature::parse(value)?),
+                Some(("committer", value)) => committer = Some(Signature::parse(value)?),
+                // gpgsig, mergetag, encoding, ... and their continuation lines
+                _ => {}
+            }
+        }
+        let missing = |field: &str| eyre!("Commit {} has no {}", hash, field);
+        Ok(Commit {
+            hash: hash.to_string(),
+            tree: tree.ok_or_else(|| missing("tree"))?,
+            parents,
+            author: author.ok_or_else(|| missing("author"))?,
+            committer: committer.ok_or_else(|| missing("committer"))?,
+            message: message.to_string(),
+        })
+    }
+
+    /// The contents of the commit object, as hashed by git.
+    pub fn to_object(&self) -> String {
+        let parents = self
+            .parents
+            .iter()
+            .map(|parent| format!("parent {}\n", parent))
+            .collect::<String>();
+        format!(
+            "tree {}\n{}author {}\ncommitter {}\n\n{}",
+            self.tree, parents, self.author, self.committer, self.message
+        )
+    }
+
+    /// `%s` and `%b`: the first paragraph of the message joined into one
+    /// line, and everything after it.
+    fn subject_and_body(&self) -> (String, &str) {
+        let message = skip_blank_lines(&self.message);
+        let mut subject = vec![];
+        let mut rest = message;
+        while !rest.is_empty() {
+            let (line, next) = split_line(rest);
+            rest = next;
+            let line = line.trim_end_matches(is_space);
+            if line.is_empty() {
+                break;
+            }
+            subject.push(line);
+        }
+        (subject.join(" "), skip_blank_lines(rest))
+    }
+}
+
+fn is_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r')
+}
+
+/// The first line of `text` (with its newline) and the rest.
+fn split_line(text: &str) -> (&str, &str) {
+    match text.find('\n') {
+        Some(end) => text.split_at(end + 1),
+        None => (text, ""),
+    }
+}
+
+fn skip_blank_lines(mut text: &str) -> &str {
+    loop {
+        let (line, rest) = split_line(text);
+        if line.is_empty() || !line.trim_matches(is_space).is_empty() {
+            return text;
+        }
+        text = rest;
+    }
+}
+
+/// `%f`: the first line of the subject with runs of anything but letters,
+/// digits, `.` and `_` replaced by `-`.
+fn sanitized_subject(subject: &str) -> String {
+    let bytes = subject.as_bytes();
+    let mut sanitized = String::new();
+    // 2: nothing written yet, 1: a separator is pending, 0: in a word
+    let mut space = 2;
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c.is_ascii_alphanumeric() || c == b'.' || c == b'_' {
+            if space == 1 {
+                sanitized.push('-');
+            }
+            space = 0;
+            sanitized.push(c as char);
+            if c == b'.' {
+                while bytes.get(i + 1) == Some(&b'.') {
+                    i += 1;
+                }
+            }
+        } else {
+            space |= 1;
+        }
+        i += 1;
+    }
+    sanitized.trim_end_matches(['.', '-']).to_string()
+}
+
+/// A ref and the object it points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ref {
+    /// The full name, e.g. `refs/heads/main`.
+    pub name: String,
+    pub target: String,
+    /// The commit an annotated tag points at.
+    pub peeled: Option<String>,
+}
+
+impl Ref {
+    /// The commit this ref ends up at.
+    pub fn commit(&self) -> &str {
+        self.peeled.as_deref().unwrap_or(&self.target)
+    }
+}
+
+/// What HEAD points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Head {
+    /// A branch by its full name, and its commit unless the branch is unborn.
+    Branch(String, Option<String>),
+    Detached(String),
+}
+
+impl Head {
+    pub fn commit(&self) -> Option<&str> {
+        match self {
+            Head::Branch(_, commit) => commit.as_deref(),
+            Head::Detached(commit) => Some(commit),
+        }
+    }
+
+    /// `git rev-parse --abbrev-ref HEAD`.
+    pub fn abbrev(&self) -> &str {
+        match self {
+            Head::Branch(name, _) => name.strip_prefix("refs/heads/").unwrap_or(name),
+            Head::Detached(_) => "HEAD",
+        }
+    }
+}
+
+/// A repository's commits and refs.
+pub trait History {
+    /// The commit `hash`, or `None` if the repository doesn't have it.
+    fn commit(&self, hash: &str) -> Result<Option<Commit>>;
+
+    /// The paths `commit` changed relative to its first parent (every path
+    /// for a root commit).
+    fn changed_paths(&self, commit: &Commit) -> Result<Vec<String>>;
+
+    /// Every ref, sorted by name.
+    fn refs(&self) -> Result<Vec<Ref>>;
+
+    fn head(&self) -> Result<Option<Head>>;
+
+    /// The commits a shallow clone cut the parents of.
+    fn grafted(&self) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+}
+
+/// The namespaces `git log` decorates commits with by default.
+fn decorates(name: &str) -> bool {
+    ["refs/heads/", "refs/remotes/", "refs/tags/"]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+        || name == "refs/stash"
+}
+
+fn prettify(name: &str) -> &str {
+    ["refs/heads/", "refs/tags/", "refs/remotes/"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
+}
+
+/// `%d`, e.g. ` (HEAD -> main, tag: v1.0.0, origin/main)`.
+fn decorations(names: &[&str], head: Option<&Head>) -> String {
+    if names.is_empty() {
+        return String::new();
+    }
+    let current = match head {
+        Some(Head::Branch(branch, _)) if names.contains(&"HEAD") => {
+            names.iter().find(|name| **name == branch.as_str()).copied()
+        }
+        _ => None,
+    };
+    let shown = names
+        .iter()
+        .filter(|name| Some(**name) != current)
+        .map(|name| match (*name, current) {
+            ("HEAD", Some(current)) => format!("HEAD -> {}", prettify(current)),
+            (name, _) if name.starts_with("refs/tags/") => format!("tag: {}", prettify(name)),
+            (name, _) => prettify(name).to_string(),
+        })
+        .collect::<Vec<_>>();
+    format!(" ({})", shown.join(", "))
+}
+
+/// The commit `rev` names, like `git rev-parse <rev>^{commit}` for a full
+/// commit hash, `HEAD` or the name of a ref.
+pub fn resolve(history: &impl History, rev: &str) -> Result<String> {
+    let hash = match rev {
+        "HEAD" => history
+            .head()?
+            .as_ref()
+            .and_then(Head::commit)
+            .map(str::to_string),
+        _ if rev.len() == 40 && rev.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            Some(rev.to_ascii_lowercase())
+        }
+        _ => {
+            // in the order git looks names up in
+            let refs = history.refs()?;
+            [
+                rev.to_string(),
+                format!("refs/{}", rev),
+                format!("refs/tags/{}", rev),
+                format!("refs/heads/{}", rev),
+                format!("refs/remotes/{}", rev),
+            ]
+            .iter()
+            .find_map(|name| refs.iter().find(|r| r.name == *name))
+            .map(|r| r.commit().to_string())
+        }
+    };
+    match hash {
+        Some(hash) if history.commit(&hash)?.is_some() => Ok(hash),
+        _ => Err(eyre!(
+            "Could not resolve revision `{}` to a commit; other revisions need git",
+            rev
+        )),
+    }
+}
+
+/// The name of every branch, in ref order.
+pub fn branches(history: &impl History) -> Result<Vec<String>> {
+    Ok(history
+        .refs()?
+        .iter()
+        .filter_map(|r| Some(r.name.strip_prefix("refs/heads/")?.to_string()))
+        .collect())
+}
+
+/// The tags reachable from a branch, by name, with the commit each points at
+/// and that commit's committer time.
+pub fn branch_tags(history: &impl History) -> Result<HashMap<String, (String, i64)>> {
+    let refs = history.refs()?;
+    let mut times = HashMap::<String, i64>::new();
+    let mut pending = refs
+        .iter()
+        .filter(|r| r.name.starts_with("refs/heads/"))
+        .map(|branch| branch.commit().to_string())
+        .collect::<Vec<_>>();
+    while let Some(hash) = pending.pop() {
+        if times.contains_key(&hash) {
+            continue;
+        }
+        let commit = history
+            .commit(&hash)?
+            .ok_or_else(|| eyre!("Commit {} is missing from the repository", hash))?;
+        pending.extend(commit.parents);
+        times.insert(hash, commit.committer.time);
+    }
+    Ok(refs
+        .iter()
+        .filter_map(|r| {
+            let tag = r.name.strip_prefix("refs/tags/")?;
+            let time = *times.get(r.commit())?;
+            Some((tag.to_string(), (r.commit().to_string(), time)))
+        })
+        .collect())
+}
+
+/// How many left out commits older than the last listed one `git log` walks
+/// past before it stops looking for more to leave out (git's `SLOP`).
+const SLOP: usize = 5;
+
+/// Marks `hash` and every loaded commit it reaches as left out.
+fn leave_out(commits: &HashMap<String, Commit>, left_out: &mut HashSet<String>, hash: &str) {
+    let mut pending = vec![hash.to_string()];
+    while let Some(hash) = pending.pop() {
+        if let Some(commit) = commits.get(&hash) {
+            pending.extend(
+                commit
+                    .parents
+                    .iter()
+                    .filter(|parent| !left_out.contains(*parent))
+                    .cloned(),
+            );
+        }
+        left_out.insert(hash);
+    }
+}
+
+/// The output of `git log` with [`GIT_FORMAT_ARGS`](crate::logs::GIT_FORMAT_ARGS):
+/// every commit reachable from a branch, or from the commit `tip` when it is
+/// given, newest first, with the branch (or `tip`) it was reached from. The
+/// ancestors of `boundaries` are left out, as they are by
+/// `git log ^<boundary>^@`.
+pub fn render_log(
+    history: &impl History,
+    changed_paths: bool,
+    tip: Option<&str>,
+    boundaries: &[String],
+) -> Result<String> {
+    let tips = history
+        .refs()?
+        .iter()
+        .filter(|r| r.name.starts_with("refs/heads/"))
+        .map(|branch| {
+            (
+                branch.commit().to_string(),
+                prettify(&branch.name).to_string(),
+            )
+        })
+        .chain(tip.map(|tip| (tip.to_string(), tip.to_string())))
+        .collect::<Vec<_>>();
+    let mut excluded = vec![];
+    for boundary in boundaries {
+        let commit = history
+            .commit(boundary)?
+            .ok_or_else(|| eyre!("Commit {} is missing from the repository", boundary))?;
+        excluded.extend(commit.parents);
+    }
+    render(history, changed_paths, &tips, &excluded)
+}
+
+/// The output of `git log <range>` with
+/// [`GIT_FORMAT_ARGS`](crate::logs::GIT_FORMAT_ARGS) but without
+/// `--branches`: the commits `<to>` reaches and `<from>` does not for
+/// `<from>..<to>` (either defaulting to `HEAD`), or every commit a single
+/// revision reaches. Revisions are read as [`resolve`] reads them.
+pub fn render_range(history: &impl History, range: &str) -> Result<String> {
+    let or_head = |rev: &'_ str| match rev {
+        "" => "HEAD".to_string(),
+        rev => rev.to_string(),
+    };
+    let (to, from) = match range.split_once("..") {
+        Some((_, to)) if to.starts_with('.') => {
+            return Err(eyre!("Symmetric ranges like `{}` need git", range));
+        }
+        Some((from, to)) => (or_head(to), Some(or_head(from))),
+        None => (range.to_string(), None),
+    };
+    let tips = [(resolve(history, &to)?, to)];
+    let excluded = from
+        .map(|from| resolve(history, &from))
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+    render(history, false, &tips, &excluded)
+}
+
+/// Lists the commits `tips` reach, each `(commit, source)`, leaving out
+/// `excluded` and their ancestors, in the format of [`render_log`].
+fn render(
+    history: &impl History,
+    changed_paths: bool,
+    tips: &[(String, String)],
+    excluded: &[String],
+) -> Result<String> {
+    let refs = history.refs()?;
+    let head = history.head()?;
+    let grafted = history.grafted()?;
+
+    // git lists the decorations of a commit in reverse ref order, after HEAD
+    // and after `grafted` for the ends of a shallow clone
+    let mut decorated = HashMap::<&str, Vec<&str>>::new();
+    for r in refs.iter().filter(|r| decorates(&r.name)) {
+        decorated.entry(r.commit()).or_default().insert(0, &r.name);
+    }
+    if let Some(commit) = head.as_ref().and_then(Head::commit) {
+        decorated.entry(commit).or_default().insert(0, "HEAD");
+    }
+    for commit in &grafted {
+        decorated.entry(commit).or_default().insert(0, "grafted");
+    }
+
+    // loads a commit into `commits`, returning its committer time
+    let mut commits = HashMap::<String, Commit>::new();
+    let load = |commits: &mut HashMap<String, Commit>, hash: &str| -> Result<i64> {
+        if let Some(commit) = commits.get(hash) {
+            return Ok(commit.committer.time);
+        }
+        let commit = history
+            .commit(hash)?
+            .ok_or_else(|| eyre!("Commit {} is missing from the repository", hash))?;
+        let time = commit.committer.time;
+        commits.insert(hash.to_string(), commit);
+        Ok(time)
+    };
+
+    // the tips and the excluded commits, newest first (ties keep ref order)
+    let mut sources = HashMap::<String, String>::new();
+    let mut seen = HashSet::<String>::new();
+    let mut left_out = HashSet::<String>::new();
+    let mut queue = VecDeque::<(String, i64)>::new();
+    for (hash, source) in tips {
+        sources
+            .entry(hash.clone())
+            .or_insert_with(|| source.clone());
+        if seen.insert(hash.clone()) {
+            queue.push_back((hash.clone(), load(&mut commits, hash)?));
+        }
+    }
+    for hash in excluded {
+        left_out.insert(hash.clone());
+        if seen.insert(hash.clone()) {
+            queue.push_back((hash.clone(), load(&mut commits, hash)?));
+        }
+    }
+    queue.make_contiguous().sort_by_key(|(_, time)| -time);
+
+    let mut listed = vec![];
+    let mut last_listed = i64::MAX;
+    let mut slop = SLOP;
+    while let Some((hash, time)) = queue.pop_front() {
+        let parents = commits[&hash].parents.clone();
+        let leaving_out = left_out.contains(&hash);
+        let source = sources.get(&hash).cloned().unwrap_or_default();
+        for parent in &parents {
+            match leaving_out {
+                true => leave_out(&commits, &mut left_out, parent),
+                false => {
+                    sources
+                        .entry(parent.clone())
+                        .or_insert_with(|| source.clone());
+                }
+            }
+            if seen.insert(parent.clone()) {
+                let time = load(&mut commits, parent)?;
+                // after every queued commit at least as new
+                let at = queue
+                    .iter()
+                    .position(|(_, queued)| *queued < time)
+                    .unwrap_or(queue.len());
+                queue.insert(at, (parent.clone(), time));
+            }
+        }
+        if !leaving_out {
+            last_listed = time;
+            listed.push(hash);
+            continue;
+        }
+        // once only left out commits older than the last listed one are
+        // queued, a few more are walked in case they leave out listed ones
+        slop = match queue.front() {
+            None => 0,
+            Some((_, newest)) if *newest >= last_listed => SLOP,
+            Some(_) if queue.iter().any(|(hash, _)| !left_out.contains(hash)) => SLOP,
+            Some(_) => slop - 1,
+        };
+        if slop == 0 {
+            break;
+        }
+    }
+
+    let mut out = String::new();
+    let mut count = 0;
+    for hash in listed.iter().filter(|hash| !left_out.contains(*hash)) {
+        let commit = commits.remove(hash).unwrap();
+        let (subject, body) = commit.subject_and_body();
+        let names = decorated.get(hash.as_str()).cloned().unwrap_or_default();
+        out.push_str(&format!(
+            "name=\n{}\nbranch=\n{}\ncommit=\n{}\ncommit-time=\n{}\nauthor=\n{}\ndec=\n{}\nparent=\n{}\nsub=\n{}\nbody=\n{}\u{1e}\n\n",
+            sanitized_subject(split_line(skip_blank_lines(&commit.message)).0),
+            sources.get(hash).map(String::as_str).unwrap_or_default(),
+            commit.hash,
+            commit.committer.iso8601(),
+            commit.author.name,
+            decorations(&names, head.as_ref()),
+            commit.parents.join(" "),
+            subject,
+            body,
+        ));
+        if changed_paths {
+            let paths = history.changed_paths(&commit)?;
+            if !paths.is_empty() {
+                out.push('\n');
+                paths.iter().for_each(|path| {
+                    out.push_str(path);
+                    out.push('\n');
+                });
+            }
+        }
+        count += 1;
+    }
+    debug!("Rendered {} commit(s)", count);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod log_tests {
+    use super::{Commit, Signature, sanitized_subject};
+
+    #[test]
+    fn splits_messages_like_git() {
+        let commit = Commit {
+            hash: "a".repeat(40),
+            tree: "b".repeat(40),
+            parents: vec![],
+            author: Signature::parse("A U Thor <a@example.com> 1700000000 -0130").unwrap(),
+            committer: Signature::parse("C <c@example.com> 1700000000 +0000").unwrap(),
+            message: "\nfeat(api): add\n  the thing  \n\n\nbody\n\nRefs: #1\n".to_string(),
+        };
+        assert_eq!(commit.author.name, "A U Thor");
+        assert_eq!(commit.author.offset, -90);
+        assert_eq!(
+            commit.author.to_string(),
+            "A U Thor <a@example.com> 1700000000 -0130"
+        );
+        assert_eq!(commit.author.iso8601(), "2023-11-14T20:43:20-01:30");
+        let (subject, body) = commit.subject_and_body();
+        assert_eq!(subject, "feat(api): add   the thing");
+        assert_eq!(body, "body\n\nRefs: #1\n");
+        assert_eq!(sanitized_subject(&subject), "feat-api-add-the-thing");
+        assert_eq!(sanitized_subject("fix: v1...2 ."), "fix-v1.2");
+    }
+}
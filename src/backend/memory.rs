@@ -0,0 +1,435 @@
+//! A repository held in memory, for computing versions without a repository
+//! on disk.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::PathBuf,
+    sync::{Mutex, MutexGuard},
+};
+
+use eyre::{Result, eyre};
+
+use super::{
+    GitBackend,
+    log::{self, Commit, Head, History, Ref, Signature},
+    native::odb::{Kind, hash_object},
+};
+use crate::git::{NewTag, PushOutcome};
+
+/// The tree every in-memory commit has.
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// When the first in-memory commit is made. Each later commit is a second
+/// newer, so the history is ordered the way it was built.
+const EPOCH: i64 = 1_700_000_000;
+
+/// A repository built commit by commit, e.g. for tests. Commits all have the
+/// empty tree; the paths they changed are whatever they are given.
+#[derive(Debug)]
+pub struct MemoryBackend {
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    /// Every commit with the paths it changed.
+    commits: HashMap<String, (Commit, Vec<String>)>,
+    refs: BTreeMap<String, Ref>,
+    /// The message of every annotated tag, by tag name.
+    tag_messages: HashMap<String, String>,
+    /// The full name of the branch HEAD is on, or its commit when HEAD is
+    /// detached.
+    head: String,
+    dirty: bool,
+    time: i64,
+}
+
+impl MemoryBackend {
+    /// An empty repository with HEAD on the unborn `branch`.
+    pub fn new(branch: &str) -> MemoryBackend {
+        MemoryBackend {
+            state: Mutex::new(State {
+                commits: HashMap::new(),
+                refs: BTreeMap::new(),
+                tag_messages: HashMap::new(),
+                head: format!("refs/heads/{}", branch),
+                dirty: false,
+                time: EPOCH,
+            }),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// Commits `message` on the current branch and returns its hash.
+    pub fn commit(&self, message: &str) -> String {
+        self.commit_with_paths(message, &[])
+    }
+
+    /// Commits `message` on the current branch, changing `paths`, and
+    /// returns its hash.
+    pub fn commit_with_paths(&self, message: &str, paths: &[&str]) -> String {
+        let mut state = self.state();
+        let parents = state.head_commit().into_iter().collect();
+        state.commit(message, parents, paths)
+    }
+
+    /// Creates the branch `name` at HEAD.
+    pub fn branch(&self, name: &str) -> Result<()> {
+        let mut state = self.state();
+        let head = state
+            .head_commit()
+            .ok_or_else(|| eyre!("HEAD has no commit to branch {} from", name))?;
+        let full = format!("refs/heads/{}", name);
+        if state.refs.contains_key(&full) {
+            return Err(eyre!("Branch {} already exists", name));
+        }
+        state.set_ref(&full, head, None);
+        Ok(())
+    }
+
+    /// Moves HEAD to the branch `name`.
+    pub fn checkout(&self, name: &str) -> Result<()> {
+        let mut state = self.state();
+        let full = format!("refs/heads/{}", name);
+        if !state.refs.contains_key(&full) {
+            return Err(eyre!("No branch {}", name));
+        }
+        state.head = full;
+        Ok(())
+    }
+
+    /// Detaches HEAD at `commit`, as checking out a tag or a commit does.
+    pub fn detach(&self, commit: &str) -> Result<()> {
+        let mut state = self.state();
+        if !state.commits.contains_key(commit) {
+            return Err(eyre!("No commit {}", commit));
+        }
+        state.head = commit.to_string();
+        Ok(())
+    }
+
+    /// Merges the branch `name` into the current one with a merge commit and
+    /// returns its hash.
+    pub fn merge(&self, name: &str, message: &str) -> Result<String> {
+        let mut state = self.state();
+        let head = state
+            .head_commit()
+            .ok_or_else(|| eyre!("HEAD has no commit to merge {} into", name))?;
+        let branch = state
+            .refs
+            .get(&format!("refs/heads/{}", name))
+            .map(|r| r.target.clone())
+            .ok_or_else(|| eyre!("No branch {}", name))?;
+        Ok(state.commit(message, vec![head, branch], &[]))
+    }
+
+    /// Tags HEAD with the lightweight tag `name`.
+    pub fn tag(&self, name: &str) -> Result<()> {
+        let mut state = self.state();
+        let head = state
+            .head_commit()
+            .ok_or_else(|| eyre!("HEAD has no commit to tag {}", name))?;
+        let full = format!("refs/tags/{}", name);
+        if state.refs.contains_key(&full) {
+            return Err(eyre!("Tag {} already exists", name));
+        }
+        state.set_ref(&full, head, None);
+        Ok(())
+    }
+
+    /// Whether the work tree has unstaged changes.
+    pub fn set_dirty(&self, dirty: bool) {
+        self.state().dirty = dirty;
+    }
+}
+
+impl State {
+    fn head_commit(&self) -> Option<String> {
+        match self.is_detached() {
+            true => Some(self.head.clone()),
+            false => self.refs.get(&self.head).map(|r| r.target.clone()),
+        }
+    }
+
+    fn is_detached(&self) -> bool {
+        !self.head.starts_with("refs/")
+    }
+
+    fn set_ref(&mut self, name: &str, target: String, peeled: Option<String>) {
+        self.refs.insert(
+            name.to_string(),
+            Ref {
+                name: name.to_string(),
+                target,
+                peeled,
+            },
+        );
+    }
+
+    fn signature(&self) -> Signature {
+        Signature {
+            name: "A U Thor".to_string(),
+            email: "author@example.com".to_string(),
+            time: self.time,
+            offset: 0,
+        }
+    }
+
+    fn commit(&mut self, message: &str, parents: Vec<String>, paths: &[&str]) -> String {
+        let mut commit = Commit {
+            hash: String::new(),
+            tree: EMPTY_TREE.to_string(),
+            parents,
+            author: self.signature(),
+            committer: self.signature(),
+            message: format!("{}\n", message.trim_end()),
+        };
+        commit.hash = hash_object(Kind::Commit, commit.to_object().as_bytes());
+        self.time += 1;
+        match self.is_detached() {
+            true => self.head = commit.hash.clone(),
+            false => {
+                let head = self.head.clone();
+                self.set_ref(&head, commit.hash.clone(), None);
+            }
+        }
+        let paths = paths.iter().map(|path| path.to_string()).collect();
+        self.commits
+            .insert(commit.hash.clone(), (commit.clone(), paths));
+        commit.hash
+    }
+}
+
+impl History for MemoryBackend {
+    fn commit(&self, hash: &str) -> Result<Option<Commit>> {
+        Ok(self
+            .state()
+            .commits
+            .get(hash)
+            .map(|(commit, _)| commit.clone()))
+    }
+
+    fn changed_paths(&self, commit: &Commit) -> Result<Vec<String>> {
+        Ok(self
+            .state()
+            .commits
+            .get(&commit.hash)
+            .map(|(_, paths)| paths.clone())
+            .unwrap_or_default())
+    }
+
+    fn refs(&self) -> Result<Vec<Ref>> {
+        Ok(self.state().refs.values().cloned().collect())
+    }
+
+    fn head(&self) -> Result<Option<Head>> {
+        let state = self.state();
+        Ok(Some(match state.is_detached() {
+            true => Head::Detached(state.head.clone()),
+            false => Head::Branch(state.head.clone(), state.head_commit()),
+        }))
+    }
+}
+
+impl GitBackend for MemoryBackend {
+    fn log(&self, changed_paths: bool, tip: Option<&str>) -> Result<String> {
+        log::render_log(self, changed_paths, tip, &[])
+    }
+
+    fn log_after(
+        &self,
+        changed_paths: bool,
+        tip: Option<&str>,
+        boundaries: &[String],
+    ) -> Result<String> {
+        log::render_log(self, changed_paths, tip, boundaries)
+    }
+
+    fn log_range(&self, range: &str) -> Result<String> {
+        log::render_range(self, range)
+    }
+
+    fn resolve(&self, rev: &str) -> Result<String> {
+        log::resolve(self, rev)
+    }
+
+    fn is_dirty(&self) -> Result<bool> {
+        Ok(self.state().dirty)
+    }
+
+    fn head_hash(&self) -> Result<String> {
+        self.state()
+            .head_commit()
+            .ok_or_else(|| eyre!("HEAD does not point at a commit"))
+    }
+
+    fn current_branch(&self) -> Result<String> {
+        let state = self.state();
+        Ok(match state.is_detached() {
+            true => "HEAD".to_string(),
+            false => state
+                .head
+                .strip_prefix("refs/heads/")
+                .unwrap_or(&state.head)
+                .to_string(),
+        })
+    }
+
+    fn tag_targets(&self) -> Result<HashMap<String, String>> {
+        Ok(self
+            .state()
+            .refs
+            .values()
+            .filter_map(|r| {
+                Some((
+                    r.name.strip_prefix("refs/tags/")?.to_string(),
+                    r.commit().to_string(),
+                ))
+            })
+            .collect())
+    }
+
+    fn branches(&self) -> Result<Vec<String>> {
+        log::branches(self)
+    }
+
+    fn branch_tags(&self) -> Result<HashMap<String, (String, i64)>> {
+        log::branch_tags(self)
+    }
+
+    fn common_dir(&self) -> Result<Option<PathBuf>> {
+        Ok(None)
+    }
+
+    fn tag_messages(&self) -> Result<HashMap<String, String>> {
+        Ok(self.state().tag_messages.clone())
+    }
+
+    fn hooks_dir(&self) -> Result<PathBuf> {
+        Err(eyre!("The memory backend has no hooks directory"))
+    }
+
+    fn create_tags(&self, tags: &[NewTag]) -> Result<()> {
+        let mut state = self.state();
+        let mut batch = HashSet::new();
+        if let Some(tag) = tags.iter().find(|tag| {
+            state.refs.contains_key(&format!("refs/tags/{}", tag.tag)) || !batch.insert(tag.tag)
+        }) {
+            return Err(eyre!(
+                "Failed to create tags, none were created: tag {} already exists",
+                tag.tag
+            ));
+        }
+        for tag in tags {
+            if !state.commits.contains_key(tag.hash) {
+                return Err(eyre!(
+                    "Failed to create tags, none were created: no commit {}",
+                    tag.hash
+                ));
+            }
+            if tag.annotation.is_some_and(|annotation| annotation.sign) {
+                return Err(eyre!("The memory backend cannot sign tags"));
+            }
+        }
+        for tag in tags {
+            let name = format!("refs/tags/{}", tag.tag);
+            match tag.annotation {
+                Some(annotation) => {
+                    let object = format!(
+                        "object {}\ntype commit\ntag {}\ntagger {}\n\n{}",
+                        tag.hash,
+                        tag.tag,
+                        state.signature(),
+                        annotation.message
+                    );
+                    let target = hash_object(Kind::Tag, object.as_bytes());
+                    state.set_ref(&name, target, Some(tag.hash.to_string()));
+                    state.tag_messages.insert(
+                        tag.tag.to_string(),
+                        annotation.message.trim_end().to_string(),
+                    );
+                }
+                None => state.set_ref(&name, tag.hash.to_string(), None),
+            }
+        }
+        Ok(())
+    }
+
+    fn push_tags(&self, remote: &str, tags: &[&str]) -> Result<HashMap<String, PushOutcome>> {
+        match tags.is_empty() {
+            true => Ok(HashMap::new()),
+            false => Err(eyre!("The memory backend has no remote {}", remote)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod memory_tests {
+    use super::MemoryBackend;
+    use crate::{
+        backend::GitBackend as _,
+        config::CCVerConfig,
+        git::{NewTag, TagAnnotation},
+        graph::MemoizedCommitGraph,
+        logs::{InfersVersionFormat as _, Logs},
+    };
+
+    #[test]
+    fn computes_versions_without_a_repository() {
+        let repo = MemoryBackend::new("main");
+        repo.commit("feat: initial");
+        repo.tag("v0.1.0").unwrap();
+        repo.branch("feature").unwrap();
+        repo.checkout("feature").unwrap();
+        repo.commit("feat: something new");
+        repo.checkout("main").unwrap();
+        repo.commit("fix: a bug");
+        let merge = repo.merge("feature", "Merge branch 'feature'").unwrap();
+        assert!(repo.checkout("no-such-branch").is_err());
+        assert!(repo.merge("no-such-branch", "Merge").is_err());
+        assert!(repo.tag("v0.1.0").is_err());
+
+        let config = CCVerConfig::default();
+        let raw = repo.log(false, None).unwrap();
+        let logs = Logs::from_log_str(&raw).unwrap();
+        assert_eq!(logs.len(), 4);
+        let version_format = logs.infer_version_format();
+        let graph = MemoizedCommitGraph::new(logs, &version_format, &config).unwrap();
+        let head = graph.head().unwrap();
+        let head = head.lock().unwrap();
+        assert_eq!(head.log_entry.commit_hash, merge);
+        assert_eq!(head.version.as_ref().unwrap().to_string(), "v0.2.0");
+        // the fix, the feature and the merge
+        let range = repo.log_range("v0.1.0..main").unwrap();
+        assert_eq!(Logs::from_log_str(&range).unwrap().len(), 3);
+        assert!(repo.log_range("main..").unwrap().is_empty());
+
+        assert!(!repo.is_dirty().unwrap());
+        repo.set_dirty(true);
+        assert!(repo.is_dirty().unwrap());
+
+        let tag = |tag| NewTag {
+            hash: &merge,
+            tag,
+            annotation: None,
+        };
+        assert!(repo.create_tags(&[tag("v0.2.0"), tag("v0.1.0")]).is_err());
+        assert!(repo.create_tags(&[tag("v0.2.0"), tag("v0.2.0")]).is_err());
+        assert!(!repo.tag_targets().unwrap().contains_key("v0.2.0"));
+        let annotation = TagAnnotation {
+            message: "## v0.2.0\n".to_string(),
+            sign: false,
+        };
+        repo.create_tags(&[NewTag {
+            annotation: Some(&annotation),
+            ..tag("v0.2.0")
+        }])
+        .unwrap();
+        assert_eq!(repo.tag_targets().unwrap()["v0.2.0"], merge);
+        assert_eq!(repo.tag_messages().unwrap()["v0.2.0"], "## v0.2.0");
+        assert!(repo.push_tags("origin", &["v0.2.0"]).is_err());
+    }
+}
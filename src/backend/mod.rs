@@ -0,0 +1,205 @@
+//! Where ccver reads history from and creates tags: the `git` binary
+//! ([`SubprocessBackend`]), the `.git` directory read directly
+//! ([`NativeBackend`]) or a repository held in memory ([`MemoryBackend`]).
+
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use eyre::Result;
+use tracing::{info, instrument, warn};
+
+use crate::git::{self, NewTag, PushOutcome};
+
+pub mod log;
+pub mod memory;
+pub mod native;
+
+pub use memory::MemoryBackend;
+pub use native::NativeBackend;
+
+/// The repository operations versioning and tagging need.
+pub trait GitBackend: Debug {
+    /// The history of every branch, and of the commit `tip` when it is
+    /// given, formatted the way `git log` prints it with
+    /// [`GIT_FORMAT_ARGS`](crate::logs::GIT_FORMAT_ARGS), and with
+    /// [`GIT_CHANGED_PATHS_ARGS`](crate::logs::GIT_CHANGED_PATHS_ARGS) when
+    /// `changed_paths` is set.
+    fn log(&self, changed_paths: bool, tip: Option<&str>) -> Result<String>;
+
+    /// Like [`GitBackend::log`], but without the history `boundaries` are
+    /// built on: each boundary commit is listed, its ancestors are not.
+    fn log_after(
+        &self,
+        changed_paths: bool,
+        tip: Option<&str>,
+        boundaries: &[String],
+    ) -> Result<String>;
+
+    /// The commits in `range` (`<from>..<to>`, or a single revision and its
+    /// history), formatted like [`GitBackend::log`] but without the other
+    /// branches. Empty when the range is.
+    fn log_range(&self, range: &str) -> Result<String>;
+
+    /// The commit `rev` names, like `git rev-parse <rev>^{commit}`.
+    fn resolve(&self, rev: &str) -> Result<String>;
+
+    /// Whether the work tree has changes that are not staged, like
+    /// `git diff --exit-code`.
+    fn is_dirty(&self) -> Result<bool>;
+
+    /// The commit HEAD points at.
+    fn head_hash(&self) -> Result<String>;
+
+    /// The branch HEAD is on, or `HEAD` when it is detached.
+    fn current_branch(&self) -> Result<String>;
+
+    /// The commit every tag points at, by tag name.
+    fn tag_targets(&self) -> Result<HashMap<String, String>>;
+
+    /// The name of every branch, like `git branch --list`.
+    fn branches(&self) -> Result<Vec<String>>;
+
+    /// The tags reachable from a branch, by name, with the commit each
+    /// points at and that commit's committer time.
+    fn branch_tags(&self) -> Result<HashMap<String, (String, i64)>>;
+
+    /// The git directory every worktree shares, where ccver keeps its
+    /// [`VersionCache`](crate::cache::VersionCache), or `None` when the
+    /// repository is not on disk.
+    fn common_dir(&self) -> Result<Option<PathBuf>>;
+
+    /// The message of every annotated tag, without its signature, by tag
+    /// name.
+    fn tag_messages(&self) -> Result<HashMap<String, String>>;
+
+    /// The directory git runs hooks from, honouring `core.hooksPath` and
+    /// worktrees.
+    fn hooks_dir(&self) -> Result<PathBuf>;
+
+    /// Creates all of `tags` or none of them, failing if any already exists
+    /// or a name is given twice.
+    fn create_tags(&self, tags: &[NewTag]) -> Result<()>;
+
+    /// Pushes `tags` to `remote`, with the outcome for each tag as
+    /// [`git::push_tags`] reports it.
+    fn push_tags(&self, remote: &str, tags: &[&str]) -> Result<HashMap<String, PushOutcome>>;
+}
+
+/// Which [`GitBackend`] `--git-backend` selects.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BackendKind {
+    /// `git` when it is installed, `native` otherwise.
+    #[default]
+    Auto,
+    Git,
+    Native,
+}
+
+/// Opens the repository at `path` with the backend `kind` selects.
+#[instrument]
+pub fn open(kind: BackendKind, path: &Path) -> Result<Box<dyn GitBackend>> {
+    match kind {
+        BackendKind::Git => {
+            git::git_installed()?;
+            Ok(Box::new(SubprocessBackend::new(path)))
+        }
+        BackendKind::Native => Ok(Box::new(NativeBackend::open(path)?)),
+        BackendKind::Auto => match git::git_installed() {
+            Ok(()) => Ok(Box::new(SubprocessBackend::new(path))),
+            Err(e) => {
+                warn!(error = %e, "git is not installed, reading the repository directly");
+                Ok(Box::new(NativeBackend::open(path)?))
+            }
+        },
+    }
+}
+
+/// Runs the `git` binary in the repository.
+#[derive(Debug, Clone)]
+pub struct SubprocessBackend {
+    path: PathBuf,
+}
+
+impl SubprocessBackend {
+    pub fn new(path: &Path) -> Self {
+        info!(?path, "Using the git binary");
+        SubprocessBackend {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl GitBackend for SubprocessBackend {
+    fn log(&self, changed_paths: bool, tip: Option<&str>) -> Result<String> {
+        match (changed_paths, tip) {
+            (_, Some(_)) => git::formatted_logs_after(&self.path, changed_paths, tip, &[]),
+            (true, None) => git::formatted_logs_with_changed_paths(&self.path),
+            (false, None) => git::formatted_logs(&self.path),
+        }
+    }
+
+    fn log_after(
+        &self,
+        changed_paths: bool,
+        tip: Option<&str>,
+        boundaries: &[String],
+    ) -> Result<String> {
+        git::formatted_logs_after(&self.path, changed_paths, tip, boundaries)
+    }
+
+    fn log_range(&self, range: &str) -> Result<String> {
+        git::formatted_logs_in_range(&self.path, range)
+    }
+
+    fn resolve(&self, rev: &str) -> Result<String> {
+        git::rev_parse(&self.path, rev)
+    }
+
+    fn is_dirty(&self) -> Result<bool> {
+        git::is_dirty(&self.path)
+    }
+
+    fn head_hash(&self) -> Result<String> {
+        Ok(git::head_hash(&self.path)?.trim().to_string())
+    }
+
+    fn current_branch(&self) -> Result<String> {
+        git::current_branch(&self.path)
+    }
+
+    fn tag_targets(&self) -> Result<HashMap<String, String>> {
+        git::tag_targets(&self.path)
+    }
+
+    fn branches(&self) -> Result<Vec<String>> {
+        git::branches(&self.path)
+    }
+
+    fn branch_tags(&self) -> Result<HashMap<String, (String, i64)>> {
+        git::branch_tags(&self.path)
+    }
+
+    fn common_dir(&self) -> Result<Option<PathBuf>> {
+        git::common_dir(&self.path).map(Some)
+    }
+
+    fn tag_messages(&self) -> Result<HashMap<String, String>> {
+        git::tag_messages(&self.path)
+    }
+
+    fn hooks_dir(&self) -> Result<PathBuf> {
+        git::hooks_dir(&self.path)
+    }
+
+    fn create_tags(&self, tags: &[NewTag]) -> Result<()> {
+        git::create_tags(tags, &self.path)
+    }
+
+    fn push_tags(&self, remote: &str, tags: &[&str]) -> Result<HashMap<String, PushOutcome>> {
+        git::push_tags(remote, tags, &self.path)
+    }
+}
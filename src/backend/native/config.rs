@@ -0,0 +1,176 @@
+//! Reads the git config files the native backend needs values from.
+
+use std::path::{Path, PathBuf};
+
+use tracing::debug;
+
+/// Values from the system, global and repository config files, later files
+/// overriding earlier ones. Keys are `section.key` or
+/// `section.subsection.key`, with the section and key lowercased.
+#[derive(Debug, Clone, Default)]
+pub struct GitConfig {
+    values: Vec<(String, String)>,
+}
+
+impl GitConfig {
+    pub fn load(common_dir: &Path) -> GitConfig {
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        let xdg = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home| home.join(".config")));
+        let files = [
+            std::env::var_os("GIT_CONFIG_SYSTEM")
+                .map(PathBuf::from)
+                .or_else(|| Some(PathBuf::from("/etc/gitconfig"))),
+            xdg.map(|xdg| xdg.join("git/config")),
+            std::env::var_os("GIT_CONFIG_GLOBAL")
+                .map(PathBuf::from)
+                .or_else(|| home.map(|home| home.join(".gitconfig"))),
+            Some(common_dir.join("config")),
+        ];
+        let mut config = GitConfig::default();
+        for file in files.into_iter().flatten() {
+            if let Ok(contents) = std::fs::read_to_string(&file) {
+                debug!(?file, "Reading git config");
+                config.values.extend(parse(&contents));
+            }
+        }
+        config
+    }
+
+    /// The last value of `key`, e.g. `user.name` or `gpg.ssh.program`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        let key = normalize(key);
+        self.values
+            .iter()
+            .rev()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn bool(&self, key: &str) -> Option<bool> {
+        self.get(key).map(|value| {
+            !matches!(
+                value.to_ascii_lowercase().as_str(),
+                "false" | "no" | "off" | "0" | ""
+            )
+        })
+    }
+}
+
+/// Lowercases the section and key of `section[.subsection].key`.
+fn normalize(key: &str) -> String {
+    match (key.split_once('.'), key.rsplit_once('.')) {
+        (Some((section, _)), Some((middle, name))) if middle.len() > section.len() => format!(
+            "{}{}.{}",
+            section.to_ascii_lowercase(),
+            &middle[section.len()..],
+            name.to_ascii_lowercase()
+        ),
+        _ => key.to_ascii_lowercase(),
+    }
+}
+
+/// `key = value` pairs of a config file, named by [`normalize`]d keys. Keys
+/// without a value are `true`.
+fn parse(contents: &str) -> Vec<(String, String)> {
+    let mut values = vec![];
+    let mut section = String::new();
+    let mut lines = contents.lines();
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header.split(']').next().unwrap_or_default();
+            section = match header.split_once(' ') {
+                Some((name, subsection)) => format!(
+                    "{}.{}",
+                    name.to_ascii_lowercase(),
+                    subsection.trim().trim_matches('"').replace("\\\"", "\"")
+                ),
+                None => match header.split_once('.') {
+                    // the deprecated [section.subsection]
+                    Some((name, subsection)) => {
+                        format!(
+                            "{}.{}",
+                            name.to_ascii_lowercase(),
+                            subsection.to_ascii_lowercase()
+                        )
+                    }
+                    None => header.to_ascii_lowercase(),
+                },
+            };
+            continue;
+        }
+        if line.is_empty() || line.starts_with(['#', ';']) || section.is_empty() {
+            continue;
+        }
+        let (name, raw) = line.split_once('=').unwrap_or((line, ""));
+        let key = format!("{}.{}", section, name.trim().to_ascii_lowercase());
+        if !line.contains('=') {
+            values.push((key, "true".to_string()));
+            continue;
+        }
+        let mut raw = raw.to_string();
+        while raw.ends_with('\\') && !raw.ends_with("\\\\") {
+            raw.pop();
+            raw.push_str(lines.next().unwrap_or_default());
+        }
+        values.push((key, value(&raw)));
+    }
+    values
+}
+
+/// A value with its quotes, escapes and trailing comment resolved.
+fn value(raw: &str) -> String {
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = raw.trim().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('b') => {
+                    value.pop();
+                }
+                Some(c) => value.push(c),
+                None => {}
+            },
+            '#' | ';' if !quoted => break,
+            c => value.push(c),
+        }
+    }
+    match quoted {
+        true => value,
+        false => value.trim_end().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::{GitConfig, parse};
+
+    #[test]
+    fn reads_sections_and_values() {
+        let config = GitConfig {
+            values: parse(indoc::indoc! {r#"
+                [user]
+                    name = "A U Thor" ; who
+                    signingKey = ~/.ssh/id.pub
+                [gpg "ssh"]
+                    program = /usr/bin/ssh-keygen
+                [Tag]
+                    gpgSign
+                [core]
+                    fileMode = false
+            "#}),
+        };
+        assert_eq!(config.get("user.name"), Some("A U Thor"));
+        assert_eq!(config.get("user.signingkey"), Some("~/.ssh/id.pub"));
+        assert_eq!(config.get("gpg.ssh.program"), Some("/usr/bin/ssh-keygen"));
+        assert_eq!(config.bool("tag.gpgSign"), Some(true));
+        assert_eq!(config.bool("core.filemode"), Some(false));
+        assert_eq!(config.get("gpg.format"), None);
+    }
+}
@@ -0,0 +1,472 @@
+//! The paths a commit changed, as `git log --name-only` lists them: tree
+//! differences with renamed files listed only at their new path.
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use eyre::{Result, eyre};
+
+use super::odb::{Kind, ObjectDatabase, to_hex};
+
+/// git's `MAX_SCORE`: a similarity score of 100%.
+const MAX_SCORE: u64 = 60000;
+/// The similarity a file needs to count as renamed, 50% like `-M`.
+const RENAME_SCORE: u64 = MAX_SCORE / 2;
+/// Files keeping their name only need 75% similarity.
+const BASENAME_SCORE: u64 = RENAME_SCORE + (MAX_SCORE - RENAME_SCORE) / 2;
+/// How many sources each destination remembers in the similarity matrix.
+const CANDIDATES_PER_DESTINATION: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TreeEntry {
+    name: Vec<u8>,
+    mode: u32,
+    hash: String,
+}
+
+impl TreeEntry {
+    fn is_tree(&self) -> bool {
+        self.mode & 0o170000 == 0o040000
+    }
+
+    /// The order of entries in a tree, where a tree's name sorts as if it
+    /// ended in `/`.
+    fn cmp(&self, other: &TreeEntry) -> Ordering {
+        let len = self.name.len().min(other.name.len());
+        self.name[..len].cmp(&other.name[..len]).then_with(|| {
+            let end = |entry: &TreeEntry| match entry.is_tree() {
+                true => b'/',
+                false => 0,
+            };
+            let this = self.name.get(len).copied().unwrap_or_else(|| end(self));
+            let that = other.name.get(len).copied().unwrap_or_else(|| end(other));
+            this.cmp(&that)
+        })
+    }
+}
+
+/// A file as one side of a change: its mode and blob.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct File {
+    mode: u32,
+    hash: String,
+}
+
+impl File {
+    fn is_regular(&self) -> bool {
+        self.mode & 0o170000 == 0o100000
+    }
+}
+
+/// A path that differs between two trees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Change {
+    path: String,
+    old: Option<File>,
+    new: Option<File>,
+}
+
+fn tree(odb: &ObjectDatabase, hash: Option<&str>) -> Result<Vec<TreeEntry>> {
+    let Some(hash) = hash else {
+        return Ok(vec![]);
+    };
+    let data = match odb.read(hash)? {
+        Some((Kind::Tree, data)) => data,
+        _ => return Err(eyre!("Tree {} is missing from the repository", hash)),
+    };
+    let corrupt = || eyre!("Corrupt tree {}", hash);
+    let mut entries = vec![];
+    let mut at = 0;
+    while at < data.len() {
+        let space = at
+            + data[at..]
+                .iter()
+                .position(|byte| *byte == b' ')
+                .ok_or_else(corrupt)?;
+        let nul = space
+            + data[space..]
+                .iter()
+                .position(|byte| *byte == 0)
+                .ok_or_else(corrupt)?;
+        entries.push(TreeEntry {
+            mode: u32::from_str_radix(&String::from_utf8_lossy(&data[at..space]), 8)?,
+            name: data[space + 1..nul].to_vec(),
+            hash: to_hex(data.get(nul + 1..nul + 21).ok_or_else(corrupt)?),
+        });
+        at = nul + 21;
+    }
+    Ok(entries)
+}
+
+/// The files that differ between two trees, in tree order.
+fn diff_trees(
+    odb: &ObjectDatabase,
+    old: Option<&str>,
+    new: Option<&str>,
+    prefix: &str,
+    changes: &mut Vec<Change>,
+) -> Result<()> {
+    let (old, new) = (tree(odb, old)?, tree(odb, new)?);
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        let order = match (old.get(i), new.get(j)) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => Ordering::Less,
+            _ => Ordering::Greater,
+        };
+        let (a, b) = match order {
+            Ordering::Less => (old.get(i), None),
+            Ordering::Greater => (None, new.get(j)),
+            Ordering::Equal => (old.get(i), new.get(j)),
+        };
+        i += a.is_some() as usize;
+        j += b.is_some() as usize;
+        if let (Some(a), Some(b)) = (a, b)
+            && a.mode == b.mode
+            && a.hash == b.hash
+        {
+            continue;
+        }
+        let entry = a.or(b).unwrap();
+        let path = format!("{}{}", prefix, String::from_utf8_lossy(&entry.name));
+        if entry.is_tree() {
+            let hash = |entry: Option<&TreeEntry>| entry.map(|entry| entry.hash.clone());
+            diff_trees(
+                odb,
+                hash(a).as_deref(),
+                hash(b).as_deref(),
+                &format!("{}/", path),
+                changes,
+            )?;
+            continue;
+        }
+        let file = |entry: Option<&TreeEntry>| {
+            entry.map(|entry| File {
+                mode: entry.mode,
+                hash: entry.hash.clone(),
+            })
+        };
+        changes.push(Change {
+            path,
+            old: file(a),
+            new: file(b),
+        });
+    }
+    Ok(())
+}
+
+/// The paths that differ from the tree `old` (`None` for a root commit) to
+/// the tree `new`. With `renames`, files git would detect as renamed are
+/// only listed at their new path; `rename_limit` caps the sources times
+/// destinations compared by content, like `diff.renameLimit`.
+pub fn changed_paths(
+    odb: &ObjectDatabase,
+    old: Option<&str>,
+    new: &str,
+    renames: bool,
+    rename_limit: usize,
+) -> Result<Vec<String>> {
+    let mut changes = vec![];
+    diff_trees(odb, old, Some(new), "", &mut changes)?;
+    let renamed = match renames {
+        true => Renames::new(odb, &changes).detect(rename_limit)?,
+        false => vec![false; changes.len()],
+    };
+    Ok(changes
+        .into_iter()
+        .zip(renamed)
+        .filter(|(_, renamed)| !renamed)
+        .map(|(change, _)| change.path)
+        .collect())
+}
+
+/// The path after its last `/`.
+fn basename(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Whether both paths end in the same file name.
+fn basename_same(a: &str, b: &str) -> bool {
+    basename(a) == basename(b)
+}
+
+/// How many bytes of each chunk of a file there are, by chunk hash: lines,
+/// or 64-byte pieces of longer ones. git's `hash_chars`.
+fn spans(data: &[u8]) -> HashMap<u32, u64> {
+    const HASHBASE: u32 = 107927;
+    let text = !data[..data.len().min(8000)].contains(&0);
+    let mut spans = HashMap::new();
+    let (mut accum1, mut accum2, mut n) = (0u32, 0u32, 0u64);
+    for (i, &c) in data.iter().enumerate() {
+        // a CR ending a line of text doesn't count
+        if text && c == b'\r' && data.get(i + 1) == Some(&b'\n') {
+            continue;
+        }
+        let old = accum1;
+        accum1 = (accum1 << 7) ^ (accum2 >> 25);
+        accum2 = (accum2 << 7) ^ (old >> 25);
+        accum1 = accum1.wrapping_add(c as u32);
+        n += 1;
+        if n < 64 && c != b'\n' {
+            continue;
+        }
+        *spans
+            .entry(accum1.wrapping_add(accum2.wrapping_mul(0x61)) % HASHBASE)
+            .or_default() += n;
+        (accum1, accum2, n) = (0, 0, 0);
+    }
+    if n > 0 {
+        *spans
+            .entry(accum1.wrapping_add(accum2.wrapping_mul(0x61)) % HASHBASE)
+            .or_default() += n;
+    }
+    spans
+}
+
+/// A source a destination may have been renamed from.
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    score: u64,
+    same_name: bool,
+    destination: usize,
+    source: usize,
+}
+
+/// git's `score_compare`: better candidates sort first, empty slots last.
+fn compare(a: &Option<Candidate>, b: &Option<Candidate>) -> Ordering {
+    match (a, b) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => b.score.cmp(&a.score).then(b.same_name.cmp(&a.same_name)),
+    }
+}
+
+/// git's rename detection (`diffcore-rename`) over the changes between two
+/// trees.
+struct Renames<'a> {
+    odb: &'a ObjectDatabase,
+    changes: &'a [Change],
+    /// Deleted files, by index into `changes`.
+    sources: Vec<usize>,
+    /// Added files, by index into `changes`.
+    destinations: Vec<usize>,
+    used: Vec<bool>,
+    renamed: Vec<bool>,
+    blobs: HashMap<String, Vec<u8>>,
+    spans: HashMap<String, HashMap<u32, u64>>,
+}
+
+impl<'a> Renames<'a> {
+    fn new(odb: &'a ObjectDatabase, changes: &'a [Change]) -> Renames<'a> {
+        let indices = |side: fn(&Change) -> bool| {
+            changes
+                .iter()
+                .enumerate()
+                .filter(|(_, change)| side(change))
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>()
+        };
+        let sources = indices(|change| change.old.is_some() && change.new.is_none());
+        let destinations = indices(|change| change.old.is_none() && change.new.is_some());
+        Renames {
+            odb,
+            changes,
+            used: vec![false; sources.len()],
+            renamed: vec![false; destinations.len()],
+            sources,
+            destinations,
+            blobs: HashMap::new(),
+            spans: HashMap::new(),
+        }
+    }
+
+    fn source(&self, source: usize) -> (&str, &File) {
+        let change = &self.changes[self.sources[source]];
+        (&change.path, change.old.as_ref().unwrap())
+    }
+
+    fn destination(&self, destination: usize) -> (&str, &File) {
+        let change = &self.changes[self.destinations[destination]];
+        (&change.path, change.new.as_ref().unwrap())
+    }
+
+    /// Which changes are deletions of a file that was renamed.
+    fn detect(mut self, rename_limit: usize) -> Result<Vec<bool>> {
+        let mut deleted = vec![false; self.changes.len()];
+        if self.sources.is_empty() || self.destinations.is_empty() {
+            return Ok(deleted);
+        }
+        self.exact();
+        self.same_names()?;
+        self.similar(rename_limit)?;
+        for (source, used) in self.used.iter().enumerate() {
+            deleted[self.sources[source]] = *used;
+        }
+        Ok(deleted)
+    }
+
+    /// Files moved unchanged, preferring a source with the same name.
+    fn exact(&mut self) {
+        for destination in 0..self.destinations.len() {
+            let (path, file) = self.destination(destination);
+            let best = (0..self.sources.len())
+                .filter(|&source| {
+                    let (_, old) = self.source(source);
+                    !self.used[source]
+                        && old.hash == file.hash
+                        && ((old.is_regular() && file.is_regular()) || old.mode == file.mode)
+                })
+                .min_by_key(|&source| !basename_same(self.source(source).0, path));
+            if let Some(source) = best {
+                self.used[source] = true;
+                self.renamed[destination] = true;
+            }
+        }
+    }
+
+    /// Files moved to another directory, when only one file of that name was
+    /// deleted and only one added.
+    fn same_names(&mut self) -> Result<()> {
+        let unique = |paths: Vec<(usize, &'a str)>| {
+            let mut unique = HashMap::<&str, Option<usize>>::new();
+            for (i, path) in paths {
+                unique
+                    .entry(basename(path))
+                    .and_modify(|index| *index = None)
+                    .or_insert(Some(i));
+            }
+            unique
+        };
+        let changes = self.changes;
+        let sources = unique(
+            (0..self.sources.len())
+                .filter(|&source| !self.used[source])
+                .map(|source| (source, changes[self.sources[source]].path.as_str()))
+                .collect(),
+        );
+        let destinations = unique(
+            (0..self.destinations.len())
+                .filter(|&destination| !self.renamed[destination])
+                .map(|destination| {
+                    (
+                        destination,
+                        changes[self.destinations[destination]].path.as_str(),
+                    )
+                })
+                .collect(),
+        );
+        for (name, source) in sources {
+            let (Some(source), Some(Some(destination))) = (source, destinations.get(name)) else {
+                continue;
+            };
+            if self.similarity(source, *destination, BASENAME_SCORE)? >= BASENAME_SCORE {
+                self.used[source] = true;
+                self.renamed[*destination] = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Files renamed with changes, most similar pairs first.
+    fn similar(&mut self, rename_limit: usize) -> Result<()> {
+        let sources = (0..self.sources.len())
+            .filter(|&source| !self.used[source])
+            .collect::<Vec<_>>();
+        let destinations = (0..self.destinations.len())
+            .filter(|&destination| !self.renamed[destination])
+            .collect::<Vec<_>>();
+        if sources.is_empty()
+            || destinations.is_empty()
+            || (rename_limit > 0
+                && sources.len() * destinations.len() > rename_limit * rename_limit)
+        {
+            return Ok(());
+        }
+        let mut matrix = vec![];
+        for &destination in &destinations {
+            let mut best = [None; CANDIDATES_PER_DESTINATION];
+            for &source in &sources {
+                let candidate = Some(Candidate {
+                    score: self.similarity(source, destination, RENAME_SCORE)?,
+                    same_name: basename_same(
+                        self.source(source).0,
+                        self.destination(destination).0,
+                    ),
+                    destination,
+                    source,
+                });
+                // replaces the worst candidate so far if it is better
+                let mut worst = 0;
+                for i in 1..CANDIDATES_PER_DESTINATION {
+                    if compare(&best[i], &best[worst]) == Ordering::Greater {
+                        worst = i;
+                    }
+                }
+                if compare(&best[worst], &candidate) == Ordering::Greater {
+                    best[worst] = candidate;
+                }
+            }
+            matrix.extend(best);
+        }
+        matrix.sort_by(compare);
+        for candidate in matrix {
+            let Some(candidate) = candidate.filter(|candidate| candidate.score >= RENAME_SCORE)
+            else {
+                break;
+            };
+            if self.renamed[candidate.destination] || self.used[candidate.source] {
+                continue;
+            }
+            self.used[candidate.source] = true;
+            self.renamed[candidate.destination] = true;
+        }
+        Ok(())
+    }
+
+    fn blob(&mut self, hash: &str) -> Result<&[u8]> {
+        if !self.blobs.contains_key(hash) {
+            let data = match self.odb.read(hash)? {
+                Some((Kind::Blob, data)) => data,
+                _ => return Err(eyre!("Blob {} is missing from the repository", hash)),
+            };
+            self.blobs.insert(hash.to_string(), data);
+        }
+        Ok(&self.blobs[hash])
+    }
+
+    /// Caches the [`spans`] of the blob `hash`.
+    fn spans(&mut self, hash: &str) -> Result<()> {
+        if !self.spans.contains_key(hash) {
+            let spans = spans(self.blob(hash)?);
+            self.spans.insert(hash.to_string(), spans);
+        }
+        Ok(())
+    }
+
+    /// How much of the destination comes from the source, out of
+    /// [`MAX_SCORE`]. Pairs whose sizes differ too much for `minimum` to be
+    /// reached score 0. git's `estimate_similarity`.
+    fn similarity(&mut self, source: usize, destination: usize, minimum: u64) -> Result<u64> {
+        let (old, new) = (
+            self.source(source).1.clone(),
+            self.destination(destination).1.clone(),
+        );
+        if !old.is_regular() || !new.is_regular() {
+            return Ok(0);
+        }
+        let old_size = self.blob(&old.hash)?.len() as u64;
+        let new_size = self.blob(&new.hash)?.len() as u64;
+        let (max, min) = (old_size.max(new_size), old_size.min(new_size));
+        if max * (MAX_SCORE - minimum) < (max - min) * MAX_SCORE || new_size == 0 {
+            return Ok(0);
+        }
+        self.spans(&old.hash)?;
+        self.spans(&new.hash)?;
+        let old_spans = &self.spans[&old.hash];
+        let copied = self.spans[&new.hash]
+            .iter()
+            .map(|(hash, count)| old_spans.get(hash).map_or(0, |old| *old.min(count)))
+            .sum::<u64>();
+        Ok(copied * MAX_SCORE / max)
+    }
+}
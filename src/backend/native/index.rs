@@ -0,0 +1,183 @@
+//! Compares the work tree with the index, like `git diff --exit-code`.
+
+use std::{io::ErrorKind, path::Path, time::UNIX_EPOCH};
+
+use eyre::{Result, eyre};
+use tracing::debug;
+
+use super::odb::{Kind, hash_object, to_hex};
+
+const ASSUME_VALID: u16 = 0x8000;
+const EXTENDED: u16 = 0x4000;
+const STAGE: u16 = 0x3000;
+const SKIP_WORKTREE: u16 = 0x4000;
+const INTENT_TO_ADD: u16 = 0x2000;
+
+const SYMLINK: u32 = 0o120000;
+const GITLINK: u32 = 0o160000;
+
+/// One path staged in the index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    path: String,
+    mtime: (u32, u32),
+    mode: u32,
+    size: u32,
+    hash: String,
+    flags: u16,
+    extended_flags: u16,
+}
+
+fn u32_at(data: &[u8], at: usize) -> Result<u32> {
+    Ok(u32::from_be_bytes(
+        data.get(at..at + 4)
+            .ok_or_else(|| eyre!("Truncated index"))?
+            .try_into()?,
+    ))
+}
+
+fn parse(data: &[u8]) -> Result<Vec<Entry>> {
+    if data.get(..4) != Some(b"DIRC") {
+        return Err(eyre!("Not an index file"));
+    }
+    let version = u32_at(data, 4)?;
+    if !(2..=4).contains(&version) {
+        return Err(eyre!("Unsupported index version {}", version));
+    }
+    let count = u32_at(data, 8)? as usize;
+    let mut entries: Vec<Entry> = Vec::with_capacity(count);
+    let mut at = 12;
+    for _ in 0..count {
+        let start = at;
+        let flags = u16::from_be_bytes(
+            data.get(at + 60..at + 62)
+                .ok_or_else(|| eyre!("Truncated index"))?
+                .try_into()?,
+        );
+        at += 62;
+        let extended_flags = match flags & EXTENDED {
+            0 => 0,
+            _ => {
+                at += 2;
+                u16::from_be_bytes(data[at - 2..at].try_into()?)
+            }
+        };
+        let path = match version {
+            // the path is what is left of the previous one after dropping
+            // some bytes from its end, then the rest of the name
+            4 => {
+                let mut byte = data[at];
+                let mut strip = (byte & 0x7f) as usize;
+                at += 1;
+                while byte & 0x80 != 0 {
+                    byte = data[at];
+                    strip = ((strip + 1) << 7) | (byte & 0x7f) as usize;
+                    at += 1;
+                }
+                let previous = entries
+                    .last()
+                    .map(|entry| entry.path.as_str())
+                    .unwrap_or("");
+                let end = at + data[at..].iter().position(|byte| *byte == 0).unwrap_or(0);
+                let suffix = String::from_utf8_lossy(&data[at..end]);
+                at = end + 1;
+                format!(
+                    "{}{}",
+                    &previous[..previous.len() - strip.min(previous.len())],
+                    suffix
+                )
+            }
+            // NUL-terminated and padded to a multiple of 8 bytes
+            _ => {
+                let end = at + data[at..].iter().position(|byte| *byte == 0).unwrap_or(0);
+                let path = String::from_utf8_lossy(&data[at..end]).to_string();
+                at = start + (end - start + 8) / 8 * 8;
+                path
+            }
+        };
+        entries.push(Entry {
+            path,
+            mtime: (u32_at(data, start + 8)?, u32_at(data, start + 12)?),
+            mode: u32_at(data, start + 24)?,
+            size: u32_at(data, start + 36)?,
+            hash: to_hex(&data[start + 40..start + 60]),
+            flags,
+            extended_flags,
+        });
+    }
+    Ok(entries)
+}
+
+/// Whether a file in `work_tree` differs from what the index at `index`
+/// holds for it. Untracked files don't count.
+pub fn is_dirty(work_tree: &Path, index: &Path, file_mode: bool) -> Result<bool> {
+    let data = match std::fs::read(index) {
+        Ok(data) => data,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    let index_mtime = std::fs::metadata(index)?.modified()?;
+    for entry in parse(&data)? {
+        if entry.flags & STAGE != 0 {
+            debug!(path = %entry.path, "Unmerged path");
+            return Ok(true);
+        }
+        if entry.flags & ASSUME_VALID != 0
+            || entry.extended_flags & SKIP_WORKTREE != 0
+            || entry.mode == GITLINK
+        {
+            continue;
+        }
+        if entry.extended_flags & INTENT_TO_ADD != 0
+            || changed(work_tree, &entry, index_mtime, file_mode)?
+        {
+            debug!(path = %entry.path, "Changed path");
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn changed(
+    work_tree: &Path,
+    entry: &Entry,
+    index_mtime: std::time::SystemTime,
+    file_mode: bool,
+) -> Result<bool> {
+    let path = work_tree.join(&entry.path);
+    let metadata = match std::fs::symlink_metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(true),
+        Err(e) => return Err(e.into()),
+    };
+    if metadata.is_symlink() != (entry.mode == SYMLINK) || metadata.is_dir() {
+        return Ok(true);
+    }
+    if metadata.is_symlink() {
+        let target = std::fs::read_link(&path)?;
+        return Ok(hash_object(Kind::Blob, target.to_string_lossy().as_bytes()) != entry.hash);
+    }
+    #[cfg(unix)]
+    if file_mode {
+        use std::os::unix::fs::PermissionsExt as _;
+        let executable = metadata.permissions().mode() & 0o100 != 0;
+        if executable != (entry.mode & 0o100 != 0) {
+            return Ok(true);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = file_mode;
+    if metadata.len() as u32 != entry.size {
+        return Ok(true);
+    }
+    // unchanged stat data is trusted unless the file may have been written
+    // in the same instant the index was (git's "racy" entries)
+    let modified = metadata.modified()?;
+    let since_epoch = modified.duration_since(UNIX_EPOCH)?;
+    if (since_epoch.as_secs() as u32, since_epoch.subsec_nanos()) == entry.mtime
+        && modified < index_mtime
+    {
+        return Ok(false);
+    }
+    Ok(hash_object(Kind::Blob, &std::fs::read(&path)?) != entry.hash)
+}
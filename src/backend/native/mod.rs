@@ -0,0 +1,559 @@
+//! Reads the `.git` directory without running `git`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Result, WrapErr as _, eyre};
+use tracing::{debug, info, instrument};
+
+use super::{
+    GitBackend,
+    log::{self, Commit, Head, History, Ref, Signature},
+};
+use crate::git::{self, NewTag, PushOutcome};
+use config::GitConfig;
+use odb::{Kind, ObjectDatabase};
+use refs::RefValue;
+
+pub mod config;
+pub mod diff;
+pub mod index;
+pub mod odb;
+pub mod refs;
+
+/// A repository read straight from its `.git` directory. Supports loose and
+/// packed objects and refs, worktrees, alternates and shallow clones; not
+/// SHA-256 repositories or reftables.
+#[derive(Debug)]
+pub struct NativeBackend {
+    /// `None` for a bare repository.
+    work_tree: Option<PathBuf>,
+    git_dir: PathBuf,
+    /// Where objects and refs live, shared by all worktrees.
+    common_dir: PathBuf,
+    odb: ObjectDatabase,
+    /// Commits whose parents a shallow clone left out.
+    shallow: HashSet<String>,
+    config: GitConfig,
+}
+
+/// The work tree and git directory of the repository containing `path`.
+fn discover(path: &Path) -> Result<(Option<PathBuf>, PathBuf)> {
+    let path = std::fs::canonicalize(path)?;
+    for dir in path.ancestors() {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return Ok((Some(dir.to_path_buf()), dot_git));
+        }
+        // worktrees and submodules point at their git directory
+        if dot_git.is_file() {
+            let contents = std::fs::read_to_string(&dot_git)?;
+            let git_dir = contents
+                .trim()
+                .strip_prefix("gitdir:")
+                .ok_or_else(|| eyre!("Invalid .git file {:?}", dot_git))?;
+            return Ok((Some(dir.to_path_buf()), dir.join(git_dir.trim())));
+        }
+        if dir.join("HEAD").is_file() && dir.join("objects").is_dir() && dir.join("refs").is_dir() {
+            return Ok((None, dir.to_path_buf()));
+        }
+    }
+    Err(eyre!("{:?} is not in a git repository", path))
+}
+
+impl NativeBackend {
+    #[instrument]
+    pub fn open(path: &Path) -> Result<NativeBackend> {
+        let (work_tree, git_dir) = discover(path)?;
+        let common_dir = match std::fs::read_to_string(git_dir.join("commondir")) {
+            Ok(common_dir) => git_dir.join(common_dir.trim()),
+            Err(e) if e.kind() == ErrorKind::NotFound => git_dir.clone(),
+            Err(e) => return Err(e.into()),
+        };
+        if common_dir.join("reftable").is_dir() {
+            return Err(eyre!("Repositories using reftables need the git backend"));
+        }
+        let config = GitConfig::load(&common_dir);
+        if config
+            .get("extensions.objectFormat")
+            .is_some_and(|format| format != "sha1")
+        {
+            return Err(eyre!("SHA-256 repositories need the git backend"));
+        }
+        let shallow = match std::fs::read_to_string(common_dir.join("shallow")) {
+            Ok(shallow) => shallow.lines().map(str::to_string).collect(),
+            Err(e) if e.kind() == ErrorKind::NotFound => HashSet::new(),
+            Err(e) => return Err(e.into()),
+        };
+        info!(?git_dir, "Reading the repository directly");
+        Ok(NativeBackend {
+            odb: ObjectDatabase::open(&common_dir.join("objects"))?,
+            work_tree,
+            git_dir,
+            common_dir,
+            shallow,
+            config,
+        })
+    }
+
+    /// The object an annotated tag points at, following tags of tags.
+    fn peel(&self, hash: &str) -> Result<Option<String>> {
+        let mut target = hash.to_string();
+        while let Some((Kind::Tag, data)) = self.odb.read(&target)? {
+            target = String::from_utf8_lossy(&data)
+                .lines()
+                .find_map(|line| line.strip_prefix("object "))
+                .ok_or_else(|| eyre!("Tag {} has no object", hash))?
+                .to_string();
+        }
+        Ok((target != hash).then_some(target))
+    }
+
+    /// `user.name` and `user.email` (or their environment overrides) as of
+    /// now, for the `tagger` line.
+    fn tagger(&self) -> Result<String> {
+        let value = |env: &str, key: &str| {
+            std::env::var(env)
+                .ok()
+                .or_else(|| self.config.get(key).map(str::to_string))
+                .ok_or_else(|| eyre!("Committer identity unknown, set {}", key))
+        };
+        let now = chrono::Local::now();
+        Ok(Signature {
+            name: value("GIT_COMMITTER_NAME", "user.name")?,
+            email: value("GIT_COMMITTER_EMAIL", "user.email")?,
+            time: now.timestamp(),
+            offset: now.offset().local_minus_utc() / 60,
+        }
+        .to_string())
+    }
+
+    fn work_dir(&self) -> &Path {
+        self.work_tree.as_deref().unwrap_or(&self.git_dir)
+    }
+}
+
+/// The message of the tag object `data`, without the signature `git tag -s`
+/// appends to it.
+fn tag_message(data: &str) -> &str {
+    let message = data.split_once("\n\n").map_or("", |(_, message)| message);
+    let signature = [
+        "PGP SIGNATURE",
+        "PGP MESSAGE",
+        "SSH SIGNATURE",
+        "SIGNED MESSAGE",
+    ]
+    .iter()
+    .filter_map(|kind| message.rfind(&format!("-----BEGIN {}-----", kind)))
+    .filter(|start| *start == 0 || message[..*start].ends_with('\n'))
+    .min();
+    signature
+        .map_or(message, |start| &message[..start])
+        .trim_end()
+}
+
+impl History for NativeBackend {
+    fn commit(&self, hash: &str) -> Result<Option<Commit>> {
+        let Some((kind, data)) = self.odb.read(hash)? else {
+            return Ok(None);
+        };
+        if kind != Kind::Commit {
+            return Err(eyre!("{} is a {:?}, not a commit", hash, kind));
+        }
+        let mut commit = Commit::parse(hash, &data)?;
+        if self.shallow.contains(hash) {
+            commit.parents.clear();
+        }
+        Ok(Some(commit))
+    }
+
+    fn changed_paths(&self, commit: &Commit) -> Result<Vec<String>> {
+        let parent_tree = match commit.parents.first() {
+            Some(parent) => Some(
+                self.commit(parent)?
+                    .ok_or_else(|| eyre!("Commit {} is missing from the repository", parent))?
+                    .tree,
+            ),
+            None => None,
+        };
+        diff::changed_paths(
+            &self.odb,
+            parent_tree.as_deref(),
+            &commit.tree,
+            self.config.bool("diff.renames").unwrap_or(true),
+            self.config
+                .get("diff.renameLimit")
+                .and_then(|limit| limit.parse().ok())
+                .unwrap_or(1000),
+        )
+    }
+
+    fn refs(&self) -> Result<Vec<Ref>> {
+        let (refs, peeled) = refs::read_refs(&self.common_dir)?;
+        let mut resolved = vec![];
+        for (name, value) in &refs {
+            let Some(target) = refs::resolve(&refs, value) else {
+                continue;
+            };
+            let peeled = match peeled.get(name) {
+                Some(peeled) => Some(peeled.clone()),
+                None if name.starts_with("refs/tags/") => self.peel(&target)?,
+                None => None,
+            };
+            resolved.push(Ref {
+                name: name.clone(),
+                target,
+                peeled,
+            });
+        }
+        Ok(resolved)
+    }
+
+    fn head(&self) -> Result<Option<Head>> {
+        let contents = match std::fs::read_to_string(self.git_dir.join("HEAD")) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(match RefValue::parse(&contents) {
+            RefValue::Symbolic(branch) => {
+                let (refs, _) = refs::read_refs(&self.common_dir)?;
+                let commit = refs::resolve(&refs, &RefValue::Symbolic(branch.clone()));
+                Head::Branch(branch, commit)
+            }
+            RefValue::Direct(hash) => Head::Detached(hash),
+        }))
+    }
+
+    fn grafted(&self) -> Result<Vec<String>> {
+        Ok(self.shallow.iter().cloned().collect())
+    }
+}
+
+impl GitBackend for NativeBackend {
+    fn log(&self, changed_paths: bool, tip: Option<&str>) -> Result<String> {
+        log::render_log(self, changed_paths, tip, &[])
+    }
+
+    fn log_after(
+        &self,
+        changed_paths: bool,
+        tip: Option<&str>,
+        boundaries: &[String],
+    ) -> Result<String> {
+        log::render_log(self, changed_paths, tip, boundaries)
+    }
+
+    fn log_range(&self, range: &str) -> Result<String> {
+        log::render_range(self, range)
+    }
+
+    fn resolve(&self, rev: &str) -> Result<String> {
+        log::resolve(self, rev)
+    }
+
+    fn is_dirty(&self) -> Result<bool> {
+        match &self.work_tree {
+            Some(work_tree) => index::is_dirty(
+                work_tree,
+                &self.git_dir.join("index"),
+                self.config.bool("core.fileMode").unwrap_or(true),
+            ),
+            None => Ok(false),
+        }
+    }
+
+    fn head_hash(&self) -> Result<String> {
+        self.head()?
+            .as_ref()
+            .and_then(Head::commit)
+            .map(str::to_string)
+            .ok_or_else(|| eyre!("HEAD does not point at a commit"))
+    }
+
+    fn current_branch(&self) -> Result<String> {
+        Ok(self
+            .head()?
+            .ok_or_else(|| eyre!("The repository has no HEAD"))?
+            .abbrev()
+            .to_string())
+    }
+
+    fn tag_targets(&self) -> Result<HashMap<String, String>> {
+        Ok(self
+            .refs()?
+            .into_iter()
+            .filter_map(|r| {
+                let tag = r.name.strip_prefix("refs/tags/")?.to_string();
+                Some((tag, r.commit().to_string()))
+            })
+            .collect())
+    }
+
+    fn branches(&self) -> Result<Vec<String>> {
+        log::branches(self)
+    }
+
+    fn branch_tags(&self) -> Result<HashMap<String, (String, i64)>> {
+        log::branch_tags(self)
+    }
+
+    fn common_dir(&self) -> Result<Option<PathBuf>> {
+        Ok(Some(self.common_dir.clone()))
+    }
+
+    fn tag_messages(&self) -> Result<HashMap<String, String>> {
+        let mut messages = HashMap::new();
+        for r in self.refs()? {
+            let Some(tag) = r.name.strip_prefix("refs/tags/") else {
+                continue;
+            };
+            if let Some((Kind::Tag, data)) = self.odb.read(&r.target)? {
+                let message = tag_message(&String::from_utf8_lossy(&data)).to_string();
+                messages.insert(tag.to_string(), message);
+            }
+        }
+        debug!("Read {} annotated tag message(s)", messages.len());
+        Ok(messages)
+    }
+
+    fn hooks_dir(&self) -> Result<PathBuf> {
+        // a relative `core.hooksPath` is relative to where hooks run
+        Ok(match self.config.get("core.hooksPath") {
+            Some(hooks) => self.work_dir().join(hooks),
+            None => self.common_dir.join("hooks"),
+        })
+    }
+
+    #[instrument(skip_all, fields(count = tags.len()))]
+    fn create_tags(&self, tags: &[NewTag]) -> Result<()> {
+        git::check_unique_tags(tags)?;
+        if tags.is_empty() {
+            return Ok(());
+        }
+        info!("Creating {} tag(s)", tags.len());
+        // git signs the tags it is asked to sign
+        let sign_by_default = self.config.bool("tag.gpgSign").unwrap_or(false);
+        let signed = tags
+            .iter()
+            .filter(|tag| tag.annotation.is_some_and(|a| a.sign || sign_by_default))
+            .copied()
+            .collect::<Vec<_>>();
+        let signed = git::tag_objects(&signed, self.work_dir())?;
+        let mut new = vec![];
+        for tag in tags {
+            let object = match (tag.annotation, signed.get(tag.tag)) {
+                (_, Some(object)) => object.clone(),
+                (Some(annotation), None) => {
+                    let payload = git::tag_payload(tag, annotation, &self.tagger()?);
+                    self.odb.write(Kind::Tag, payload.as_bytes())?
+                }
+                (None, None) => tag.hash.to_string(),
+            };
+            new.push((format!("refs/tags/{}", tag.tag), object));
+        }
+        refs::create_refs(&self.common_dir, &new)
+            .map_err(|e| eyre!("Failed to create tags, none were created: {}", e))?;
+        debug!("Created {} tag(s)", tags.len());
+        Ok(())
+    }
+
+    fn push_tags(&self, remote: &str, tags: &[&str]) -> Result<HashMap<String, PushOutcome>> {
+        if tags.is_empty() {
+            return Ok(HashMap::new());
+        }
+        git::git_installed().wrap_err("Pushing tags needs git")?;
+        git::push_tags(remote, tags, self.work_dir())
+    }
+}
+
+#[cfg(test)]
+mod native_tests {
+    use super::{NativeBackend, tag_message};
+    use crate::{
+        backend::{GitBackend, SubprocessBackend},
+        git::{NewTag, TagAnnotation, tag_messages},
+        test_repo::{ScratchDir, TestRepo},
+    };
+
+    #[test]
+    fn reads_the_repository_like_git() -> eyre::Result<()> {
+        let test_repo = TestRepo::new("native");
+        let repo = test_repo.path();
+        std::fs::create_dir_all(repo.join("src/api"))?;
+        let git_at = |args: &[&str], date: &str| test_repo.git_at(args, date);
+        let git = |args: &[&str]| git_at(args, "2024-01-01T00:00:00+01:00");
+        let write = |path: &str, contents: &str| test_repo.write(path, contents);
+        write("src/api/lib.rs", "1\n")?;
+        write("README", "readme\n")?;
+        git(&["add", "--all"])?;
+        git(&["commit", "--quiet", "-m", "feat: first"])?;
+        git(&["tag", "v0.1.0"])?;
+        git(&["checkout", "--quiet", "-b", "feature"])?;
+        write("src/api/new.rs", "2\n")?;
+        git(&["add", "--all"])?;
+        git_at(
+            &[
+                "commit",
+                "--quiet",
+                "-m",
+                "feat(api): add\n a thing\n\nbody\n\nBREAKING CHANGE: x",
+            ],
+            "2024-01-02T00:00:00-02:30",
+        )?;
+        git(&["checkout", "--quiet", "main"])?;
+        git(&["mv", "README", "README.md"])?;
+        write("src/api/lib.rs", "1\n3\n")?;
+        git(&["add", "--all"])?;
+        git_at(
+            &["commit", "--quiet", "-m", "fix: move"],
+            "2024-01-02T00:00:00Z",
+        )?;
+        git_at(
+            &[
+                "merge",
+                "--quiet",
+                "--no-ff",
+                "feature",
+                "-m",
+                "Merge branch 'feature'",
+            ],
+            "2024-01-03T00:00:00Z",
+        )?;
+        git(&["tag", "--annotate", "v0.2.0", "-m", "v0.2.0"])?;
+        write("src/notes.txt", "notes\n")?;
+        git(&["add", "--all"])?;
+        git_at(
+            &["commit", "--quiet", "-m", "chore: same time"],
+            "2024-01-03T00:00:00Z",
+        )?;
+
+        let assert_same = |dir: &std::path::Path| -> eyre::Result<()> {
+            let (native, subprocess) = (NativeBackend::open(dir)?, SubprocessBackend::new(dir));
+            let tagged = subprocess.tag_targets()?.into_values().collect::<Vec<_>>();
+            for changed_paths in [false, true] {
+                assert_eq!(
+                    native.log(changed_paths, None)?,
+                    subprocess.log(changed_paths, None)?
+                );
+                assert_eq!(
+                    native.log_after(changed_paths, None, &tagged)?,
+                    subprocess.log_after(changed_paths, None, &tagged)?
+                );
+            }
+            for rev in ["HEAD", "v0.1.0", "v0.2.0", "feature"] {
+                let tip = subprocess.resolve(rev)?;
+                assert_eq!(native.resolve(rev)?, tip);
+                assert_eq!(
+                    native.log(false, Some(&tip))?,
+                    subprocess.log(false, Some(&tip))?
+                );
+            }
+            for range in [
+                "v0.1.0..HEAD",
+                "v0.1.0..feature",
+                "feature..",
+                "HEAD..",
+                "main",
+            ] {
+                assert_eq!(native.log_range(range)?, subprocess.log_range(range)?);
+            }
+            assert_eq!(
+                native.hooks_dir()?.canonicalize()?,
+                subprocess.hooks_dir()?.canonicalize()?
+            );
+            assert_eq!(native.tag_messages()?, subprocess.tag_messages()?);
+            assert_eq!(native.head_hash()?, subprocess.head_hash()?);
+            assert_eq!(native.current_branch()?, subprocess.current_branch()?);
+            assert_eq!(native.tag_targets()?, subprocess.tag_targets()?);
+            assert_eq!(native.branch_tags()?, subprocess.branch_tags()?);
+            assert_eq!(native.is_dirty()?, subprocess.is_dirty()?);
+            Ok(())
+        };
+        assert_same(repo)?;
+        // packed objects and refs
+        git(&["gc", "--quiet"])?;
+        assert_same(repo)?;
+        // hooks kept in the work tree
+        git(&["config", "core.hooksPath", ".githooks"])?;
+        std::fs::create_dir(repo.join(".githooks"))?;
+        assert_same(repo)?;
+        git(&["config", "--unset", "core.hooksPath"])?;
+        std::fs::remove_dir(repo.join(".githooks"))?;
+        // a detached HEAD
+        git(&["checkout", "--quiet", "--detach", "v0.1.0"])?;
+        assert_same(repo)?;
+        git(&["checkout", "--quiet", "main"])?;
+
+        let native = NativeBackend::open(&repo.join("src"))?;
+        assert!(!native.is_dirty()?);
+        write("src/api/lib.rs", "changed\n")?;
+        assert!(native.is_dirty()?);
+        assert_same(repo)?;
+        git(&["checkout", "--", "src/api/lib.rs"])?;
+        assert!(!native.is_dirty()?);
+        // a staged rename, an untracked file and a deleted one
+        git(&["mv", "README.md", "README.txt"])?;
+        write("untracked", "new\n")?;
+        assert_same(repo)?;
+        std::fs::remove_file(repo.join("src/notes.txt"))?;
+        assert_same(repo)?;
+        git(&["reset", "--quiet", "--hard"])?;
+        std::fs::remove_file(repo.join("untracked"))?;
+        // a linked worktree on another branch
+        let scratch = ScratchDir::new("native-worktree");
+        let worktree = scratch.path().join("feature");
+        git(&[
+            "worktree",
+            "add",
+            "--quiet",
+            worktree.to_str().unwrap(),
+            "feature",
+        ])?;
+        assert_same(&worktree)?;
+        std::fs::write(worktree.join("src/api/new.rs"), "changed\n")?;
+        assert_same(&worktree)?;
+        assert_same(repo)?;
+        std::fs::remove_dir_all(&worktree)?;
+        git(&["worktree", "prune"])?;
+
+        let head = native.head_hash()?;
+        let annotation = TagAnnotation {
+            message: "## v0.3.0\n".to_string(),
+            sign: false,
+        };
+        let v3 = NewTag {
+            hash: &head,
+            tag: "v0.3.0",
+            annotation: Some(&annotation),
+        };
+        let v2 = NewTag {
+            tag: "v0.2.0",
+            annotation: None,
+            ..v3
+        };
+        assert!(native.create_tags(&[v3, v2]).is_err());
+        assert!(!native.tag_targets()?.contains_key("v0.3.0"));
+        native.create_tags(&[v3])?;
+        assert_eq!(native.tag_targets()?["v0.3.0"], head);
+        assert_eq!(tag_messages(repo)?["v0.3.0"], "## v0.3.0");
+        git(&["fsck", "--strict"])?;
+        assert_same(repo)?;
+        Ok(())
+    }
+
+    #[test]
+    fn reads_tag_messages_without_their_signature() {
+        let header = "object 0000000000000000000000000000000000000000\ntype commit\ntag v1\n";
+        let signature = "-----BEGIN PGP SIGNATURE-----\n\niQ==\n-----END PGP SIGNATURE-----\n";
+        assert_eq!(
+            tag_message(&format!("{header}\n## v1\n\nNotes\n{signature}")),
+            "## v1\n\nNotes"
+        );
+        assert_eq!(tag_message(&format!("{header}\n## v1\n")), "## v1");
+        assert_eq!(tag_message(&format!("{header}\n{signature}")), "");
+    }
+}
@@ -0,0 +1,221 @@
+//! Reads loose and packed refs and creates new ones.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{ErrorKind, Write as _},
+    path::{Path, PathBuf},
+};
+
+use eyre::{Result, eyre};
+use tracing::debug;
+
+/// What a ref file holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RefValue {
+    Direct(String),
+    Symbolic(String),
+}
+
+impl RefValue {
+    pub fn parse(contents: &str) -> RefValue {
+        match contents.trim().strip_prefix("ref:") {
+            Some(target) => RefValue::Symbolic(target.trim().to_string()),
+            None => RefValue::Direct(contents.trim().to_string()),
+        }
+    }
+}
+
+/// Every ref under `refs/` by name, loose refs taking precedence over packed
+/// ones, with the peeled commits `packed-refs` records for annotated tags.
+pub fn read_refs(
+    common_dir: &Path,
+) -> Result<(BTreeMap<String, RefValue>, BTreeMap<String, String>)> {
+    let mut refs = BTreeMap::new();
+    let mut peeled = BTreeMap::new();
+    match std::fs::read_to_string(common_dir.join("packed-refs")) {
+        Ok(packed) => {
+            let mut last = None;
+            for line in packed.lines() {
+                if line.starts_with('#') {
+                    continue;
+                }
+                if let Some(commit) = line.strip_prefix('^') {
+                    if let Some(name) = last.take() {
+                        peeled.insert(name, commit.to_string());
+                    }
+                    continue;
+                }
+                if let Some((hash, name)) = line.split_once(' ') {
+                    refs.insert(name.to_string(), RefValue::Direct(hash.to_string()));
+                    last = Some(name.to_string());
+                }
+            }
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let mut dirs = vec![common_dir.join("refs")];
+    while let Some(dir) = dirs.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "lock")
+            {
+                continue;
+            }
+            let name = path
+                .strip_prefix(common_dir)?
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let value = RefValue::parse(&std::fs::read_to_string(&path)?);
+            // a loose ref replaces its packed version and what it peeled to
+            peeled.remove(&name);
+            refs.insert(name, value);
+        }
+    }
+    debug!("Read {} ref(s)", refs.len());
+    Ok((refs, peeled))
+}
+
+/// Follows symbolic refs to a hash. `None` for a ref to an unborn branch.
+pub fn resolve(refs: &BTreeMap<String, RefValue>, value: &RefValue) -> Option<String> {
+    let mut value = value.clone();
+    for _ in 0..5 {
+        match value {
+            RefValue::Direct(hash) => return Some(hash),
+            RefValue::Symbolic(target) => value = refs.get(&target)?.clone(),
+        }
+    }
+    None
+}
+
+/// `git check-ref-format`, for the names ccver creates.
+fn check_ref_format(name: &str) -> Result<()> {
+    let invalid = name.ends_with(['/', '.'])
+        || name.ends_with(".lock")
+        || name.contains("..")
+        || name.contains("@{")
+        || name.contains("//")
+        || name
+            .split('/')
+            .any(|part| part.is_empty() || part.starts_with('.'))
+        || name
+            .chars()
+            .any(|c| c.is_ascii_control() || " ~^:?*[\\".contains(c));
+    match invalid {
+        true => Err(eyre!("{} is not a valid ref name", name)),
+        false => Ok(()),
+    }
+}
+
+/// Creates every ref in `new` (full name and hash), or none of them: all
+/// ref files are locked, and checked not to exist, before any is written.
+pub fn create_refs(common_dir: &Path, new: &[(String, String)]) -> Result<()> {
+    let (existing, _) = read_refs(common_dir)?;
+    let mut locks = vec![];
+    let locked = new
+        .iter()
+        .try_for_each(|(name, hash)| lock_ref(common_dir, &existing, name, hash, &mut locks))
+        // a ref someone packed while we were locking
+        .and_then(|()| {
+            let (now, _) = read_refs(common_dir)?;
+            match new.iter().find(|(name, _)| now.contains_key(name)) {
+                Some((name, _)) => Err(eyre!(
+                    "cannot lock ref '{}': reference already exists",
+                    name
+                )),
+                None => Ok(()),
+            }
+        });
+    if let Err(e) = locked {
+        locks.iter().for_each(|(lock, _)| {
+            let _ = std::fs::remove_file(lock);
+        });
+        return Err(e);
+    }
+    commit_locks(&locks)?;
+    debug!("Created {} ref(s)", locks.len());
+    Ok(())
+}
+
+/// Moves every lock file onto its ref. When one can't be moved, the refs
+/// already moved are removed again along with the remaining locks.
+fn commit_locks(locks: &[(PathBuf, PathBuf)]) -> Result<()> {
+    for (moved, (lock, path)) in locks.iter().enumerate() {
+        if let Err(e) = std::fs::rename(lock, path) {
+            locks[..moved].iter().for_each(|(_, path)| {
+                let _ = std::fs::remove_file(path);
+            });
+            locks[moved..].iter().for_each(|(lock, _)| {
+                let _ = std::fs::remove_file(lock);
+            });
+            return Err(eyre!("cannot update ref '{}': {}", path.display(), e));
+        }
+    }
+    Ok(())
+}
+
+/// Takes the lock of the new ref `name`, holding `hash`, adding it to `locks`.
+fn lock_ref(
+    common_dir: &Path,
+    existing: &BTreeMap<String, RefValue>,
+    name: &str,
+    hash: &str,
+    locks: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<()> {
+    check_ref_format(name)?;
+    let path = common_dir.join(name);
+    if existing.contains_key(name) || path.exists() {
+        return Err(eyre!(
+            "cannot lock ref '{}': reference already exists",
+            name
+        ));
+    }
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    let lock = PathBuf::from(format!("{}.lock", path.display()));
+    let mut file =
+        File::create_new(&lock).map_err(|e| eyre!("cannot lock ref '{}': {}", name, e))?;
+    locks.push((lock, path));
+    file.write_all(format!("{}\n", hash).as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod refs_tests {
+    use super::commit_locks;
+    use crate::test_repo::ScratchDir;
+
+    #[test]
+    fn rolls_back_when_a_ref_cannot_be_moved() -> eyre::Result<()> {
+        let scratch = ScratchDir::new("refs");
+        let dir = scratch.path();
+        std::fs::create_dir(dir.join("v2"))?;
+        let locks = ["v1", "v2", "v3"]
+            .map(|name| (dir.join(format!("{name}.lock")), dir.join(name)))
+            .to_vec();
+        for (lock, _) in &locks {
+            std::fs::write(lock, "0\n")?;
+        }
+
+        // a directory is in the way of `v2`
+        assert!(commit_locks(&locks).is_err());
+        assert!(!dir.join("v1").exists());
+        assert!(dir.join("v2").is_dir());
+        assert!(locks.iter().all(|(lock, _)| !lock.exists()));
+        Ok(())
+    }
+}
@@ -0,0 +1,586 @@
+//! Versions computed by earlier runs, kept in `ccver/cache` in the git
+//! directory so that later runs only compute the versions of new commits.
+//!
+//! The file starts with a line naming the [`key`] the versions were computed
+//! under, followed by one line per tag reachable from a branch, `tag`, its
+//! name and its commit, and one line per commit: its hash, the branch it was
+//! reached from, its version and its parents.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::Display,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{OptionExt as _, Result, eyre};
+use serde::Serialize;
+use tracing::{debug, info, instrument, warn};
+
+use crate::{
+    backend::GitBackend,
+    config::CCVerConfig,
+    graph::{
+        GraphOps as _, MemoizedCommitGraph, derivation::BaseSource, head::HasHead as _,
+        version::ExistingVersionExt as _,
+    },
+    logs::Logs,
+    parser,
+    version::Version,
+    version_format::{PreTagFormat, VersionFormat, VersionNumberFormat},
+};
+
+/// Where the cache is kept, relative to the common git directory.
+const CACHE_PATH: &str = "ccver/cache";
+
+/// The first line of the cache, before its key.
+const HEADER: &str = "ccver-cache 2";
+
+/// Identifies what cached versions were computed with: changing the version
+/// format, the rules in `config` or ccver itself makes every cached version
+/// stale. Tags and branches only make the versions they change stale, see
+/// [`VersionCache::retag`] and the branch each version is cached for.
+pub fn key(version_format: &VersionFormat, config: &CCVerConfig) -> String {
+    let mut sha = sha1_smol::Sha1::new();
+    sha.update(env!("CARGO_PKG_VERSION").as_bytes());
+    sha.update(format!("{:?}\n", version_format).as_bytes());
+    sha.update(
+        format!(
+            "{:?}\n{:?}\n{:?}\n{}\n",
+            config.branches, config.commit_types, config.tag_pattern, config.initial_development
+        )
+        .as_bytes(),
+    );
+    sha.digest().to_string()
+}
+
+/// A cached version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CacheEntry {
+    /// The branch the commit was reached from.
+    pub branch: String,
+    pub version: String,
+    pub parents: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct VersionCache {
+    path: PathBuf,
+    /// The key the entries were computed under.
+    pub key: String,
+    /// The commit of every tag reachable from a branch when the entries were
+    /// computed, by tag name.
+    pub tags: BTreeMap<String, String>,
+    pub entries: BTreeMap<String, CacheEntry>,
+}
+
+impl VersionCache {
+    /// Reads the cache of the repository `backend` reads, whatever it was
+    /// computed under, or `None` when the repository is not on disk.
+    #[instrument(skip(backend))]
+    pub fn read(backend: &dyn GitBackend) -> Result<Option<VersionCache>> {
+        let Some(common_dir) = backend.common_dir()? else {
+            return Ok(None);
+        };
+        let path = common_dir.join(CACHE_PATH);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(eyre!("Could not read {}: {}", path.display(), e)),
+        };
+        let mut lines = text.lines();
+        let key = lines
+            .next()
+            .and_then(|line| line.strip_prefix(HEADER))
+            .map(|key| key.trim().to_string())
+            .unwrap_or_default();
+        let mut tags = BTreeMap::new();
+        let entries = lines
+            .filter_map(|line| {
+                if let Some(tag) = line.strip_prefix("tag ") {
+                    let (name, commit) = tag.split_once(' ')?;
+                    tags.insert(name.to_string(), commit.to_string());
+                    return None;
+                }
+                let mut fields = line.split(' ');
+                let (commit, branch, version) = (fields.next()?, fields.next()?, fields.next()?);
+                let entry = CacheEntry {
+                    branch: branch.to_string(),
+                    version: version.to_string(),
+                    parents: fields.map(str::to_string).collect(),
+                };
+                Some((commit.to_string(), entry))
+            })
+            .collect::<BTreeMap<_, _>>();
+        debug!("Read {} cached version(s)", entries.len());
+        Ok(Some(VersionCache {
+            path,
+            key,
+            tags,
+            entries,
+        }))
+    }
+
+    /// The cache for versions computed under `key` with the tags in
+    /// `branch_tags`: the entries are dropped when they were computed under
+    /// another key, and those the tags changed since are, see [`retag`].
+    ///
+    /// [`retag`]: VersionCache::retag
+    pub fn open(
+        backend: &dyn GitBackend,
+        key: String,
+        branch_tags: &HashMap<String, (String, i64)>,
+    ) -> Result<Option<VersionCache>> {
+        let Some(mut cache) = Self::read(backend)? else {
+            return Ok(None);
+        };
+        if cache.key != key {
+            debug!("The cached versions are stale");
+            cache.entries.clear();
+            cache.key = key;
+        }
+        cache.retag(backend, branch_tags)?;
+        Ok(Some(cache))
+    }
+
+    /// Moves the cache to the tags in `branch_tags`, dropping the versions
+    /// of the commits at or after a tag that was created, moved or deleted
+    /// since the entries were computed: a tag changes the version its
+    /// commit's descendants start from.
+    #[instrument(skip_all)]
+    pub fn retag(
+        &mut self,
+        backend: &dyn GitBackend,
+        branch_tags: &HashMap<String, (String, i64)>,
+    ) -> Result<()> {
+        let tags = branch_tags
+            .iter()
+            .map(|(tag, (commit, _))| (tag.clone(), commit.clone()))
+            .collect::<BTreeMap<_, _>>();
+        let changed = self
+            .tags
+            .iter()
+            .chain(&tags)
+            .filter(|(tag, _)| self.tags.get(*tag) != tags.get(*tag))
+            .map(|(_, commit)| commit.clone())
+            .collect::<BTreeSet<_>>();
+        self.tags = tags;
+        for commit in changed {
+            if self.entries.is_empty() {
+                break;
+            }
+            // the tags of every branch, but not what the tagged commit reaches
+            let raw = match backend.log_after(false, None, std::slice::from_ref(&commit)) {
+                Ok(raw) => raw,
+                Err(e) => {
+                    debug!(error = %e, "Could not list the commits after a changed tag");
+                    self.entries.clear();
+                    break;
+                }
+            };
+            let logs = Logs::from_log_str(&raw)?;
+            let mut after = HashSet::from([commit.as_str()]);
+            // the log lists children before their parents, unless clocks
+            // were skewed
+            loop {
+                let count = after.len();
+                for log_entry in logs[..].iter().rev() {
+                    if log_entry
+                        .parent_hashes
+                        .iter()
+                        .any(|parent| after.contains(parent))
+                    {
+                        after.insert(log_entry.commit_hash);
+                    }
+                }
+                if after.len() == count {
+                    break;
+                }
+            }
+            let count = self.entries.len();
+            self.entries
+                .retain(|commit, _| !after.contains(commit.as_str()));
+            debug!(
+                "Dropped {} cached version(s) after a changed tag",
+                count - self.entries.len()
+            );
+        }
+        Ok(())
+    }
+
+    /// The cached versions to start the walk from, but `head`'s.
+    pub fn seeds(&self, head: &str, version_format: &VersionFormat) -> HashMap<String, Version> {
+        self.entries
+            .iter()
+            .filter(|(commit, _)| *commit != head)
+            .filter_map(|(commit, entry)| {
+                let version =
+                    parser::parse_exact_version(&entry.version, version_format.clone()).ok()?;
+                Some((commit.clone(), version))
+            })
+            .collect()
+    }
+
+    /// The branch `commit` was reached from when its version was cached.
+    pub fn branch(&self, commit: &str) -> Option<&str> {
+        self.entries.get(commit).map(|entry| entry.branch.as_str())
+    }
+
+    /// Caches the versions in `graph` that a walk from another HEAD gives
+    /// too, see [`cacheable`].
+    pub fn record(&mut self, graph: &MemoizedCommitGraph, version_format: &VersionFormat) {
+        let entries = cacheable(graph, version_format);
+        debug!("Cached {} version(s)", entries.len());
+        self.entries.extend(entries);
+    }
+
+    /// Writes the cache, replacing the file at once.
+    #[instrument(skip(self), fields(path = ?self.path))]
+    pub fn save(&self) -> Result<()> {
+        let mut text = format!("{} {}\n", HEADER, self.key);
+        for (tag, commit) in &self.tags {
+            text.push_str(&format!("tag {} {}\n", tag, commit));
+        }
+        for (commit, entry) in &self.entries {
+            text.push_str(&format!("{} {} {}", commit, entry.branch, entry.version));
+            for parent in &entry.parents {
+                text.push(' ');
+                text.push_str(parent);
+            }
+            text.push('\n');
+        }
+        let dir = self
+            .path
+            .parent()
+            .ok_or_eyre("The cache has no directory")?;
+        std::fs::create_dir_all(dir)?;
+        // runs saving at once each write a file of their own
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.subsec_nanos())
+            .unwrap_or_default();
+        let temporary = self
+            .path
+            .with_extension(format!("{}-{}.tmp", std::process::id(), nanos));
+        let saved =
+            std::fs::write(&temporary, text).and_then(|()| std::fs::rename(&temporary, &self.path));
+        if let Err(e) = saved {
+            let _ = std::fs::remove_file(&temporary);
+            return Err(e.into());
+        }
+        info!("Saved {} cached version(s)", self.entries.len());
+        Ok(())
+    }
+
+    /// Records the versions in `graph` and saves the cache. A cache that
+    /// cannot be written only costs the next run time, so failing to is not
+    /// an error.
+    pub fn store(mut self, graph: &MemoizedCommitGraph, version_format: &VersionFormat) {
+        self.record(graph, version_format);
+        if let Err(e) = self.save() {
+            warn!(error = %e, "Could not save the version cache");
+        }
+    }
+}
+
+/// The versions in `graph` a walk from another HEAD gives too: those of the
+/// commits the walk reached before any of their ancestors, following single
+/// parents down from HEAD. HEAD's own bump can be forced, and commits whose
+/// tag started the walk are read from the tag again instead. Versions that
+/// would not read back the same are left out.
+fn cacheable(
+    graph: &MemoizedCommitGraph,
+    version_format: &VersionFormat,
+) -> BTreeMap<String, CacheEntry> {
+    let mut entries = BTreeMap::new();
+    let Some(head) = graph
+        .head()
+        .map(|head| head.lock().unwrap().log_entry.commit_hash)
+    else {
+        return entries;
+    };
+    let nodes = graph
+        .node_references()
+        .into_iter()
+        .filter_map(|(_, node)| {
+            let data = node.lock().unwrap();
+            let derivation = data.derivation.clone()?;
+            let version = data.as_existing_version()?;
+            Some((
+                data.log_entry.commit_hash,
+                (data.log_entry.clone(), derivation, version),
+            ))
+        })
+        .collect::<HashMap<_, _>>();
+    // the default version of a commit the walk starts from can depend on HEAD
+    if !is_fixed(version_format)
+        && nodes
+            .values()
+            .any(|(_, derivation, _)| derivation.base_source == BaseSource::PreviousInWalk(None))
+    {
+        debug!("Versions depend on where the walk started");
+        return entries;
+    }
+
+    let mut next = nodes.get(head);
+    while let Some((log_entry, derivation, version)) = next {
+        // the parents of a merge are not reached before each other
+        next = match &log_entry.parent_hashes[..] {
+            [parent] => nodes.get(parent),
+            _ => None,
+        };
+        if log_entry.commit_hash == head
+            || log_entry.branch.is_empty()
+            || derivation.base_source == BaseSource::Tag
+        {
+            continue;
+        }
+        let text = version.to_string();
+        match parser::parse_exact_version(&text, version_format.clone()) {
+            Ok(read) if read == *version => {}
+            _ => continue,
+        }
+        entries.insert(
+            log_entry.commit_hash.to_string(),
+            CacheEntry {
+                branch: log_entry.branch.to_string(),
+                version: text,
+                parents: log_entry
+                    .parent_hashes
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect(),
+            },
+        );
+    }
+    entries
+}
+
+/// A cached version that differs from the one the whole history gives.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CacheMismatch {
+    pub commit: String,
+    pub cached: CacheEntry,
+    pub version: String,
+}
+
+impl Display for CacheMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}) is cached as {} but is {}",
+            &self.commit[..self.commit.len().min(7)],
+            self.cached.branch,
+            self.cached.version,
+            self.version
+        )
+    }
+}
+
+/// The cached versions checked against the ones `graph`, built from the whole
+/// history, would cache. Only the commits HEAD reaches before their
+/// ancestors, from the branch they were cached for, have such a version;
+/// the `skipped` others are not checked.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CacheReport {
+    /// Whether the cache was computed under the current key. A stale cache
+    /// is not used and the next run starts it over.
+    pub current: bool,
+    pub checked: usize,
+    pub skipped: usize,
+    pub mismatches: Vec<CacheMismatch>,
+}
+
+/// Checks every version in `cache` against `graph`, built from the whole
+/// history under `key`.
+pub fn verify(
+    cache: &VersionCache,
+    key: &str,
+    graph: &MemoizedCommitGraph,
+    version_format: &VersionFormat,
+) -> CacheReport {
+    let mut report = CacheReport {
+        current: cache.key == key,
+        checked: 0,
+        skipped: 0,
+        mismatches: vec![],
+    };
+    if !report.current {
+        return report;
+    }
+    let expected = cacheable(graph, version_format);
+    for (commit, cached) in &cache.entries {
+        // a cached version is not used for a commit reached from another
+        // branch now
+        let Some(entry) = expected
+            .get(commit)
+            .filter(|entry| entry.branch == cached.branch)
+        else {
+            report.skipped += 1;
+            continue;
+        };
+        report.checked += 1;
+        if entry.version != cached.version {
+            report.mismatches.push(CacheMismatch {
+                commit: commit.clone(),
+                cached: cached.clone(),
+                version: entry.version.clone(),
+            });
+        }
+    }
+    report
+}
+
+/// Removes the cache of the repository `backend` reads, returning whether
+/// there was one.
+#[instrument(skip(backend))]
+pub fn clear(backend: &dyn GitBackend) -> Result<bool> {
+    let Some(common_dir) = backend.common_dir()? else {
+        return Ok(false);
+    };
+    match std::fs::remove_file(common_dir.join(CACHE_PATH)) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Whether every commit starts from the same default version, as it does
+/// unless the format has sha or calendar parts.
+fn is_fixed(version_format: &VersionFormat) -> bool {
+    let fixed = |number: &VersionNumberFormat| *number == VersionNumberFormat::CCVer;
+    [
+        &version_format.major,
+        &version_format.minor,
+        &version_format.patch,
+    ]
+    .into_iter()
+    .all(fixed)
+        && version_format.prerelease.as_ref().is_none_or(|prerelease| {
+            !matches!(prerelease, PreTagFormat::Sha | PreTagFormat::ShortSha)
+                && fixed(&prerelease.version_format())
+        })
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use super::VersionCache;
+    use crate::{
+        backend::{GitBackend as _, MemoryBackend},
+        config::CCVerConfig,
+        graph::MemoizedCommitGraph,
+        logs::Logs,
+        version_format::VersionFormat,
+    };
+    use std::collections::HashMap;
+
+    fn head_version(graph: &MemoizedCommitGraph) -> String {
+        let head = graph.head().unwrap();
+        let version = head.lock().unwrap().version.clone().unwrap();
+        version.to_string()
+    }
+
+    #[test]
+    fn seeds_version_head_like_the_whole_history() {
+        let repo = MemoryBackend::new("main");
+        repo.commit("feat: initial");
+        repo.tag("v0.1.0").unwrap();
+        repo.commit("fix: a bug");
+        let parent = repo.commit("feat: something new");
+        let head = repo.commit("fix: another bug");
+
+        let version_format = VersionFormat::default();
+        let config = CCVerConfig::default();
+        let whole = repo.log(false, None).unwrap();
+        let graph = MemoizedCommitGraph::new(
+            Logs::from_log_str(&whole).unwrap(),
+            &version_format,
+            &config,
+        )
+        .unwrap();
+        let mut cache = VersionCache {
+            path: Default::default(),
+            key: Default::default(),
+            tags: Default::default(),
+            entries: Default::default(),
+        };
+        cache.record(&graph, &version_format);
+        // HEAD's bump can be forced, so only the commits before it are cached
+        assert!(!cache.entries.contains_key(&head));
+        assert!(cache.entries.contains_key(&parent));
+
+        repo.commit("feat: more");
+        let seeds = cache
+            .seeds(&repo.head_hash().unwrap(), &version_format)
+            .into_iter()
+            .filter(|(commit, _)| *commit == parent)
+            .collect::<HashMap<_, _>>();
+        let recent = repo.log_after(false, None, &[parent]).unwrap();
+        let seeded = MemoizedCommitGraph::with_seeds(
+            Logs::from_log_str(&recent).unwrap(),
+            &version_format,
+            &config,
+            &seeds,
+        )
+        .unwrap();
+        let whole = repo.log(false, None).unwrap();
+        let graph = MemoizedCommitGraph::new(
+            Logs::from_log_str(&whole).unwrap(),
+            &version_format,
+            &config,
+        )
+        .unwrap();
+        assert_eq!(head_version(&seeded), head_version(&graph));
+    }
+
+    #[test]
+    fn drops_versions_after_changed_tags() {
+        let repo = MemoryBackend::new("main");
+        repo.commit("feat: initial");
+        repo.tag("v0.1.0").unwrap();
+        let before = repo.commit("fix: a bug");
+        let tagged = repo.commit("fix: another bug");
+        let after = repo.commit("feat: something new");
+        repo.commit("fix: more");
+
+        let version_format = VersionFormat::default();
+        let whole = repo.log(false, None).unwrap();
+        let graph = MemoizedCommitGraph::new(
+            Logs::from_log_str(&whole).unwrap(),
+            &version_format,
+            &CCVerConfig::default(),
+        )
+        .unwrap();
+        let mut cache = VersionCache {
+            path: Default::default(),
+            key: Default::default(),
+            tags: Default::default(),
+            entries: Default::default(),
+        };
+        cache.retag(&repo, &repo.branch_tags().unwrap()).unwrap();
+        cache.record(&graph, &version_format);
+        let cached = [&before, &tagged, &after];
+        assert!(
+            cached
+                .iter()
+                .all(|commit| cache.entries.contains_key(*commit))
+        );
+
+        // the same tags change nothing
+        cache.retag(&repo, &repo.branch_tags().unwrap()).unwrap();
+        assert!(
+            cached
+                .iter()
+                .all(|commit| cache.entries.contains_key(*commit))
+        );
+
+        repo.detach(&tagged).unwrap();
+        repo.tag("v0.1.2").unwrap();
+        repo.checkout("main").unwrap();
+        cache.retag(&repo, &repo.branch_tags().unwrap()).unwrap();
+        assert!(cache.entries.contains_key(&before));
+        assert!(!cache.entries.contains_key(&tagged));
+        assert!(!cache.entries.contains_key(&after));
+        assert_eq!(cache.tags["v0.1.2"], tagged);
+    }
+}
@@ -1,20 +1,33 @@
-use std::{cmp::Ordering, fmt::Display, rc::Rc, vec};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::HashSet,
+    fmt::Display,
+    path::Path,
+    rc::Rc,
+    vec,
+};
 
 use eyre::*;
-use petgraph::graph::NodeIndex;
+use petgraph::{csr::IndexType, graph::NodeIndex};
+use serde::{Serialize, Serializer, ser::SerializeStruct as _};
+use tracing::{debug, instrument};
 
 use crate::{
+    changelog_format::ChangeLogRenderer,
+    config::{CCVerConfig, SubjectClass},
     graph::{
-        GraphOps, assign_versions::AsLogEntry, head::HasHead,
-        parents_and_children::HasParentsAndChildren, version::ExistingVersionExt,
-    },
-    logs::{ConventionalSubject, Subject},
-    pattern_macros::{
-        major_commit_types, minor_commit_types, patch_commit_types, semver_advancing_subject,
+        GraphOps,
+        assign_versions::AsLogEntry,
+        head::HasHead,
+        parents_and_children::HasParentsAndChildren,
+        version::{ExistingVersionExt, TaggedVersionExt as _},
     },
+    logs::{ConventionalSubject, LogEntry, Subject},
+    tag_pattern::TagPattern,
+    version::Version,
 };
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Default, PartialEq, Eq)]
 pub struct ChangeLogData(Rc<[ChangeScoped]>);
 pub type ChangeLog = Rc<ChangeLogData>;
 
@@ -31,118 +44,133 @@ impl Ord for ChangeScoped {
 
 impl Display for ChangeLogData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        assert!(self.0.is_sorted());
         writeln!(f, "# ChangeLog",)?;
+        self.write_changes(f, 2)
+    }
+}
+
+impl ChangeLogData {
+    /// The changes in display order: grouped by kind, then scope, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = ChangeEntry<'_>> {
+        self.0.iter().map(ChangeEntry::from)
+    }
+
+    /// Writes the grouped changes with change-type headings at `level`
+    /// (`2` renders `## Features`) and scope headings one level below.
+    fn write_changes(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        assert!(self.0.is_sorted());
+        let section = "#".repeat(level);
+        let subsection = "#".repeat(level + 1);
 
         let mut current_scope: Option<String> = None;
         let mut last_level: Option<String> = None;
         for change in self.0.iter() {
             match change {
                 ChangeScoped::All(change) => match change {
-                    Change::Breaking(desc, date) => {
+                    Change::Breaking(desc, date, commit) => {
                         if last_level != "Breaking Changes".to_string().into() {
-                            writeln!(f, "## Breaking Changes")?;
+                            writeln!(f, "{} Breaking Changes", section)?;
                             last_level = Some("Breaking Changes".to_string());
                         };
 
-                        writeln!(f, "- ({}): {}", date, desc)?;
+                        write_change(f, date, desc, commit)?;
                     }
-                    Change::Feature(desc, date) => {
+                    Change::Feature(desc, date, commit) => {
                         if last_level != "Features".to_string().into() {
-                            writeln!(f, "## Features")?;
+                            writeln!(f, "{} Features", section)?;
                             last_level = Some("Features".to_string());
                         };
 
-                        writeln!(f, "- ({}): {}", date, desc)?;
+                        write_change(f, date, desc, commit)?;
                     }
-                    Change::Fix(desc, date) => {
+                    Change::Fix(desc, date, commit) => {
                         if last_level != "Fixes".to_string().into() {
-                            writeln!(f, "## Fixes")?;
+                            writeln!(f, "{} Fixes", section)?;
                             last_level = Some("Fixes".to_string());
                         };
-                        writeln!(f, "- ({}): {}", date, desc)?;
+                        write_change(f, date, desc, commit)?;
                     }
-                    Change::Named(name, desc, date) => {
+                    Change::Named(name, desc, date, commit) => {
                         if last_level != name.to_string().into() {
-                            writeln!(f, "## {}", name)?;
+                            writeln!(f, "{} {}", section, name)?;
                             last_level = Some(name.to_string());
                         };
 
-                        writeln!(f, "- ({}): {}", date, desc)?;
+                        write_change(f, date, desc, commit)?;
                     }
-                    Change::Misc(desc, date) => {
+                    Change::Misc(desc, date, commit) => {
                         if last_level != "Misc".to_string().into() {
-                            writeln!(f, "## Misc")?;
+                            writeln!(f, "{} Misc", section)?;
                             last_level = Some("Misc".to_string());
                         }
 
-                        writeln!(f, "- ({}): {}", date, desc)?;
+                        write_change(f, date, desc, commit)?;
                     }
                 },
                 ChangeScoped::Scoped(scope, change) => match change {
-                    Change::Breaking(desc, date) => {
+                    Change::Breaking(desc, date, commit) => {
                         if last_level != Some("Breaking Changes".to_string()) {
-                            writeln!(f, "## Breaking Changes")?;
+                            writeln!(f, "{} Breaking Changes", section)?;
                             last_level = Some("Breaking Changes".to_string());
                         };
 
                         if current_scope != Some(scope.clone()) {
-                            writeln!(f, "### {}", scope)?;
+                            writeln!(f, "{} {}", subsection, scope)?;
                             current_scope = Some(scope.clone());
                         };
-                        writeln!(f, "- ({}): {}", date, desc)?;
+                        write_change(f, date, desc, commit)?;
                     }
-                    Change::Feature(desc, date) => {
+                    Change::Feature(desc, date, commit) => {
                         if last_level != Some("Features".to_string()) {
-                            writeln!(f, "## Features")?;
+                            writeln!(f, "{} Features", section)?;
                             last_level = "Features".to_string().into();
                         };
 
                         if current_scope != Some(scope.clone()) {
-                            writeln!(f, "### {}", scope)?;
+                            writeln!(f, "{} {}", subsection, scope)?;
                             current_scope = Some(scope.clone());
                         };
 
-                        writeln!(f, "- ({}): {}", date, desc)?;
+                        write_change(f, date, desc, commit)?;
                     }
-                    Change::Fix(desc, date) => {
+                    Change::Fix(desc, date, commit) => {
                         if last_level != Some("Fixes".to_string()) {
-                            writeln!(f, "## Fixes")?;
+                            writeln!(f, "{} Fixes", section)?;
                             last_level = Some("Fixes".to_string());
                         };
 
                         if current_scope != Some(scope.clone()) {
-                            writeln!(f, "### {}", scope)?;
+                            writeln!(f, "{} {}", subsection, scope)?;
                             current_scope = Some(scope.clone());
                         };
 
-                        writeln!(f, "- ({}): {}", date, desc)?;
+                        write_change(f, date, desc, commit)?;
                     }
-                    Change::Named(name, desc, date) => {
+                    Change::Named(name, desc, date, commit) => {
                         if last_level != Some(name.clone()) {
-                            writeln!(f, "## {}", name)?;
+                            writeln!(f, "{} {}", section, name)?;
                             last_level = Some(name.to_string());
                         };
 
                         if current_scope != Some(scope.clone()) {
-                            writeln!(f, "### {}", scope)?;
+                            writeln!(f, "{} {}", subsection, scope)?;
                             current_scope = Some(scope.clone());
                         };
 
-                        writeln!(f, "- ({}): {}", date, desc)?;
+                        write_change(f, date, desc, commit)?;
                     }
-                    Change::Misc(desc, date) => {
+                    Change::Misc(desc, date, commit) => {
                         if last_level != Some("Misc".to_string()) {
-                            writeln!(f, "## Misc")?;
+                            writeln!(f, "{} Misc", section)?;
                             last_level = Some("Misc".to_string());
                         }
 
                         if current_scope != Some(scope.clone()) {
-                            writeln!(f, "### {}", scope)?;
+                            writeln!(f, "{} {}", subsection, scope)?;
                             current_scope = Some(scope.clone());
                         };
 
-                        writeln!(f, "- ({}): {}", date, desc)?;
+                        write_change(f, date, desc, commit)?;
                     }
                 },
             }
@@ -152,6 +180,15 @@ impl Display for ChangeLogData {
     }
 }
 
+/// A list of entries in display order. `section` is one of `breaking`,
+/// `features`, `fixes`, `named` or `misc`; `type` is the commit type for
+/// `named` entries and `date` is RFC 3339.
+impl Serialize for ChangeLogData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.entries())
+    }
+}
+
 impl PartialOrd for ChangeScoped {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
@@ -166,15 +203,178 @@ enum ChangeScoped {
 
 #[derive(Debug, PartialEq, Eq)]
 enum Change {
-    Breaking(String, chrono::DateTime<chrono::Utc>),
-    Feature(String, chrono::DateTime<chrono::Utc>),
-    Fix(String, chrono::DateTime<chrono::Utc>),
-    Named(String, String, chrono::DateTime<chrono::Utc>),
-    Misc(String, chrono::DateTime<chrono::Utc>),
+    Breaking(String, chrono::DateTime<chrono::Utc>, ChangeCommit),
+    Feature(String, chrono::DateTime<chrono::Utc>, ChangeCommit),
+    Fix(String, chrono::DateTime<chrono::Utc>, ChangeCommit),
+    Named(String, String, chrono::DateTime<chrono::Utc>, ChangeCommit),
+    Misc(String, chrono::DateTime<chrono::Utc>, ChangeCommit),
+}
+
+/// The commit a change came from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeCommit {
+    pub hash: String,
+    pub author: String,
+    /// The commit body without its footers.
+    pub body: String,
+    /// Migration notes from `BREAKING CHANGE:` footers.
+    pub breaking_notes: Vec<String>,
+    /// `Refs`, `Closes`, ... footers as `(key, value)` pairs.
+    pub references: Vec<(String, String)>,
+}
+
+impl ChangeCommit {
+    pub fn short_sha(&self) -> &str {
+        &self.hash[..self.hash.len().min(7)]
+    }
+
+    /// References as `Closes #12, Refs #7`.
+    pub fn references_text(&self) -> String {
+        self.references
+            .iter()
+            .map(|(key, value)| format!("{} {}", key, value))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl From<&LogEntry<'_>> for ChangeCommit {
+    fn from(log_entry: &LogEntry) -> Self {
+        ChangeCommit {
+            hash: log_entry.commit_hash.to_string(),
+            author: log_entry.author.to_string(),
+            body: log_entry.body.to_string(),
+            breaking_notes: log_entry.breaking_notes().map(str::to_string).collect(),
+            references: log_entry
+                .references()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+}
+
+/// Writes one `- (<date>): <description>` line with the commit's references,
+/// followed by its breaking change notes as a quote.
+fn write_change(
+    f: &mut std::fmt::Formatter<'_>,
+    date: &chrono::DateTime<chrono::Utc>,
+    desc: &str,
+    commit: &ChangeCommit,
+) -> std::fmt::Result {
+    write!(f, "- ({}): {}", date, desc)?;
+    if !commit.references.is_empty() {
+        write!(f, " ({})", commit.references_text())?;
+    }
+    writeln!(f)?;
+    for note in &commit.breaking_notes {
+        for line in note.lines() {
+            writeln!(f, "  > {}", line.trim())?;
+        }
+    }
+    std::fmt::Result::Ok(())
+}
+
+/// What kind of change an entry is; decides the heading it is grouped under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind<'a> {
+    Breaking,
+    Feature,
+    Fix,
+    Named(&'a str),
+    Misc,
+}
+
+impl<'a> ChangeKind<'a> {
+    /// Heading used by the Markdown renderers, e.g. `Features` or `docs`.
+    pub fn title(&self) -> &'a str {
+        match self {
+            ChangeKind::Breaking => "Breaking Changes",
+            ChangeKind::Feature => "Features",
+            ChangeKind::Fix => "Fixes",
+            ChangeKind::Named(name) => name,
+            ChangeKind::Misc => "Misc",
+        }
+    }
+
+    /// Stable identifier used in JSON output.
+    pub fn section(&self) -> &'static str {
+        match self {
+            ChangeKind::Breaking => "breaking",
+            ChangeKind::Feature => "features",
+            ChangeKind::Fix => "fixes",
+            ChangeKind::Named(_) => "named",
+            ChangeKind::Misc => "misc",
+        }
+    }
+}
+
+/// A flattened, borrowed view of one change, for renderers.
+#[derive(Debug, Clone, Copy)]
+pub struct ChangeEntry<'a> {
+    pub kind: ChangeKind<'a>,
+    pub scope: Option<&'a str>,
+    pub description: &'a str,
+    pub date: chrono::DateTime<chrono::Utc>,
+    pub commit: &'a ChangeCommit,
+}
+
+impl<'a> From<&'a ChangeScoped> for ChangeEntry<'a> {
+    fn from(change: &'a ChangeScoped) -> Self {
+        let (scope, change) = match change {
+            ChangeScoped::All(change) => (None, change),
+            ChangeScoped::Scoped(scope, change) => (Some(scope.as_str()), change),
+        };
+        let (kind, description, date, commit) = match change {
+            Change::Breaking(desc, date, commit) => (ChangeKind::Breaking, desc, date, commit),
+            Change::Feature(desc, date, commit) => (ChangeKind::Feature, desc, date, commit),
+            Change::Fix(desc, date, commit) => (ChangeKind::Fix, desc, date, commit),
+            Change::Named(name, desc, date, commit) => {
+                (ChangeKind::Named(name.as_str()), desc, date, commit)
+            }
+            Change::Misc(desc, date, commit) => (ChangeKind::Misc, desc, date, commit),
+        };
+        ChangeEntry {
+            kind,
+            scope,
+            description,
+            date: *date,
+            commit,
+        }
+    }
+}
+
+/// `{"section", "type", "scope", "description", "date", "commit", "author"}`.
+impl Serialize for ChangeEntry<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let commit_type = match self.kind {
+            ChangeKind::Named(name) => Some(name),
+            _ => None,
+        };
+        let mut state = serializer.serialize_struct("Change", 10)?;
+        state.serialize_field("section", self.kind.section())?;
+        state.serialize_field("type", &commit_type)?;
+        state.serialize_field("scope", &self.scope)?;
+        state.serialize_field("description", self.description)?;
+        state.serialize_field("date", &self.date.to_rfc3339())?;
+        state.serialize_field("commit", &self.commit.hash)?;
+        state.serialize_field("author", &self.commit.author)?;
+        state.serialize_field("body", &self.commit.body)?;
+        state.serialize_field("breaking_notes", &self.commit.breaking_notes)?;
+        state.serialize_field(
+            "references",
+            &self
+                .commit
+                .references
+                .iter()
+                .map(|(key, value)| serde_json::json!({ "key": key, "value": value }))
+                .collect::<Vec<_>>(),
+        )?;
+        state.end()
+    }
 }
 
 impl ChangeLogData {
-    pub fn new<'a, N, E, Ty, Ix, T>(graph: T) -> Result<ChangeLog>
+    pub fn new<N, E, Ty, Ix, T>(graph: T, config: &CCVerConfig) -> Result<ChangeLog>
     where
         T: GraphOps<N, E, Ty, Ix> + HasHead<N, E, Ty, Ix> + HasParentsAndChildren<N, E, Ty, Ix>,
         N: AsLogEntry + ExistingVersionExt,
@@ -183,10 +383,14 @@ impl ChangeLogData {
         let root = graph
             .head_idx()
             .ok_or_eyre("No head index found in graph")?;
-        Self::from_index(graph, root)
+        Self::from_index(graph, root, config)
     }
 
-    pub fn from_index<'a, N, E, Ty, Ix, T>(graph: T, from: NodeIndex<Ix>) -> Result<ChangeLog>
+    pub fn from_index<N, E, Ty, Ix, T>(
+        graph: T,
+        from: NodeIndex<Ix>,
+        config: &CCVerConfig,
+    ) -> Result<ChangeLog>
     where
         T: GraphOps<N, E, Ty, Ix> + HasParentsAndChildren<N, E, Ty, Ix>,
         N: AsLogEntry + ExistingVersionExt,
@@ -198,108 +402,357 @@ impl ChangeLogData {
             let mut versions = vec![current_ver];
             while let Some(parent_idx) = stack.pop() {
                 let parent = graph.node_weight(parent_idx).unwrap();
-                match parent.as_log_entry().subject {
-                    semver_advancing_subject!() => {}
-                    _ => {
-                        stack.extend(graph.parent_idxs(parent_idx));
-                        versions.push(parent);
-                    }
-                };
+                if !config
+                    .commit_class(&parent.as_log_entry())
+                    .is_semver_advancing()
+                {
+                    stack.extend(graph.parent_idxs(parent_idx));
+                    versions.push(parent);
+                }
             }
             versions
         };
 
-        let mut changes = versions
+        let changes = versions
             .iter()
-            .map(|commit| match &commit.as_log_entry().subject {
-                Subject::Conventional(ConventionalSubject {
-                    commit_type,
-                    scope: None,
-                    description,
-                    ..
-                }) => {
-                    let commit_datetime = commit.as_log_entry().commit_datetime;
-                    match *commit_type {
-                        major_commit_types!() => ChangeScoped::All(Change::Breaking(
-                            description.to_string(),
-                            commit_datetime,
-                        )),
-                        minor_commit_types!() => ChangeScoped::All(Change::Feature(
-                            description.to_string(),
-                            commit_datetime,
-                        )),
-                        patch_commit_types!() => {
-                            ChangeScoped::All(Change::Fix(description.to_string(), commit_datetime))
-                        }
-                        _ => ChangeScoped::All(Change::Named(
-                            commit_type.to_string(),
-                            description.to_string(),
-                            commit_datetime,
-                        )),
-                    }
-                }
-                Subject::Conventional(ConventionalSubject {
-                    commit_type,
-                    scope: Some(scope),
-                    description,
-                    ..
-                }) => {
-                    let commit_datetime = commit.as_log_entry().commit_datetime;
-                    match *commit_type {
-                        major_commit_types!() => ChangeScoped::Scoped(
-                            scope.to_string(),
-                            Change::Breaking(description.to_string(), commit_datetime),
-                        ),
-                        minor_commit_types!() => ChangeScoped::Scoped(
-                            scope.to_string(),
-                            Change::Feature(description.to_string(), commit_datetime),
-                        ),
-                        patch_commit_types!() => ChangeScoped::Scoped(
-                            scope.to_string(),
-                            Change::Fix(description.to_string(), commit_datetime),
-                        ),
-                        _ => ChangeScoped::Scoped(
-                            scope.to_string(),
-                            Change::Named(
-                                commit_type.to_string(),
-                                description.to_string(),
-                                commit_datetime,
-                            ),
-                        ),
-                    }
-                }
-                Subject::Text(t) => {
-                    let commit_datetime = commit.as_log_entry().commit_datetime;
-                    ChangeScoped::All(Change::Misc(t.to_string(), commit_datetime))
-                }
-            })
+            .map(|commit| change_of(&commit.as_log_entry(), config))
             .collect::<Vec<_>>();
 
+        Ok(Self::from_changes(changes))
+    }
+
+    /// Builds one section per release reachable from `to` and not from
+    /// `from` (as in `git log from..to`), newest first. A commit is a release
+    /// when it carries a version tag or was assigned a version without a
+    /// pre-release; its section covers the commits back to (but not
+    /// including) the previous releases. `to` always gets a section, marked
+    /// unreleased when it is not a release itself.
+    pub fn between<N, E, Ty, Ix, T>(
+        graph: &T,
+        to: NodeIndex<Ix>,
+        from: Option<NodeIndex<Ix>>,
+        config: &CCVerConfig,
+    ) -> Result<Vec<ChangeLogRelease>>
+    where
+        T: GraphOps<N, E, Ty, Ix> + HasParentsAndChildren<N, E, Ty, Ix>,
+        N: AsLogEntry + ExistingVersionExt,
+        Ix: IndexType,
+    {
+        let excluded = match from {
+            Some(from) => ancestors(graph, from),
+            None => HashSet::new(),
+        };
+
+        let mut releases = vec![];
+        let mut seen = excluded.clone();
+        let mut stack = vec![to];
+        while let Some(idx) = stack.pop() {
+            if !seen.insert(idx) {
+                continue;
+            }
+            stack.extend(graph.parent_idxs(idx));
+            if idx == to || is_release(graph.node_weight(idx).unwrap()) {
+                releases.push(Self::release_at(graph, idx, &excluded, config)?);
+            }
+        }
+
+        releases.sort_by_key(|release| Reverse(release.date));
+        Ok(releases)
+    }
+
+    /// The section of the release at `idx`, covering the commits back to the
+    /// previous releases.
+    pub fn release<N, E, Ty, Ix, T>(
+        graph: &T,
+        idx: NodeIndex<Ix>,
+        config: &CCVerConfig,
+    ) -> Result<ChangeLogRelease>
+    where
+        T: GraphOps<N, E, Ty, Ix> + HasParentsAndChildren<N, E, Ty, Ix>,
+        N: AsLogEntry + ExistingVersionExt,
+        Ix: IndexType,
+    {
+        Self::release_at(graph, idx, &HashSet::new(), config)
+    }
+
+    fn release_at<N, E, Ty, Ix, T>(
+        graph: &T,
+        idx: NodeIndex<Ix>,
+        excluded: &HashSet<NodeIndex<Ix>>,
+        config: &CCVerConfig,
+    ) -> Result<ChangeLogRelease>
+    where
+        T: GraphOps<N, E, Ty, Ix> + HasParentsAndChildren<N, E, Ty, Ix>,
+        N: AsLogEntry + ExistingVersionExt,
+        Ix: IndexType,
+    {
+        let node = graph
+            .node_weight(idx)
+            .ok_or_eyre("Commit not found in graph")?;
+        let log_entry = node.as_log_entry();
+        let version = node
+            .as_existing_version()
+            .ok_or_eyre("Released commit was not assigned a version")?;
+
+        let mut changes = vec![change_of(&log_entry, config)];
+        let mut seen = HashSet::from([idx]);
+        let mut stack = graph.parent_idxs(idx);
+        while let Some(parent_idx) = stack.pop() {
+            if excluded.contains(&parent_idx) || !seen.insert(parent_idx) {
+                continue;
+            }
+            let parent = graph.node_weight(parent_idx).unwrap();
+            if !is_release(parent) {
+                changes.push(change_of(&parent.as_log_entry(), config));
+                stack.extend(graph.parent_idxs(parent_idx));
+            }
+        }
+
+        Ok(ChangeLogRelease {
+            released: is_release(node),
+            version,
+            commit: log_entry.commit_hash.to_string(),
+            date: log_entry.commit_datetime,
+            changes: Self::from_changes(changes),
+            annotation: None,
+        })
+    }
+
+    fn from_changes(mut changes: Vec<ChangeScoped>) -> ChangeLog {
         changes.sort();
+        Rc::new(Self(changes.into()))
+    }
+}
+
+fn ancestors<N, E, Ty, Ix, T>(graph: &T, from: NodeIndex<Ix>) -> HashSet<NodeIndex<Ix>>
+where
+    T: HasParentsAndChildren<N, E, Ty, Ix>,
+    Ix: IndexType,
+{
+    let mut seen = HashSet::new();
+    let mut stack = vec![from];
+    while let Some(idx) = stack.pop() {
+        if seen.insert(idx) {
+            stack.extend(graph.parent_idxs(idx));
+        }
+    }
+    seen
+}
+
+fn is_release<N: AsLogEntry + ExistingVersionExt>(node: &N) -> bool {
+    node.as_log_entry().as_tagged_version().is_some()
+        || node
+            .as_existing_version()
+            .is_some_and(|version| !version.is_prerelease())
+}
+
+/// The changes that went into one released version.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ChangeLogRelease {
+    /// `false` for the tip of a range that has not been released yet.
+    pub released: bool,
+    pub version: Version,
+    pub commit: String,
+    pub date: chrono::DateTime<chrono::Utc>,
+    pub changes: ChangeLog,
+    /// The message of the release's annotated tag, when there is one.
+    pub annotation: Option<String>,
+}
+
+impl Display for ChangeLogRelease {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.released {
+            true => writeln!(f, "## {} ({})", self.version, self.date.format("%Y-%m-%d"))?,
+            false => writeln!(f, "## Unreleased ({})", self.version)?,
+        }
+        self.changes.write_changes(f, 3)
+    }
+}
+
+/// `{"version", "released", "commit", "date", "entries", "annotation"}`.
+impl Serialize for ChangeLogRelease {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ChangeLogRelease", 6)?;
+        state.serialize_field("version", &self.version)?;
+        state.serialize_field("released", &self.released)?;
+        state.serialize_field("commit", &self.commit)?;
+        state.serialize_field("date", &self.date.to_rfc3339())?;
+        state.serialize_field("entries", self.changes.as_ref())?;
+        state.serialize_field("annotation", &self.annotation)?;
+        state.end()
+    }
+}
+
+/// Prepends the sections of `releases` whose version has no `## <version>`
+/// heading in the changelog at `path` yet, creating the file if needed.
+/// Headings may name the version with or without its `v` prefix, or as a
+/// tag following `tag_pattern`. Everything already in the file is kept byte
+/// for byte. Returns the releases that were written.
+#[instrument(skip(releases, renderer))]
+pub fn write_changelog<'r>(
+    path: &Path,
+    releases: &'r [ChangeLogRelease],
+    renderer: &dyn ChangeLogRenderer,
+    tag_pattern: &TagPattern,
+) -> Result<Vec<&'r ChangeLogRelease>> {
+    if !renderer.incremental() {
+        return Err(eyre!(
+            "This changelog format cannot be prepended to an existing file"
+        ));
+    }
+
+    let existing = match std::fs::read_to_string(path) {
+        std::result::Result::Ok(existing) => existing,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).wrap_err_with(|| format!("Could not read {}", path.display())),
+    };
+
+    let documented = documented_versions(&existing, tag_pattern);
+    let new_releases = releases
+        .iter()
+        .filter(|release| !documented.contains(bare_version(&release.version.to_string())))
+        .collect::<Vec<_>>();
+    debug!(new = new_releases.len(), "Releases missing from changelog");
+    if new_releases.is_empty() {
+        return Ok(new_releases);
+    }
+
+    let sections = new_releases
+        .iter()
+        .map(|release| Ok(format!("{}\n", renderer.section(release)?)))
+        .collect::<Result<String>>()?;
+
+    let first_section = match existing.starts_with("## ") {
+        true => Some(0),
+        false => existing.find("\n## ").map(|at| at + 1),
+    };
+    let updated = match first_section {
+        _ if existing.is_empty() => format!("{}{}", renderer.header(), sections),
+        Some(at) => format!("{}{}{}", &existing[..at], sections, &existing[at..]),
+        None if existing.ends_with('\n') => format!("{}\n{}", existing, sections),
+        None => format!("{}\n\n{}", existing, sections),
+    };
+
+    std::fs::write(path, updated)
+        .wrap_err_with(|| format!("Could not write {}", path.display()))?;
+    Ok(new_releases)
+}
 
-        Ok(Rc::new(Self(changes.into())))
+/// Versions named by `## ` headings, ignoring `[...]` link brackets and
+/// anything after the version, without their `v` prefix. A heading naming
+/// the release's tag counts too.
+fn documented_versions<'c>(changelog: &'c str, tag_pattern: &TagPattern) -> HashSet<&'c str> {
+    changelog
+        .lines()
+        .filter_map(|line| line.strip_prefix("## "))
+        .filter_map(|heading| heading.split_whitespace().next())
+        .map(|name| {
+            let name = name.trim_matches(|c| c == '[' || c == ']');
+            let version = name
+                .strip_prefix(tag_pattern.prefix.as_str())
+                .and_then(|name| name.strip_suffix(tag_pattern.suffix.as_str()))
+                .unwrap_or(name);
+            bare_version(version)
+        })
+        .collect()
+}
+
+/// `version` without its `v` prefix.
+fn bare_version(version: &str) -> &str {
+    version.strip_prefix('v').unwrap_or(version)
+}
+
+fn change_of(log_entry: &LogEntry, config: &CCVerConfig) -> ChangeScoped {
+    let commit = ChangeCommit::from(log_entry);
+    match &log_entry.subject {
+        Subject::Conventional(ConventionalSubject {
+            commit_type,
+            scope: None,
+            description,
+            ..
+        }) => {
+            let commit_datetime = log_entry.commit_datetime;
+            match config.commit_class(log_entry) {
+                SubjectClass::Major => ChangeScoped::All(Change::Breaking(
+                    description.to_string(),
+                    commit_datetime,
+                    commit.clone(),
+                )),
+                SubjectClass::Minor => ChangeScoped::All(Change::Feature(
+                    description.to_string(),
+                    commit_datetime,
+                    commit.clone(),
+                )),
+                SubjectClass::Patch => ChangeScoped::All(Change::Fix(
+                    description.to_string(),
+                    commit_datetime,
+                    commit.clone(),
+                )),
+                _ => ChangeScoped::All(Change::Named(
+                    commit_type.to_string(),
+                    description.to_string(),
+                    commit_datetime,
+                    commit.clone(),
+                )),
+            }
+        }
+        Subject::Conventional(ConventionalSubject {
+            commit_type,
+            scope: Some(scope),
+            description,
+            ..
+        }) => {
+            let commit_datetime = log_entry.commit_datetime;
+            match config.commit_class(log_entry) {
+                SubjectClass::Major => ChangeScoped::Scoped(
+                    scope.to_string(),
+                    Change::Breaking(description.to_string(), commit_datetime, commit.clone()),
+                ),
+                SubjectClass::Minor => ChangeScoped::Scoped(
+                    scope.to_string(),
+                    Change::Feature(description.to_string(), commit_datetime, commit.clone()),
+                ),
+                SubjectClass::Patch => ChangeScoped::Scoped(
+                    scope.to_string(),
+                    Change::Fix(description.to_string(), commit_datetime, commit.clone()),
+                ),
+                _ => ChangeScoped::Scoped(
+                    scope.to_string(),
+                    Change::Named(
+                        commit_type.to_string(),
+                        description.to_string(),
+                        commit_datetime,
+                        commit.clone(),
+                    ),
+                ),
+            }
+        }
+        Subject::Text(t) => {
+            let commit_datetime = log_entry.commit_datetime;
+            ChangeScoped::All(Change::Misc(t.to_string(), commit_datetime, commit.clone()))
+        }
     }
 }
 
 impl Ord for Change {
     fn cmp(&self, other: &Self) -> Ordering {
         match (self, other) {
-            (Change::Breaking(_, a), Change::Breaking(_, b)) => a.cmp(b),
-            (Change::Feature(_, a), Change::Feature(_, b)) => a.cmp(b),
-            (Change::Fix(_, a), Change::Fix(_, b)) => a.cmp(b),
-            (Change::Named(_, _, a), Change::Named(_, _, b)) => a.cmp(b),
-            (Change::Misc(_, a), Change::Misc(_, b)) => a.cmp(b),
-            (Change::Breaking(_, _), _) => Ordering::Less,
-            (Change::Feature(_, _), Change::Breaking(_, _)) => Ordering::Greater,
-            (Change::Feature(_, _), _) => Ordering::Less,
-            (Change::Fix(_, _), Change::Breaking(_, _)) => Ordering::Greater,
-            (Change::Fix(_, _), Change::Feature(_, _)) => Ordering::Greater,
-            (Change::Fix(_, _), _) => Ordering::Less,
-            (Change::Named(_, _, _), Change::Breaking(_, _)) => Ordering::Greater,
-            (Change::Named(_, _, _), Change::Feature(_, _)) => Ordering::Greater,
-            (Change::Named(_, _, _), Change::Fix(_, _)) => Ordering::Greater,
-            (Change::Named(_, _, _), _) => Ordering::Less,
-            (Change::Misc(_, _), _) => Ordering::Greater,
+            (Change::Breaking(_, a, _), Change::Breaking(_, b, _)) => a.cmp(b),
+            (Change::Feature(_, a, _), Change::Feature(_, b, _)) => a.cmp(b),
+            (Change::Fix(_, a, _), Change::Fix(_, b, _)) => a.cmp(b),
+            (Change::Named(_, _, a, _), Change::Named(_, _, b, _)) => a.cmp(b),
+            (Change::Misc(_, a, _), Change::Misc(_, b, _)) => a.cmp(b),
+            (Change::Breaking(_, _, _), _) => Ordering::Less,
+            (Change::Feature(_, _, _), Change::Breaking(_, _, _)) => Ordering::Greater,
+            (Change::Feature(_, _, _), _) => Ordering::Less,
+            (Change::Fix(_, _, _), Change::Breaking(_, _, _)) => Ordering::Greater,
+            (Change::Fix(_, _, _), Change::Feature(_, _, _)) => Ordering::Greater,
+            (Change::Fix(_, _, _), _) => Ordering::Less,
+            (Change::Named(_, _, _, _), Change::Breaking(_, _, _)) => Ordering::Greater,
+            (Change::Named(_, _, _, _), Change::Feature(_, _, _)) => Ordering::Greater,
+            (Change::Named(_, _, _, _), Change::Fix(_, _, _)) => Ordering::Greater,
+            (Change::Named(_, _, _, _), _) => Ordering::Less,
+            (Change::Misc(_, _, _), _) => Ordering::Greater,
         }
     }
 }
@@ -317,7 +770,10 @@ mod changelog_tests {
     use chrono::Timelike;
     use indoc::*;
 
-    use crate::changelog::{Change, ChangeLogData, ChangeScoped};
+    use crate::{
+        changelog::{Change, ChangeCommit, ChangeLogData, ChangeLogRelease, ChangeScoped},
+        version_format::VersionFormat,
+    };
 
     #[test]
     fn test_display_changelog() {
@@ -326,14 +782,17 @@ mod changelog_tests {
             ChangeScoped::All(Change::Breaking(
                 "Added Emojis".to_string(),
                 dummy_date.with_hour(1).unwrap(),
+                ChangeCommit::default(),
             )),
             ChangeScoped::All(Change::Feature(
                 "Temp Removed Emojis".to_string(),
                 dummy_date.with_hour(2).unwrap(),
+                ChangeCommit::default(),
             )),
             ChangeScoped::All(Change::Fix(
                 "Fixed Emojis".to_string(),
                 dummy_date.with_hour(3).unwrap(),
+                ChangeCommit::default(),
             )),
             ChangeScoped::Scoped(
                 "./src/emoji.rs".to_string(),
@@ -341,6 +800,7 @@ mod changelog_tests {
                     "docs".to_string(),
                     "Documented Emojis".to_string(),
                     dummy_date.with_hour(4).unwrap(),
+                    ChangeCommit::default(),
                 ),
             ),
         ]));
@@ -363,4 +823,142 @@ mod changelog_tests {
             }
         );
     }
+
+    #[test]
+    fn test_json_changelog() {
+        let dummy_date = chrono::DateTime::from_timestamp(0, 0).unwrap();
+        let cl = ChangeLogData(Rc::new([
+            ChangeScoped::All(Change::Fix(
+                "Fixed Emojis".to_string(),
+                dummy_date,
+                ChangeCommit::default(),
+            )),
+            ChangeScoped::Scoped(
+                "emoji".to_string(),
+                Change::Named(
+                    "docs".to_string(),
+                    "Documented Emojis".to_string(),
+                    dummy_date,
+                    ChangeCommit::default(),
+                ),
+            ),
+        ]));
+
+        assert_eq!(
+            serde_json::to_value(&cl).unwrap(),
+            serde_json::json!([
+                {
+                    "section": "fixes",
+                    "type": null,
+                    "scope": null,
+                    "description": "Fixed Emojis",
+                    "date": "1970-01-01T00:00:00+00:00",
+                    "commit": "",
+                    "author": "",
+                    "body": "",
+                    "breaking_notes": [],
+                    "references": [],
+                },
+                {
+                    "section": "named",
+                    "type": "docs",
+                    "scope": "emoji",
+                    "description": "Documented Emojis",
+                    "date": "1970-01-01T00:00:00+00:00",
+                    "commit": "",
+                    "author": "",
+                    "body": "",
+                    "breaking_notes": [],
+                    "references": [],
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_write_changelog_prepends_new_sections() {
+        let dummy_date = chrono::DateTime::from_timestamp(0, 0).unwrap();
+        let release = |version: &str, desc: &str| ChangeLogRelease {
+            released: true,
+            version: crate::parser::parse_version(version, VersionFormat::default()).unwrap(),
+            commit: "0".repeat(40),
+            date: dummy_date,
+            changes: Rc::new(ChangeLogData(Rc::new([ChangeScoped::All(Change::Fix(
+                desc.to_string(),
+                dummy_date,
+                ChangeCommit::default(),
+            ))]))),
+            annotation: None,
+        };
+
+        let dir = crate::test_repo::ScratchDir::new("changelog");
+        let path = dir.path().join("CHANGELOG.md");
+        std::fs::write(
+            &path,
+            "# ChangeLog\n\nIntro kept as is.\n\n## [v0.1.0] - hand written\n- edited\n",
+        )
+        .unwrap();
+
+        let releases = [release("v0.2.0", "new fix"), release("v0.1.0", "old fix")];
+        let written = super::write_changelog(
+            &path,
+            &releases,
+            &crate::changelog_format::MarkdownRenderer,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(written.len(), 1);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            contents,
+            indoc! {"
+            # ChangeLog
+
+            Intro kept as is.
+
+            ## v0.2.0 (1970-01-01)
+            ### Fixes
+            - (1970-01-01 00:00:00 UTC): new fix
+
+            ## [v0.1.0] - hand written
+            - edited
+            "}
+        );
+    }
+
+    #[test]
+    fn test_documented_versions_match_tags() {
+        let changelog = "## [1.0.0] - 2024-01-01\n## api-v0.9.0\n## v0.8.0 (2023-01-01)\n";
+        let tag_pattern = "api-{version}".parse::<super::TagPattern>().unwrap();
+        let documented = super::documented_versions(changelog, &tag_pattern);
+        for version in ["1.0.0", "0.9.0", "0.8.0"] {
+            assert!(documented.contains(version), "{}", version);
+        }
+        assert_eq!(super::bare_version("v1.0.0"), "1.0.0");
+    }
+
+    #[test]
+    fn test_between_covers_range() {
+        use crate::{config::CCVerConfig, graph::MemoizedCommitGraph, logs::Logs};
+
+        let config = CCVerConfig::default();
+        let raw = crate::git::formatted_logs(&std::env::current_dir().unwrap()).unwrap();
+        let logs = Logs::from_log_str(&raw).unwrap();
+        let graph = MemoizedCommitGraph::new(logs, &VersionFormat::default(), &config).unwrap();
+        let head = graph.head_idx().unwrap();
+
+        let all = ChangeLogData::between(&graph, head, None, &config).unwrap();
+        let head_hash = graph
+            .node_weight(head)
+            .unwrap()
+            .lock()
+            .unwrap()
+            .log_entry
+            .commit_hash;
+        assert!(all.iter().any(|release| release.commit == head_hash));
+
+        let empty = ChangeLogData::between(&graph, head, Some(head), &config).unwrap();
+        assert!(empty.is_empty());
+    }
 }
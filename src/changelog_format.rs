@@ -0,0 +1,544 @@
+use std::{fmt::Write as _, path::Path, str::FromStr};
+
+use clap::ValueEnum;
+use eyre::*;
+
+use crate::changelog::{ChangeEntry, ChangeKind, ChangeLogRelease};
+
+/// Built-in layouts selectable with `--changelog-format`.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChangeLogFormat {
+    /// ccver's own Markdown layout.
+    #[default]
+    Markdown,
+    /// <https://keepachangelog.com>
+    KeepAChangelog,
+    /// Markdown suited to a GitHub release body.
+    Github,
+    Json,
+    Html,
+}
+
+impl ChangeLogFormat {
+    pub fn renderer(self) -> Box<dyn ChangeLogRenderer> {
+        match self {
+            ChangeLogFormat::Markdown => Box::new(MarkdownRenderer),
+            ChangeLogFormat::KeepAChangelog => Box::new(KeepAChangelogRenderer),
+            ChangeLogFormat::Github => Box::new(GithubRenderer),
+            ChangeLogFormat::Json => Box::new(JsonRenderer),
+            ChangeLogFormat::Html => Box::new(HtmlRenderer),
+        }
+    }
+}
+
+/// Turns release sections into a changelog document.
+pub trait ChangeLogRenderer {
+    /// Written once at the top of a new changelog file.
+    fn header(&self) -> String;
+
+    /// One release section, ending with a newline.
+    fn section(&self, release: &ChangeLogRelease) -> Result<String>;
+
+    /// Whether sections can be prepended to an existing file with
+    /// `changelog --write`, which looks for `## <version>` headings.
+    fn incremental(&self) -> bool {
+        true
+    }
+
+    /// A complete document for `releases`, newest first.
+    fn render(&self, releases: &[&ChangeLogRelease]) -> Result<String> {
+        let sections = releases
+            .iter()
+            .map(|release| self.section(release))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(format!("{}{}", self.header(), sections.join("\n")))
+    }
+}
+
+/// Groups consecutive entries under their [`ChangeKind::title`], keeping the
+/// display order of [`crate::changelog::ChangeLogData::entries`].
+fn grouped<'a>(release: &'a ChangeLogRelease) -> Vec<(&'a str, Vec<ChangeEntry<'a>>)> {
+    let mut groups: Vec<(&str, Vec<ChangeEntry>)> = vec![];
+    for entry in release.changes.entries() {
+        match groups.last_mut() {
+            Some((title, entries)) if *title == entry.kind.title() => entries.push(entry),
+            _ => groups.push((entry.kind.title(), vec![entry])),
+        }
+    }
+    groups
+}
+
+fn scoped(entry: &ChangeEntry) -> String {
+    match entry.scope {
+        Some(scope) => format!("**{}:** {}", scope, entry.description),
+        None => entry.description.to_string(),
+    }
+}
+
+/// ` (Closes #12, Refs #7)` or nothing.
+fn references(entry: &ChangeEntry) -> String {
+    match entry.commit.references.is_empty() {
+        true => String::new(),
+        false => format!(" ({})", entry.commit.references_text()),
+    }
+}
+
+/// Breaking change notes quoted below a Markdown list item.
+fn write_notes(out: &mut String, entry: &ChangeEntry) -> Result<()> {
+    for note in &entry.commit.breaking_notes {
+        for line in note.lines() {
+            writeln!(out, "  > {}", line.trim())?;
+        }
+    }
+    Ok(())
+}
+
+pub struct MarkdownRenderer;
+
+impl ChangeLogRenderer for MarkdownRenderer {
+    fn header(&self) -> String {
+        "# ChangeLog\n\n".to_string()
+    }
+
+    fn section(&self, release: &ChangeLogRelease) -> Result<String> {
+        Ok(release.to_string())
+    }
+}
+
+pub struct KeepAChangelogRenderer;
+
+impl ChangeLogRenderer for KeepAChangelogRenderer {
+    fn header(&self) -> String {
+        concat!(
+            "# Changelog\n\n",
+            "All notable changes to this project will be documented in this file.\n\n",
+            "The format is based on [Keep a Changelog](https://keepachangelog.com/en/1.1.0/),\n",
+            "and this project adheres to [Semantic Versioning](https://semver.org/spec/v2.0.0.html).\n\n",
+        )
+        .to_string()
+    }
+
+    fn section(&self, release: &ChangeLogRelease) -> Result<String> {
+        let mut out = match release.released {
+            true => format!(
+                "## [{}] - {}\n",
+                release.version,
+                release.date.format("%Y-%m-%d")
+            ),
+            false => "## [Unreleased]\n".to_string(),
+        };
+
+        type Belongs = fn(&ChangeKind) -> bool;
+        let categories: [(&str, Belongs); 3] = [
+            ("Added", |kind| *kind == ChangeKind::Feature),
+            ("Changed", |kind| {
+                matches!(
+                    kind,
+                    ChangeKind::Breaking | ChangeKind::Named(_) | ChangeKind::Misc
+                )
+            }),
+            ("Fixed", |kind| *kind == ChangeKind::Fix),
+        ];
+        for (category, belongs) in categories {
+            let entries = release
+                .changes
+                .entries()
+                .filter(|entry| belongs(&entry.kind))
+                .collect::<Vec<_>>();
+            if entries.is_empty() {
+                continue;
+            }
+            writeln!(out, "### {}", category)?;
+            for entry in entries {
+                match entry.kind {
+                    ChangeKind::Breaking => writeln!(
+                        out,
+                        "- **BREAKING:** {}{}",
+                        scoped(&entry),
+                        references(&entry)
+                    )?,
+                    _ => writeln!(out, "- {}{}", scoped(&entry), references(&entry))?,
+                }
+                write_notes(&mut out, &entry)?;
+            }
+        }
+        Ok(out)
+    }
+}
+
+pub struct GithubRenderer;
+
+impl ChangeLogRenderer for GithubRenderer {
+    fn header(&self) -> String {
+        String::new()
+    }
+
+    fn section(&self, release: &ChangeLogRelease) -> Result<String> {
+        let mut out = format!("## {}\n", release.version);
+        for (title, entries) in grouped(release) {
+            writeln!(out, "### {}", title)?;
+            for entry in entries {
+                write!(out, "* {}", scoped(&entry))?;
+                if !entry.commit.author.is_empty() {
+                    write!(out, " by {}", entry.commit.author)?;
+                }
+                writeln!(
+                    out,
+                    " in {}{}",
+                    entry.commit.short_sha(),
+                    references(&entry)
+                )?;
+                write_notes(&mut out, &entry)?;
+            }
+        }
+        Ok(out)
+    }
+}
+
+pub struct JsonRenderer;
+
+impl ChangeLogRenderer for JsonRenderer {
+    fn header(&self) -> String {
+        String::new()
+    }
+
+    fn section(&self, release: &ChangeLogRelease) -> Result<String> {
+        Ok(serde_json::to_string_pretty(release)? + "\n")
+    }
+
+    fn incremental(&self) -> bool {
+        false
+    }
+
+    fn render(&self, releases: &[&ChangeLogRelease]) -> Result<String> {
+        Ok(serde_json::to_string_pretty(releases)?)
+    }
+}
+
+pub struct HtmlRenderer;
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl ChangeLogRenderer for HtmlRenderer {
+    fn header(&self) -> String {
+        "<h1>ChangeLog</h1>\n".to_string()
+    }
+
+    fn section(&self, release: &ChangeLogRelease) -> Result<String> {
+        let mut out = "<section>\n".to_string();
+        match release.released {
+            true => writeln!(
+                out,
+                "<h2>{} <small>{}</small></h2>",
+                escape_html(&release.version.to_string()),
+                release.date.format("%Y-%m-%d")
+            )?,
+            false => writeln!(
+                out,
+                "<h2>Unreleased <small>{}</small></h2>",
+                escape_html(&release.version.to_string())
+            )?,
+        }
+        for (title, entries) in grouped(release) {
+            writeln!(out, "<h3>{}</h3>\n<ul>", escape_html(title))?;
+            for entry in entries {
+                write!(out, "<li>")?;
+                if let Some(scope) = entry.scope {
+                    write!(out, "<strong>{}:</strong> ", escape_html(scope))?;
+                }
+                write!(
+                    out,
+                    "{} <code>{}</code>",
+                    escape_html(entry.description),
+                    entry.commit.short_sha()
+                )?;
+                for (key, value) in &entry.commit.references {
+                    match value.starts_with("https://") || value.starts_with("http://") {
+                        true => write!(
+                            out,
+                            " {} <a href=\"{}\">{}</a>",
+                            escape_html(key),
+                            escape_html(value),
+                            escape_html(value)
+                        )?,
+                        false => write!(out, " {} {}", escape_html(key), escape_html(value))?,
+                    }
+                }
+                for note in &entry.commit.breaking_notes {
+                    write!(out, "<blockquote>{}</blockquote>", escape_html(note))?;
+                }
+                writeln!(out, "</li>")?;
+            }
+            writeln!(out, "</ul>")?;
+        }
+        writeln!(out, "</section>")?;
+        Ok(out)
+    }
+
+    fn incremental(&self) -> bool {
+        false
+    }
+}
+
+/// Placeholders available once per release and once per change.
+const RELEASE_PLACEHOLDERS: [&str; 4] = ["version", "date", "short_sha", "annotation"];
+const ENTRY_PLACEHOLDERS: [&str; 10] = [
+    "version",
+    "date",
+    "type",
+    "scope",
+    "description",
+    "short_sha",
+    "author",
+    "body",
+    "breaking_notes",
+    "references",
+];
+
+/// A user-supplied layout:
+///
+/// ```text
+/// ## {{version}} ({{date}})
+/// {{#entries}}
+/// - {{type}}{{scope}}: {{description}} ({{short_sha}} by {{author}})
+/// {{/entries}}
+/// ```
+///
+/// The text before `{{#entries}}` and after `{{/entries}}` is rendered once
+/// per release with `version`, `date`, `short_sha` and `annotation` (the
+/// message of the release's annotated tag, if any); the text between them
+/// once per change, which additionally has `type` (the heading the change is
+/// grouped under), `scope`, `description`, `body`, `breaking_notes` and
+/// `references` (`Closes #12, Refs #7`) and uses the change's own `date`,
+/// `short_sha` and `author`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateRenderer {
+    head: String,
+    entry: String,
+    tail: String,
+}
+
+impl TemplateRenderer {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Could not read changelog template {}", path.display()))?;
+        raw.parse()
+            .wrap_err_with(|| format!("Invalid changelog template {}", path.display()))
+    }
+}
+
+impl FromStr for TemplateRenderer {
+    type Err = Report;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        let (head, rest) = raw
+            .split_once("{{#entries}}")
+            .ok_or_eyre("Template must contain an {{#entries}} ... {{/entries}} block")?;
+        let (entry, tail) = rest
+            .split_once("{{/entries}}")
+            .ok_or_eyre("Template is missing {{/entries}}")?;
+
+        check_placeholders(head, &RELEASE_PLACEHOLDERS)?;
+        check_placeholders(entry, &ENTRY_PLACEHOLDERS)?;
+        check_placeholders(tail, &RELEASE_PLACEHOLDERS)?;
+
+        Ok(TemplateRenderer {
+            head: head.to_string(),
+            entry: entry.trim_start_matches('\n').to_string(),
+            tail: tail.trim_start_matches('\n').to_string(),
+        })
+    }
+}
+
+fn check_placeholders(template: &str, allowed: &[&str]) -> Result<()> {
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| eyre!("Unclosed placeholder in `{}`", &rest[start..]))?;
+        let name = &rest[start + 2..start + end];
+        if !allowed.contains(&name) {
+            return Err(eyre!(
+                "Unknown placeholder {{{{{}}}}}; expected one of {}",
+                name,
+                allowed.join(", ")
+            ));
+        }
+        rest = &rest[start + end + 2..];
+    }
+    Ok(())
+}
+
+/// Substitutes the placeholders of `template` in a single pass, so that
+/// placeholders inside the values are written as they are.
+fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{")
+        && let Some(end) = rest[start..].find("}}")
+    {
+        let name = &rest[start + 2..start + end];
+        out.push_str(&rest[..start]);
+        match values.iter().find(|(placeholder, _)| *placeholder == name) {
+            Some((_, value)) => out.push_str(value),
+            None => out.push_str(&rest[start..start + end + 2]),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+impl ChangeLogRenderer for TemplateRenderer {
+    fn header(&self) -> String {
+        String::new()
+    }
+
+    fn section(&self, release: &ChangeLogRelease) -> Result<String> {
+        let version = release.version.to_string();
+        let date = release.date.format("%Y-%m-%d").to_string();
+        let short_sha = &release.commit[..release.commit.len().min(7)];
+        let release_values = [
+            ("version", version.as_str()),
+            ("date", date.as_str()),
+            ("short_sha", short_sha),
+            (
+                "annotation",
+                release.annotation.as_deref().unwrap_or_default(),
+            ),
+        ];
+
+        let mut out = fill(&self.head, &release_values);
+        for entry in release.changes.entries() {
+            let date = entry.date.format("%Y-%m-%d").to_string();
+            let breaking_notes = entry.commit.breaking_notes.join("\n");
+            let references = entry.commit.references_text();
+            out.push_str(&fill(
+                &self.entry,
+                &[
+                    ("version", version.as_str()),
+                    ("date", date.as_str()),
+                    ("type", entry.kind.title()),
+                    ("scope", entry.scope.unwrap_or_default()),
+                    ("description", entry.description),
+                    ("short_sha", entry.commit.short_sha()),
+                    ("author", entry.commit.author.as_str()),
+                    ("body", entry.commit.body.as_str()),
+                    ("breaking_notes", breaking_notes.as_str()),
+                    ("references", references.as_str()),
+                ],
+            ));
+        }
+        out.push_str(&fill(&self.tail, &release_values));
+        Ok(out)
+    }
+}
+
+/// Uses the message of a release's annotated tag as its section, falling
+/// back to another renderer for releases without one.
+pub struct TagMessageRenderer(pub Box<dyn ChangeLogRenderer>);
+
+impl ChangeLogRenderer for TagMessageRenderer {
+    fn header(&self) -> String {
+        self.0.header()
+    }
+
+    fn section(&self, release: &ChangeLogRelease) -> Result<String> {
+        match &release.annotation {
+            Some(message) => Ok(format!("{}\n", message.trim_end())),
+            None => self.0.section(release),
+        }
+    }
+
+    fn incremental(&self) -> bool {
+        self.0.incremental()
+    }
+}
+
+#[cfg(test)]
+mod changelog_format_tests {
+    use std::rc::Rc;
+
+    use indoc::indoc;
+
+    use super::{ChangeLogFormat, ChangeLogRenderer as _, TagMessageRenderer, TemplateRenderer};
+    use crate::{
+        changelog::{ChangeLogData, ChangeLogRelease},
+        parser::parse_version,
+        version_format::VersionFormat,
+    };
+
+    fn release() -> ChangeLogRelease {
+        ChangeLogRelease {
+            released: true,
+            version: parse_version("v1.2.0", VersionFormat::default()).unwrap(),
+            commit: "0123456789abcdef0123456789abcdef01234567".to_string(),
+            date: chrono::DateTime::from_timestamp(0, 0).unwrap(),
+            changes: Rc::new(ChangeLogData::default()),
+            annotation: None,
+        }
+    }
+
+    #[test]
+    fn test_keep_a_changelog_heading() {
+        let rendered = ChangeLogFormat::KeepAChangelog
+            .renderer()
+            .section(&release())
+            .unwrap();
+        assert_eq!(rendered, "## [v1.2.0] - 1970-01-01\n");
+    }
+
+    #[test]
+    fn test_template() {
+        let template: TemplateRenderer = indoc! {"
+            ## {{version}} ({{date}})
+            {{#entries}}
+            - {{description}}
+            {{/entries}}
+            built from {{short_sha}}
+        "}
+        .parse()
+        .unwrap();
+        assert_eq!(
+            template.section(&release()).unwrap(),
+            "## v1.2.0 (1970-01-01)\nbuilt from 0123456\n"
+        );
+
+        let unknown = "{{#entries}}{{whatever}}{{/entries}}".parse::<TemplateRenderer>();
+        assert!(unknown.is_err());
+    }
+
+    #[test]
+    fn test_fill_substitutes_once() {
+        let values = [("description", "print {{version}}"), ("version", "v1.2.0")];
+        assert_eq!(
+            super::fill("- {{description}} in {{version}}", &values),
+            "- print {{version}} in v1.2.0"
+        );
+    }
+
+    #[test]
+    fn test_tag_messages() {
+        let renderer = TagMessageRenderer(ChangeLogFormat::Markdown.renderer());
+        assert_eq!(
+            renderer.section(&release()).unwrap(),
+            ChangeLogFormat::Markdown
+                .renderer()
+                .section(&release())
+                .unwrap()
+        );
+        let annotated = ChangeLogRelease {
+            annotation: Some("## v1.2.0\n\nHand written notes\n\n".to_string()),
+            ..release()
+        };
+        assert_eq!(
+            renderer.section(&annotated).unwrap(),
+            "## v1.2.0\n\nHand written notes\n"
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::{
+    config::CCVerConfig,
+    lint::{self, is_exempt},
+    logs::{ConventionalSubject, LogEntry, Subject},
+    output::CommitRef,
+};
+
+/// A commit in the checked range whose subject would not be accepted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CheckProblem {
+    pub commit: CommitRef,
+    pub subject: String,
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+impl Display for CheckProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}\n  {}",
+            &self.commit.hash[..self.commit.hash.len().min(7)],
+            self.subject,
+            self.message
+        )?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\n  help: {}", suggestion)?;
+        }
+        std::fmt::Result::Ok(())
+    }
+}
+
+/// Checks every commit in `logs`: merges and subjects git writes itself are
+/// skipped, text subjects are rejected with the same diagnosis `ccver lint`
+/// gives, and conventional subjects must use a type listed in
+/// `[commit_types]`.
+pub fn check_commits<'a>(
+    logs: impl IntoIterator<Item = &'a LogEntry<'a>>,
+    config: &CCVerConfig,
+) -> Vec<CheckProblem> {
+    logs.into_iter()
+        .filter(|log_entry| log_entry.parent_hashes.len() < 2)
+        .filter_map(|log_entry| {
+            let (message, suggestion) = match &log_entry.subject {
+                Subject::Text(subject) if is_exempt(subject) => return None,
+                // the lint only adds a diagnosis; the subject is rejected anyway
+                Subject::Text(subject) => match lint::lint_message(subject).into_iter().next() {
+                    Some(problem) => (
+                        format!("not a conventional commit: {}", problem.message),
+                        problem.suggestion,
+                    ),
+                    None => ("not a conventional commit".to_string(), None),
+                },
+                Subject::Conventional(ConventionalSubject { commit_type, .. })
+                    if !config.commit_types.is_allowed(commit_type) =>
+                {
+                    (
+                        format!("`{}` is not an allowed commit type", commit_type),
+                        Some(format!(
+                            "use one of {}",
+                            config.commit_types.allowed().collect::<Vec<_>>().join(", ")
+                        )),
+                    )
+                }
+                Subject::Conventional(_) => return None,
+            };
+            Some(CheckProblem {
+                commit: CommitRef::from(log_entry),
+                subject: log_entry.subject.to_string(),
+                message,
+                suggestion,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod check_tests {
+    use super::check_commits;
+    use crate::{
+        config::CCVerConfig,
+        logs::{PEEK_COMMIT_HASH, PeekLogEntry as _},
+    };
+
+    #[test]
+    fn reports_text_and_disallowed_types() {
+        let config = CCVerConfig::default();
+        let logs = [
+            "feat(check): gate pull requests"
+                .into_peek_log_entry(PEEK_COMMIT_HASH, "main")
+                .unwrap(),
+            "Merge branch 'main' into topic"
+                .into_peek_log_entry(PEEK_COMMIT_HASH, "main")
+                .unwrap(),
+            "wip: half done"
+                .into_peek_log_entry(PEEK_COMMIT_HASH, "main")
+                .unwrap(),
+            "quick fix"
+                .into_peek_log_entry(PEEK_COMMIT_HASH, "main")
+                .unwrap(),
+        ];
+
+        let problems = check_commits(&logs, &config);
+        assert_eq!(problems.len(), 2);
+        assert_eq!(problems[0].subject, "wip: half done");
+        assert_eq!(problems[0].message, "`wip` is not an allowed commit type");
+        assert_eq!(problems[1].subject, "quick fix");
+        assert_eq!(
+            problems[1].suggestion.as_deref(),
+            Some("start the subject with a type and a colon, e.g. `fix: quick fix`")
+        );
+    }
+
+    #[test]
+    fn reports_text_the_linter_accepts() {
+        let logs = [" feat: indented"
+            .into_peek_log_entry(PEEK_COMMIT_HASH, "main")
+            .unwrap()];
+        let problems = check_commits(&logs, &CCVerConfig::default());
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].message, "not a conventional commit");
+        assert_eq!(problems[0].suggestion, None);
+    }
+}
@@ -0,0 +1,839 @@
+use std::{fmt::Display, path::Path};
+
+use eyre::*;
+use serde::Serialize;
+use toml_edit::{DocumentMut, Item, TableLike};
+use tracing::{debug, info, instrument};
+
+use crate::{
+    logs::{ConventionalSubject, LogEntry, Subject},
+    package::Package,
+    tag_pattern::TagPattern,
+    version::{Version, VersionNumber},
+};
+
+/// Files searched (in order) at the repository root for ccver configuration.
+/// `Cargo.toml` is only used when it carries a `[package.metadata.ccver]` table.
+pub const CONFIG_FILE_NAMES: [&str; 3] = ["ccver.toml", ".ccver", "Cargo.toml"];
+
+/// Runtime rules used to classify branches and commit subjects.
+///
+/// ```toml
+/// [branches]
+/// release = ["main", "trunk", "hotfix/*"]
+/// maintenance = ["release/{major}.x", "support/{major}.{minor}"]
+/// rc = ["staging", "rc"]
+/// beta = ["develop", "beta"]
+/// alpha = ["next", "alpha"]
+///
+/// [commit_types]
+/// major = ["breaking", "major"]
+/// minor = ["feat", "feature", "minor"]
+/// patch = ["fix", "bug", "patch"]
+/// other = ["build", "chore", "ci", "docs", "perf", "refactor", "revert", "style", "test"]
+///
+/// [tags]
+/// pattern = "v{version}"
+/// annotate = true
+/// message_template = ".github/tag-message.md"
+///
+/// [versioning]
+/// initial_development = true
+///
+/// [packages.api]
+/// path = "services/api"
+/// tag_prefix = "api-"
+/// ```
+///
+/// Keys that are left out keep their default values.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CCVerConfig {
+    pub branches: BranchRules,
+    pub commit_types: CommitTypeRules,
+    /// Names version tags, both when reading and when creating them.
+    pub tag_pattern: TagPattern,
+    /// `tag` creates annotated tags whose message is the release's changelog.
+    pub annotate_tags: bool,
+    /// Changelog template file (relative to the repository root) that
+    /// renders annotated tag messages.
+    pub tag_message_template: Option<String>,
+    /// While the major version is 0, breaking changes bump the minor version
+    /// and features the patch version.
+    pub initial_development: bool,
+    /// Packages versioned independently, in the order they are declared.
+    pub packages: Vec<Package>,
+    /// Bump applied to HEAD regardless of its subject. Set from the
+    /// `--force-*` flags; never read from a config file.
+    pub force_bump: Option<Bump>,
+    /// Release 1.0.0 at HEAD when it is still in initial development. Set
+    /// from `--graduate`; never read from a config file.
+    pub graduate: bool,
+}
+
+/// Branch name globs for each prerelease channel. Branches matching none of
+/// them get a prerelease named after the branch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchRules {
+    pub release: Vec<String>,
+    /// Branch name patterns carrying a `{major}` or `{major}.{minor}` line,
+    /// such as `release/{major}.x`. Checked before every other rule.
+    pub maintenance: Vec<String>,
+    pub rc: Vec<String>,
+    pub beta: Vec<String>,
+    pub alpha: Vec<String>,
+}
+
+/// Conventional commit types for each bump level, plus the types that are
+/// allowed without bumping (checked by `ccver check`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitTypeRules {
+    pub major: Vec<String>,
+    pub minor: Vec<String>,
+    pub patch: Vec<String>,
+    pub other: Vec<String>,
+}
+
+impl CommitTypeRules {
+    /// Every type listed at any level.
+    pub fn allowed(&self) -> impl Iterator<Item = &str> {
+        [&self.major, &self.minor, &self.patch, &self.other]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    pub fn is_allowed(&self, commit_type: &str) -> bool {
+        self.allowed().any(|allowed| allowed == commit_type)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BranchClass {
+    Release,
+    /// A release branch confined to one major or major.minor line.
+    Maintenance,
+    Rc,
+    Beta,
+    Alpha,
+    Named,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubjectClass {
+    Major,
+    Minor,
+    Patch,
+    Conventional,
+    Text,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Bump {
+    Major,
+    Minor,
+    Patch,
+}
+
+impl Display for BranchClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BranchClass::Release => write!(f, "release"),
+            BranchClass::Maintenance => write!(f, "maintenance"),
+            BranchClass::Rc => write!(f, "rc"),
+            BranchClass::Beta => write!(f, "beta"),
+            BranchClass::Alpha => write!(f, "alpha"),
+            BranchClass::Named => write!(f, "named"),
+        }
+    }
+}
+
+/// The versions a maintenance branch may release: one major version, or one
+/// minor version of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct MaintenanceLine {
+    pub major: usize,
+    pub minor: Option<usize>,
+}
+
+impl MaintenanceLine {
+    /// Reads the line out of `branch` when it follows `pattern`, in which
+    /// `{major}` and `{minor}` each stand for a number.
+    pub fn parse(pattern: &str, branch: &str) -> Option<Self> {
+        let (mut pattern, mut branch) = (pattern, branch);
+        let (mut major, mut minor) = (None, None);
+        while let Some(start) = pattern.find('{') {
+            branch = branch.strip_prefix(&pattern[..start])?;
+            let end = start + pattern[start..].find('}')?;
+            let digits = branch
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(branch.len());
+            let number = branch[..digits].parse().ok()?;
+            match &pattern[start + 1..end] {
+                "major" => major = Some(number),
+                "minor" => minor = Some(number),
+                _ => return None,
+            }
+            branch = &branch[digits..];
+            pattern = &pattern[end + 1..];
+        }
+        (branch == pattern).then_some(())?;
+        Some(MaintenanceLine {
+            major: major?,
+            minor,
+        })
+    }
+
+    pub fn contains(&self, version: &Version) -> bool {
+        version.major == VersionNumber::CCVer(self.major)
+            && self
+                .minor
+                .is_none_or(|minor| version.minor == VersionNumber::CCVer(minor))
+    }
+}
+
+impl Display for MaintenanceLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.minor {
+            Some(minor) => write!(f, "{}.{}.x", self.major, minor),
+            None => write!(f, "{}.x", self.major),
+        }
+    }
+}
+
+/// Checks that a maintenance branch pattern has a `{major}` and no other
+/// placeholder than `{minor}`.
+fn check_maintenance_pattern(pattern: &str) -> Result<()> {
+    let mut placeholders = vec![];
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| eyre!("maintenance branch `{}` has an unclosed `{{`", pattern))?;
+        placeholders.push(&rest[start + 1..start + end]);
+        rest = &rest[start + end + 1..];
+    }
+    if let Some(unknown) = placeholders
+        .iter()
+        .find(|p| !matches!(**p, "major" | "minor"))
+    {
+        return Err(eyre!(
+            "maintenance branch `{}` has an unknown placeholder `{{{}}}`; expected `{{major}}` or `{{minor}}`",
+            pattern,
+            unknown
+        ));
+    }
+    if !placeholders.contains(&"major") {
+        return Err(eyre!(
+            "maintenance branch `{}` needs a `{{major}}` placeholder",
+            pattern
+        ));
+    }
+    Ok(())
+}
+
+impl Display for SubjectClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubjectClass::Major => write!(f, "major"),
+            SubjectClass::Minor => write!(f, "minor"),
+            SubjectClass::Patch => write!(f, "patch"),
+            SubjectClass::Conventional => write!(f, "conventional"),
+            SubjectClass::Text => write!(f, "text"),
+        }
+    }
+}
+
+impl Display for Bump {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        SubjectClass::from(*self).fmt(f)
+    }
+}
+
+impl From<Bump> for SubjectClass {
+    fn from(bump: Bump) -> Self {
+        match bump {
+            Bump::Major => SubjectClass::Major,
+            Bump::Minor => SubjectClass::Minor,
+            Bump::Patch => SubjectClass::Patch,
+        }
+    }
+}
+
+impl std::str::FromStr for Bump {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "major" => Ok(Bump::Major),
+            "minor" => Ok(Bump::Minor),
+            "patch" => Ok(Bump::Patch),
+            other => Err(eyre!(
+                "unknown bump `{}`; expected major, minor or patch",
+                other
+            )),
+        }
+    }
+}
+
+impl SubjectClass {
+    pub fn is_semver_advancing(&self) -> bool {
+        matches!(
+            self,
+            SubjectClass::Major | SubjectClass::Minor | SubjectClass::Patch
+        )
+    }
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+impl Default for BranchRules {
+    fn default() -> Self {
+        BranchRules {
+            release: strings(&["main", "master", "release"]),
+            maintenance: strings(&[
+                "release/{major}.x",
+                "release/{major}.{minor}.x",
+                "support/{major}.x",
+                "support/{major}.{minor}",
+            ]),
+            rc: strings(&["staging", "rc"]),
+            beta: strings(&["development", "develop", "beta"]),
+            alpha: strings(&["next", "alpha"]),
+        }
+    }
+}
+
+impl Default for CommitTypeRules {
+    fn default() -> Self {
+        CommitTypeRules {
+            major: strings(&["breaking", "major"]),
+            minor: strings(&["feat", "feature", "minor"]),
+            patch: strings(&["fix", "bug", "patch"]),
+            other: strings(&[
+                "build", "chore", "ci", "docs", "perf", "refactor", "revert", "style", "test",
+            ]),
+        }
+    }
+}
+
+impl CCVerConfig {
+    /// Loads the first configuration found at the repository root, falling
+    /// back to the defaults when there is none.
+    #[instrument]
+    pub fn from_path(repo_path: &Path) -> Result<Self> {
+        for name in CONFIG_FILE_NAMES {
+            let file = repo_path.join(name);
+            if !file.is_file() {
+                continue;
+            }
+            if let Some(config) = Self::from_file(&file)? {
+                info!(file = ?file, "Loaded ccver config");
+                return Ok(config);
+            }
+        }
+        debug!("No ccver config found, using defaults");
+        Ok(Self::default())
+    }
+
+    /// Loads an explicitly given config file, or searches the repository root.
+    pub fn load(repo_path: &Path, explicit: Option<&Path>) -> Result<Self> {
+        match explicit {
+            Some(file) => Ok(Self::from_file(file)?.unwrap_or_default()),
+            None => Self::from_path(repo_path),
+        }
+    }
+
+    /// Loads configuration from a single file. Returns `None` for a
+    /// `Cargo.toml` without a `[package.metadata.ccver]` table.
+    #[instrument]
+    pub fn from_file(file: &Path) -> Result<Option<Self>> {
+        let raw = std::fs::read_to_string(file)
+            .wrap_err_with(|| format!("could not read config file {}", file.display()))?;
+        let is_manifest = file.file_name().is_some_and(|name| name == "Cargo.toml");
+        Self::from_toml_str(&raw, is_manifest)
+            .wrap_err_with(|| format!("invalid ccver config in {}", file.display()))
+    }
+
+    pub fn from_toml_str(raw: &str, is_manifest: bool) -> Result<Option<Self>> {
+        let document = raw.parse::<DocumentMut>()?;
+        let root = if is_manifest {
+            match document
+                .get("package")
+                .and_then(|p| p.get("metadata"))
+                .and_then(|m| m.get("ccver"))
+                .and_then(Item::as_table_like)
+            {
+                Some(table) => table,
+                None => return Ok(None),
+            }
+        } else {
+            document.as_table() as &dyn TableLike
+        };
+        Self::from_table(root).map(Some)
+    }
+
+    fn from_table(root: &dyn TableLike) -> Result<Self> {
+        let mut config = Self::default();
+        known_keys(
+            root,
+            None,
+            &["branches", "commit_types", "tags", "versioning", "packages"],
+        )?;
+
+        if let Some(branches) = sub_table(root, "branches")? {
+            known_keys(
+                branches,
+                Some("branches"),
+                &["release", "maintenance", "rc", "beta", "alpha"],
+            )?;
+            let rules = &mut config.branches;
+            for (key, target) in [
+                ("release", &mut rules.release),
+                ("maintenance", &mut rules.maintenance),
+                ("rc", &mut rules.rc),
+                ("beta", &mut rules.beta),
+                ("alpha", &mut rules.alpha),
+            ] {
+                if let Some(values) = string_array(branches, key)? {
+                    *target = values;
+                }
+            }
+        }
+
+        for pattern in &config.branches.maintenance {
+            check_maintenance_pattern(pattern)?;
+        }
+
+        if let Some(commit_types) = sub_table(root, "commit_types")? {
+            known_keys(
+                commit_types,
+                Some("commit_types"),
+                &["major", "minor", "patch", "other"],
+            )?;
+            let rules = &mut config.commit_types;
+            for (key, target) in [
+                ("major", &mut rules.major),
+                ("minor", &mut rules.minor),
+                ("patch", &mut rules.patch),
+                ("other", &mut rules.other),
+            ] {
+                if let Some(values) = string_array(commit_types, key)? {
+                    *target = values;
+                }
+            }
+        }
+
+        if let Some(tags) = sub_table(root, "tags")? {
+            known_keys(
+                tags,
+                Some("tags"),
+                &["pattern", "annotate", "message_template"],
+            )?;
+            if let Some(pattern) = string(tags, "pattern")? {
+                config.tag_pattern = pattern.parse().wrap_err("invalid `tags.pattern`")?;
+            }
+            if let Some(annotate) = boolean(tags, "annotate")? {
+                config.annotate_tags = annotate;
+            }
+            config.tag_message_template = string(tags, "message_template")?;
+        }
+
+        if let Some(versioning) = sub_table(root, "versioning")? {
+            known_keys(versioning, Some("versioning"), &["initial_development"])?;
+            if let Some(initial_development) = boolean(versioning, "initial_development")? {
+                config.initial_development = initial_development;
+            }
+        }
+
+        if let Some(packages) = sub_table(root, "packages")? {
+            for (name, item) in packages.iter() {
+                let package = item
+                    .as_table_like()
+                    .ok_or_else(|| eyre!("`packages.{}` must be a table", name))?;
+                known_keys(
+                    package,
+                    Some(&format!("packages.{}", name)),
+                    &["path", "tag_prefix"],
+                )?;
+                let path = string(package, "path")?
+                    .ok_or_else(|| eyre!("`packages.{}` needs a `path`", name))?;
+                let tag_prefix = string(package, "tag_prefix")?;
+                config.packages.push(Package::new(
+                    name,
+                    &path,
+                    tag_prefix.as_deref(),
+                    &config.tag_pattern,
+                ));
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Looks up a package declared under `[packages]` by name.
+    pub fn package(&self, name: &str) -> Result<&Package> {
+        self.packages
+            .iter()
+            .find(|package| package.name == name)
+            .ok_or_else(|| {
+                eyre!(
+                    "unknown package `{}`; configured packages: {}",
+                    name,
+                    match self.packages.is_empty() {
+                        true => "none".to_string(),
+                        false => self
+                            .packages
+                            .iter()
+                            .map(|package| package.name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    }
+                )
+            })
+    }
+
+    pub fn branch_class(&self, branch: &str) -> BranchClass {
+        let matches = |globs: &[String]| globs.iter().any(|g| glob_match(g, branch));
+        if self.maintenance_line(branch).is_some() {
+            BranchClass::Maintenance
+        } else if matches(&self.branches.release) {
+            BranchClass::Release
+        } else if matches(&self.branches.rc) {
+            BranchClass::Rc
+        } else if matches(&self.branches.beta) {
+            BranchClass::Beta
+        } else if matches(&self.branches.alpha) {
+            BranchClass::Alpha
+        } else {
+            BranchClass::Named
+        }
+    }
+
+    /// The line `branch` maintains, when it matches a maintenance pattern.
+    pub fn maintenance_line(&self, branch: &str) -> Option<MaintenanceLine> {
+        self.branches
+            .maintenance
+            .iter()
+            .find_map(|pattern| MaintenanceLine::parse(pattern, branch))
+    }
+
+    pub fn subject_class(&self, subject: &Subject) -> SubjectClass {
+        match subject {
+            Subject::Conventional(ConventionalSubject { breaking: true, .. }) => {
+                SubjectClass::Major
+            }
+            Subject::Conventional(ConventionalSubject { commit_type, .. }) => {
+                self.commit_type_class(commit_type)
+            }
+            Subject::Text(_) => SubjectClass::Text,
+        }
+    }
+
+    /// Like [`CCVerConfig::subject_class`], but a conventional commit with a
+    /// `BREAKING CHANGE:` footer is also a major bump.
+    pub fn commit_class(&self, log_entry: &LogEntry) -> SubjectClass {
+        match &log_entry.subject {
+            Subject::Conventional(_) if log_entry.is_breaking() => SubjectClass::Major,
+            subject => self.subject_class(subject),
+        }
+    }
+
+    pub fn commit_type_class(&self, commit_type: &str) -> SubjectClass {
+        let contains = |types: &[String]| types.iter().any(|t| t == commit_type);
+        if contains(&self.commit_types.major) {
+            SubjectClass::Major
+        } else if contains(&self.commit_types.minor) {
+            SubjectClass::Minor
+        } else if contains(&self.commit_types.patch) {
+            SubjectClass::Patch
+        } else {
+            SubjectClass::Conventional
+        }
+    }
+}
+
+/// Fails on a key of `table` that is not one of `keys`, so that a misspelt
+/// setting is not silently left at its default.
+fn known_keys(table: &dyn TableLike, parent: Option<&str>, keys: &[&str]) -> Result<()> {
+    match table.iter().find(|(key, _)| !keys.contains(key)) {
+        None => Ok(()),
+        Some((key, _)) => Err(eyre!(
+            "unknown key `{}`; expected one of {}",
+            match parent {
+                Some(parent) => format!("{}.{}", parent, key),
+                None => key.to_string(),
+            },
+            keys.iter()
+                .map(|key| format!("`{}`", key))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+fn sub_table<'t>(root: &'t dyn TableLike, key: &str) -> Result<Option<&'t dyn TableLike>> {
+    match root.get(key) {
+        None => Ok(None),
+        Some(item) => item
+            .as_table_like()
+            .map(Some)
+            .ok_or_else(|| eyre!("`{}` must be a table", key)),
+    }
+}
+
+fn string(table: &dyn TableLike, key: &str) -> Result<Option<String>> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(item) => item
+            .as_str()
+            .map(|value| Some(value.to_string()))
+            .ok_or_else(|| eyre!("`{}` must be a string", key)),
+    }
+}
+
+fn boolean(table: &dyn TableLike, key: &str) -> Result<Option<bool>> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(item) => item
+            .as_bool()
+            .map(Some)
+            .ok_or_else(|| eyre!("`{}` must be a boolean", key)),
+    }
+}
+
+fn string_array(table: &dyn TableLike, key: &str) -> Result<Option<Vec<String>>> {
+    let Some(item) = table.get(key) else {
+        return Ok(None);
+    };
+    let array = item
+        .as_array()
+        .ok_or_else(|| eyre!("`{}` must be an array of strings", key))?;
+    array
+        .iter()
+        .map(|v| {
+            v.as_str()
+                .map(str::to_string)
+                .ok_or_else(|| eyre!("`{}` must only contain strings", key))
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(Some)
+}
+
+/// Matches `text` against a glob where `*` matches any run of characters
+/// (including `/`) and `?` matches a single character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod config_tests {
+    use indoc::indoc;
+
+    use super::*;
+    use crate::parser;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("main", "main"));
+        assert!(!glob_match("main", "maint"));
+        assert!(glob_match("hotfix/*", "hotfix/login"));
+        assert!(glob_match("hotfix/*", "hotfix/"));
+        assert!(!glob_match("hotfix/*", "feature/hotfix"));
+        assert!(glob_match("release-?.x", "release-1.x"));
+        assert!(glob_match("*/next", "team/a/next"));
+    }
+
+    #[test]
+    fn test_breaking_change_footer_is_major() {
+        use crate::logs::{PEEK_COMMIT_HASH, PeekLogEntry as _};
+
+        let config = CCVerConfig::default();
+        let footer = "feat: new flag\n\nBREAKING CHANGE: old flag removed"
+            .into_peek_log_entry(PEEK_COMMIT_HASH, "main")
+            .unwrap();
+        assert_eq!(config.commit_class(&footer), SubjectClass::Major);
+        assert_eq!(
+            footer.breaking_notes().collect::<Vec<_>>(),
+            ["old flag removed"]
+        );
+
+        let plain = "feat: new flag"
+            .into_peek_log_entry(PEEK_COMMIT_HASH, "main")
+            .unwrap();
+        assert_eq!(config.commit_class(&plain), SubjectClass::Minor);
+    }
+
+    #[test]
+    fn test_config_overrides_defaults() -> Result<()> {
+        let config = CCVerConfig::from_toml_str(
+            indoc! {r#"
+                [branches]
+                release = ["trunk", "hotfix/*"]
+
+                [commit_types]
+                patch = ["fix", "perf"]
+            "#},
+            false,
+        )?
+        .unwrap();
+
+        assert_eq!(config.branch_class("trunk"), BranchClass::Release);
+        assert_eq!(config.branch_class("hotfix/crash"), BranchClass::Release);
+        assert_eq!(config.branch_class("main"), BranchClass::Named);
+        assert_eq!(config.branch_class("develop"), BranchClass::Beta);
+        assert_eq!(config.commit_type_class("perf"), SubjectClass::Patch);
+        assert_eq!(config.commit_type_class("bug"), SubjectClass::Conventional);
+        assert_eq!(config.commit_type_class("feat"), SubjectClass::Minor);
+        Ok(())
+    }
+
+    #[test]
+    fn test_maintenance_lines() -> Result<()> {
+        use crate::logs::{PEEK_COMMIT_HASH, PeekLogEntry as _};
+        use crate::version_format::VersionFormat;
+
+        let config = CCVerConfig::default();
+        assert_eq!(config.branch_class("release/1.x"), BranchClass::Maintenance);
+        assert_eq!(config.branch_class("release"), BranchClass::Release);
+        assert_eq!(config.branch_class("release/next"), BranchClass::Named);
+        assert_eq!(
+            config.maintenance_line("support/2.3"),
+            Some(MaintenanceLine {
+                major: 2,
+                minor: Some(3)
+            })
+        );
+        assert_eq!(config.maintenance_line("support/2.3-old"), None);
+
+        let format = VersionFormat::default();
+        let entry = |message: &'static str| {
+            message
+                .into_peek_log_entry(PEEK_COMMIT_HASH, "support/2.3")
+                .unwrap()
+        };
+        let base = parser::parse_version("v2.3.4", format.clone())?;
+        let fix = entry("fix: crash");
+        let patch = base.next_version(&fix, &format, &config);
+        assert_eq!(patch.to_string(), "v2.3.5");
+        assert!(base.check_line(&patch, &fix, &config).is_ok());
+        let feat = entry("feat: flag");
+        let minor = base.next_version(&feat, &format, &config);
+        assert!(base.check_line(&minor, &feat, &config).is_err());
+
+        assert!(
+            CCVerConfig::from_toml_str("[branches]\nmaintenance = [\"lts/{minor}\"]\n", false)
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest_metadata() -> Result<()> {
+        let manifest = indoc! {r#"
+            [package]
+            name = "example"
+
+            [package.metadata.ccver.commit_types]
+            major = ["breaking", "epoch"]
+        "#};
+        let config = CCVerConfig::from_toml_str(manifest, true)?.unwrap();
+        assert_eq!(config.commit_type_class("epoch"), SubjectClass::Major);
+
+        assert!(CCVerConfig::from_toml_str("[package]\nname = \"x\"\n", true)?.is_none());
+        assert!(CCVerConfig::from_toml_str("[branches]\nrc = \"staging\"\n", false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_packages() -> Result<()> {
+        let config = CCVerConfig::from_toml_str(
+            indoc! {r#"
+                [packages.api]
+                path = "services/api/"
+
+                [packages.web]
+                path = "web"
+                tag_prefix = "web@"
+            "#},
+            false,
+        )?
+        .unwrap();
+
+        assert_eq!(config.packages.len(), 2);
+        let api = config.package("api")?;
+        assert_eq!(api.path, "services/api");
+        assert_eq!(api.tag_prefix, "api-");
+        assert!(api.contains("services/api/src/main.rs"));
+        assert!(!api.contains("services/api-gateway/main.rs"));
+        assert_eq!(config.package("web")?.tag_prefix, "web@");
+        assert!(config.package("docs").is_err());
+        assert!(CCVerConfig::from_toml_str("[packages.api]\n", false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tag_pattern() -> Result<()> {
+        let config = CCVerConfig::from_toml_str(
+            indoc! {r#"
+                [tags]
+                pattern = "release-{version}"
+
+                [packages.api]
+                path = "api"
+            "#},
+            false,
+        )?
+        .unwrap();
+
+        assert_eq!(config.tag_pattern.prefix, "release-");
+        assert!(!config.annotate_tags);
+        assert_eq!(config.package("api")?.tag_pattern.prefix, "api-release-");
+        assert!(CCVerConfig::from_toml_str("[tags]\npattern = \"release\"\n", false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        for raw in [
+            "[branch]\nrelease = [\"main\"]\n",
+            "[branches]\nreleases = [\"main\"]\n",
+            "[tags]\nannotated = true\n",
+            "[packages.api]\npath = \"api\"\nprefix = \"api@\"\n",
+        ] {
+            assert!(CCVerConfig::from_toml_str(raw, false).is_err(), "{}", raw);
+        }
+        let manifest = "[package.metadata.ccver.versioning]\ninitial = true\n";
+        assert!(CCVerConfig::from_toml_str(manifest, true).is_err());
+    }
+}
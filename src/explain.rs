@@ -0,0 +1,296 @@
+use std::fmt::Display;
+
+use eyre::*;
+use petgraph::graph::NodeIndex;
+use serde::Serialize;
+
+use crate::{
+    config::{BranchClass, Bump, SubjectClass},
+    graph::{
+        GraphOps,
+        assign_versions::AsLogEntry,
+        commit::CommitExt,
+        derivation::{BaseSource, DerivationExt},
+        version::{ExistingVersionExt, TaggedVersionExt as _},
+    },
+    version::Version,
+};
+
+/// The chain of steps that produced a commit's version, newest first.
+#[derive(Debug, Serialize)]
+pub struct Explanation {
+    pub commit: String,
+    pub version: Version,
+    pub steps: Vec<ExplainStep>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExplainStep {
+    pub commit: String,
+    pub branch: String,
+    pub subject: String,
+    /// Parents and the version each had when this commit was visited.
+    pub parents: Vec<ExplainParent>,
+    pub base: Version,
+    pub base_source: ExplainBaseSource,
+    /// The `next_version` arm that fired.
+    pub rule: ExplainRule,
+    pub forced: Option<Bump>,
+    /// The version a `Release-As:` footer pinned the commit to.
+    pub release_as: Option<Version>,
+    pub computed: Version,
+    /// Why the computed version leaves the line of a maintenance branch.
+    pub line_violation: Option<String>,
+    pub tag: Option<Version>,
+    /// Whether the existing tag replaced the computed version.
+    pub tag_overrides: bool,
+    pub version: Version,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExplainParent {
+    pub commit: String,
+    pub version: Option<Version>,
+    /// Whether this parent won the `max()` and became the base.
+    pub selected: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExplainBaseSource {
+    Parent {
+        commit: String,
+    },
+    PreviousInWalk {
+        commit: Option<String>,
+    },
+    /// The commit's own tag, as the history before it was not loaded.
+    Tag,
+    /// A version known for the commit, such as a cached one, as the history
+    /// before it was not loaded.
+    Seed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExplainRule {
+    pub subject_class: SubjectClass,
+    pub branch_class: BranchClass,
+    pub merge: bool,
+}
+
+impl Explanation {
+    /// Follows the base of each derivation back from `from` until a commit whose
+    /// version comes from a tag, the start of the walk, or `depth` steps.
+    pub fn new<N, E, Ty, Ix, T>(
+        graph: &T,
+        from: NodeIndex<Ix>,
+        depth: Option<usize>,
+    ) -> Result<Self>
+    where
+        T: GraphOps<N, E, Ty, Ix> + CommitExt<N, E, Ty, Ix>,
+        N: AsLogEntry + ExistingVersionExt + DerivationExt,
+        Ix: Copy,
+    {
+        let mut steps = vec![];
+        let mut next = Some(from);
+
+        while let Some(idx) = next {
+            if depth.is_some_and(|depth| steps.len() >= depth) {
+                break;
+            }
+
+            let node = graph
+                .node_weight(idx)
+                .ok_or_eyre("Commit not found in graph")?;
+            let log_entry = node.as_log_entry();
+            let derivation = node.derivation().ok_or_else(|| {
+                eyre!(
+                    "Commit {} was not assigned a version; it is not an ancestor of HEAD",
+                    log_entry.commit_hash
+                )
+            })?;
+            let version = node
+                .as_existing_version()
+                .ok_or_eyre("Commit has a derivation but no version")?;
+            let tag = log_entry.as_tagged_version().cloned();
+            let from_tag = tag.as_ref().is_some_and(|tag| *tag == version);
+
+            let selected = match &derivation.base_source {
+                BaseSource::Parent(hash) => Some(hash.as_str()),
+                BaseSource::PreviousInWalk(_) | BaseSource::Tag | BaseSource::Seed => None,
+            };
+            steps.push(ExplainStep {
+                commit: log_entry.commit_hash.to_string(),
+                branch: log_entry.branch.to_string(),
+                subject: log_entry.subject.to_string(),
+                parents: derivation
+                    .parents
+                    .iter()
+                    .map(|(commit, version)| ExplainParent {
+                        commit: commit.clone(),
+                        version: version.clone(),
+                        selected: selected == Some(commit.as_str()),
+                    })
+                    .collect(),
+                base: derivation.base.clone(),
+                base_source: match &derivation.base_source {
+                    BaseSource::Parent(commit) => ExplainBaseSource::Parent {
+                        commit: commit.clone(),
+                    },
+                    BaseSource::PreviousInWalk(commit) => ExplainBaseSource::PreviousInWalk {
+                        commit: commit.clone(),
+                    },
+                    BaseSource::Tag => ExplainBaseSource::Tag,
+                    BaseSource::Seed => ExplainBaseSource::Seed,
+                },
+                rule: ExplainRule {
+                    subject_class: derivation.subject_class,
+                    branch_class: derivation.branch_class,
+                    merge: derivation.merge,
+                },
+                forced: derivation.forced,
+                release_as: derivation.release_as.clone(),
+                computed: derivation.computed.clone(),
+                line_violation: derivation.line_violation.clone(),
+                tag,
+                tag_overrides: from_tag && derivation.computed != version,
+                version,
+            });
+
+            if from_tag {
+                break;
+            }
+
+            next = match &derivation.base_source {
+                BaseSource::Parent(hash) => graph.commit_idx_by_hash(hash),
+                BaseSource::PreviousInWalk(hash) => hash
+                    .as_ref()
+                    .and_then(|hash| graph.commit_idx_by_hash(hash)),
+                BaseSource::Tag | BaseSource::Seed => None,
+            };
+        }
+
+        let first = steps.first().ok_or_eyre("Nothing to explain")?;
+        Ok(Explanation {
+            commit: first.commit.clone(),
+            version: first.version.clone(),
+            steps,
+        })
+    }
+}
+
+fn short(hash: &str) -> &str {
+    &hash[..hash.len().min(7)]
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} is {}", short(&self.commit), self.version)?;
+        for step in &self.steps {
+            writeln!(f)?;
+            writeln!(
+                f,
+                "{} ({}) {}",
+                short(&step.commit),
+                step.branch,
+                step.subject
+            )?;
+            if step.parents.is_empty() {
+                writeln!(f, "  parents:  none")?;
+            }
+            for (i, parent) in step.parents.iter().enumerate() {
+                writeln!(
+                    f,
+                    "  {}{} {}{}",
+                    if i == 0 { "parents:  " } else { "          " },
+                    short(&parent.commit),
+                    parent
+                        .version
+                        .as_ref()
+                        .map_or("(no version yet)".to_string(), ToString::to_string),
+                    if parent.selected { " <- max" } else { "" }
+                )?;
+            }
+            match &step.base_source {
+                ExplainBaseSource::Parent { commit } => {
+                    writeln!(f, "  base:     {} from parent {}", step.base, short(commit))?
+                }
+                ExplainBaseSource::PreviousInWalk {
+                    commit: Some(commit),
+                } => writeln!(
+                    f,
+                    "  base:     {} from previously visited {} (no parent had a version)",
+                    step.base,
+                    short(commit)
+                )?,
+                ExplainBaseSource::PreviousInWalk { commit: None } => {
+                    writeln!(f, "  base:     {} (default starting version)", step.base)?
+                }
+                ExplainBaseSource::Tag => writeln!(
+                    f,
+                    "  base:     {} from its tag (earlier history not loaded)",
+                    step.base
+                )?,
+                ExplainBaseSource::Seed => writeln!(
+                    f,
+                    "  base:     {} known for it (earlier history not loaded)",
+                    step.base
+                )?,
+            }
+            writeln!(
+                f,
+                "  rule:     {} × {} × merge={}",
+                step.rule.subject_class, step.rule.branch_class, step.rule.merge
+            )?;
+            if let Some(bump) = step.forced {
+                writeln!(f, "  forced:   {} bump", bump)?;
+            }
+            if let Some(pinned) = &step.release_as {
+                writeln!(f, "  pinned:   {} (Release-As)", pinned)?;
+            }
+            writeln!(f, "  computed: {}", step.computed)?;
+            if let Some(violation) = &step.line_violation {
+                writeln!(f, "  off line: {}", violation)?;
+            }
+            match (&step.tag, step.tag_overrides) {
+                (Some(tag), true) => writeln!(f, "  tag:      {} (overrides computed)", tag)?,
+                (Some(tag), false) => writeln!(f, "  tag:      {}", tag)?,
+                (None, _) => {}
+            }
+            writeln!(f, "  version:  {}", step.version)?;
+        }
+        std::fmt::Result::Ok(())
+    }
+}
+
+#[cfg(test)]
+mod explain_tests {
+    use crate::{
+        config::CCVerConfig,
+        graph::{MemoizedCommitGraph, head::HasHead},
+        logs::Logs,
+        version_format::VersionFormat,
+    };
+    use eyre::*;
+
+    #[test]
+    fn explains_head_back_to_an_anchor() -> Result<()> {
+        let raw = crate::git::formatted_logs(&std::env::current_dir()?)?;
+        let logs = Logs::from_log_str(&raw)?;
+        let version_format = VersionFormat::default();
+        let graph = MemoizedCommitGraph::new(logs, &version_format, &CCVerConfig::default())?;
+        let head_idx = graph.head_idx().unwrap();
+
+        let explanation = super::Explanation::new(&graph, head_idx, None)?;
+        {
+            let head = graph.node_weight(head_idx).unwrap().lock().unwrap();
+            assert_eq!(explanation.commit, head.log_entry.commit_hash);
+            assert_eq!(Some(&explanation.version), head.version.as_ref());
+        }
+        assert!(!explanation.steps.is_empty());
+
+        let limited = super::Explanation::new(&graph, head_idx, Some(1))?;
+        assert_eq!(limited.steps.len(), 1);
+        Ok(())
+    }
+}
@@ -1,6 +1,17 @@
-use crate::{logs::GIT_FORMAT_ARGS, version::Version};
+use crate::{
+    logs::{GIT_CHANGED_PATHS_ARGS, GIT_FORMAT_ARGS},
+    tag_pattern::TagPattern,
+    version::Version,
+};
 use eyre::*;
-use std::{path::Path, process::Command};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write as _,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    time::{SystemTime, UNIX_EPOCH},
+};
 use tracing::{debug, info, instrument, warn};
 
 #[instrument]
@@ -22,33 +33,479 @@ pub fn is_dirty(path: &Path) -> Result<bool> {
     Ok(is_dirty)
 }
 
+/// What goes into an annotated tag. Without `sign`, git still signs it when
+/// `tag.gpgSign` is set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagAnnotation {
+    pub message: String,
+    pub sign: bool,
+}
+
+/// Tags `hash` with `version`, naming the tag with `tag_pattern`.
+#[instrument(skip(annotation))]
+pub fn tag_commit_with_version(
+    hash: &str,
+    version: &Version,
+    tag_pattern: &TagPattern,
+    annotation: Option<&TagAnnotation>,
+    path: &Path,
+) -> Result<()> {
+    tag_commit(hash, &tag_pattern.render(version), annotation, path)
+}
+
+/// Creates the tag `tag` on `hash`: annotated with `annotation`'s message
+/// (kept verbatim) when given, lightweight otherwise.
+pub fn tag_commit(
+    hash: &str,
+    tag: &str,
+    annotation: Option<&TagAnnotation>,
+    path: &Path,
+) -> Result<()> {
+    create_tags(
+        &[NewTag {
+            hash,
+            tag,
+            annotation,
+        }],
+        path,
+    )
+}
+
+/// One tag for [`create_tags`].
+#[derive(Debug, Clone, Copy)]
+pub struct NewTag<'a> {
+    pub hash: &'a str,
+    pub tag: &'a str,
+    pub annotation: Option<&'a TagAnnotation>,
+}
+
+/// Errors when a name is given twice in `tags`: the tag plan decides which
+/// commit takes a repeated name, so a batch that still repeats one is wrong.
+pub(crate) fn check_unique_tags(tags: &[NewTag]) -> Result<()> {
+    let mut seen = HashSet::new();
+    match tags.iter().find(|tag| !seen.insert(tag.tag)) {
+        Some(tag) => Err(eyre!(
+            "Failed to create tags, none were created: tag {} is given twice",
+            tag.tag
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Creates all of `tags` in a single `git update-ref` transaction: either
+/// every tag is created or none is. Each tag is only created if it does not
+/// exist yet, so a tag someone else created in the meantime fails the batch
+/// instead of being moved, and a name given twice fails it too.
+#[instrument(skip_all, fields(count = tags.len()))]
+pub fn create_tags(tags: &[NewTag], path: &Path) -> Result<()> {
+    check_unique_tags(tags)?;
+    if tags.is_empty() {
+        return Ok(());
+    }
+    info!("Creating {} tag(s)", tags.len());
+    let objects = tag_objects(tags, path)?;
+    let mut transaction = String::new();
+    for tag in tags {
+        let object = objects.get(tag.tag).map_or(tag.hash, String::as_str);
+        transaction.push_str(&format!("create refs/tags/{} {}\n", tag.tag, object));
+    }
+
+    let output = run_with_stdin(path, &["update-ref", "--stdin"], transaction.as_bytes())?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "Failed to create tags, none were created: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    debug!("Created {} tag(s)", tags.len());
+    Ok(())
+}
+
+/// Writes the annotated tag objects of `tags` (without refs pointing at them
+/// yet) and returns their hashes by tag name. `git tag` writes them, signing
+/// them the way `git tag -s` does, in a scratch repository that shares the
+/// objects and config of `path`; its refs are thrown away so the real ones
+/// can be created in one transaction.
+pub(crate) fn tag_objects(tags: &[NewTag], path: &Path) -> Result<HashMap<String, String>> {
+    if tags.iter().all(|tag| tag.annotation.is_none()) {
+        return Ok(HashMap::new());
+    }
+    let objects = git_path(path, "objects")?;
+    let config = git_path(path, "config")?;
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or_default();
+    let scratch = std::env::temp_dir().join(format!("ccver-tag-{}-{}", std::process::id(), nanos));
+    let scratch_git = |args: &[&str], input: &[u8]| -> Result<String> {
+        let mut child = Command::new("git")
+            .arg("--git-dir")
+            .arg(&scratch)
+            .args(args)
+            .env("GIT_OBJECT_DIRECTORY", &objects)
+            .current_dir(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .ok_or_eyre("could not open stdin of git")?
+            .write_all(input)?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(eyre!("{}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+    };
+
+    let created = (|| {
+        let output = Command::new("git")
+            .args(["init", "--bare", "--quiet", "--template="])
+            .arg(&scratch)
+            .output()?;
+        if !output.status.success() {
+            return Err(eyre!(
+                "Could not create a scratch repository: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        scratch_git(&["config", "include.path", &config.to_string_lossy()], &[])?;
+        let mut created = HashMap::new();
+        for tag in tags {
+            let Some(annotation) = tag.annotation else {
+                continue;
+            };
+            // without `-s`, git still signs when `tag.gpgSign` is set
+            let kind = if annotation.sign { "-s" } else { "-a" };
+            let args = [
+                "tag",
+                kind,
+                "--cleanup=verbatim",
+                "-F",
+                "-",
+                tag.tag,
+                tag.hash,
+            ];
+            scratch_git(&args, annotation.message.as_bytes())
+                .and_then(|_| scratch_git(&["rev-parse", &format!("refs/tags/{}", tag.tag)], &[]))
+                .map(|object| created.insert(tag.tag.to_string(), object))
+                .map_err(|e| eyre!("Failed to create tag {}: {}", tag.tag, e))?;
+        }
+        Ok(created)
+    })();
+    let _ = std::fs::remove_dir_all(&scratch);
+    created
+}
+
+/// The contents of an unsigned annotated tag object for `tag`.
+pub(crate) fn tag_payload(tag: &NewTag, annotation: &TagAnnotation, tagger: &str) -> String {
+    let mut object = format!(
+        "object {}\ntype commit\ntag {}\ntagger {}\n\n{}",
+        tag.hash, tag.tag, tagger, annotation.message
+    );
+    if !object.ends_with('\n') {
+        object.push('\n');
+    }
+    object
+}
+
+/// What happened to one tag pushed with [`push_tags`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PushOutcome {
+    Pushed,
+    /// The remote already had the tag on the same commit, possibly as a
+    /// different tag object.
+    UpToDate,
+    /// The remote refused the tag. `commit` is set when it already has a tag
+    /// of that name on a different commit.
+    Rejected {
+        reason: String,
+        commit: Option<String>,
+    },
+}
+
+/// Pushes `tags` to `remote`, returning the outcome for each tag. Tags the
+/// remote refuses are reported as [`PushOutcome::Rejected`]; failing to reach
+/// the remote at all is an error.
+#[instrument(skip(tags), fields(count = tags.len()))]
+pub fn push_tags(remote: &str, tags: &[&str], path: &Path) -> Result<HashMap<String, PushOutcome>> {
+    if tags.is_empty() {
+        return Ok(HashMap::new());
+    }
+    info!("Pushing {} tag(s) to {}", tags.len(), remote);
+    let refspecs = tags
+        .iter()
+        .map(|tag| format!("refs/tags/{tag}:refs/tags/{tag}"))
+        .collect::<Vec<_>>();
+    let output = Command::new("git")
+        .args(["push", "--porcelain", remote])
+        .args(&refspecs)
+        .current_dir(path)
+        .output()?;
+
+    // `<flag>\t<from>:<to>\t<summary> (<reason>)`, one line per ref
+    let mut outcomes = HashMap::new();
+    for line in String::from_utf8(output.stdout)?.lines() {
+        let mut fields = line.split('\t');
+        let (Some(flag), Some(refs), Some(summary)) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let Some(tag) = refs
+            .split_once(':')
+            .and_then(|(_, to)| to.strip_prefix("refs/tags/"))
+        else {
+            continue;
+        };
+        let outcome = match flag {
+            "=" => PushOutcome::UpToDate,
+            "!" => {
+                let reason = summary
+                    .split_once(" (")
+                    .map(|(_, reason)| reason.trim_end_matches(')'))
+                    .unwrap_or(summary)
+                    .to_string();
+                let commit = match reason.as_str() {
+                    "already exists" => remote_tag_commit(remote, tag, path)?,
+                    _ => None,
+                };
+                match commit {
+                    Some(commit) if rev_parse(path, &format!("refs/tags/{tag}"))? == commit => {
+                        warn!(%tag, "{} has the tag on the same commit with another annotation", remote);
+                        PushOutcome::UpToDate
+                    }
+                    commit => PushOutcome::Rejected { reason, commit },
+                }
+            }
+            _ => PushOutcome::Pushed,
+        };
+        debug!(%tag, ?outcome, "Pushed tag");
+        outcomes.insert(tag.to_string(), outcome);
+    }
+
+    if !output.status.success() && tags.iter().any(|tag| !outcomes.contains_key(*tag)) {
+        return Err(eyre!(
+            "Could not push tags to {}: {}",
+            remote,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(outcomes)
+}
+
+/// The commit `remote`'s tag `tag` points at, if it has one.
+fn remote_tag_commit(remote: &str, tag: &str, path: &Path) -> Result<Option<String>> {
+    let output = Command::new("git")
+        .args(["ls-remote", "--tags", remote])
+        .arg(format!("refs/tags/{tag}"))
+        .arg(format!("refs/tags/{tag}^{{}}"))
+        .current_dir(path)
+        .output()?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "Could not read tag {} from {}: {}",
+            tag,
+            remote,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    // annotated tags are listed twice, the second time peeled to the commit
+    let listing = String::from_utf8(output.stdout)?;
+    let refs = listing
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .collect::<Vec<_>>();
+    Ok(refs
+        .iter()
+        .find(|(_, name)| name.ends_with("^{}"))
+        .or(refs.first())
+        .map(|(object, _)| object.to_string()))
+}
+
+/// Runs git with `args`, writing `input` to its stdin.
+fn run_with_stdin(path: &Path, args: &[&str], input: &[u8]) -> Result<Output> {
+    let mut child = Command::new("git")
+        .args(args)
+        .current_dir(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .ok_or_eyre("could not open stdin of git")?
+        .write_all(input)?;
+    Ok(child.wait_with_output()?)
+}
+
+/// The absolute path of `name` in the git directory, as `git rev-parse
+/// --git-path` resolves it (the common directory for a worktree).
+fn git_path(path: &Path, name: &str) -> Result<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--path-format=absolute", "--git-path", name])
+        .current_dir(path)
+        .output()?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "Could not find the git directory: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(PathBuf::from(String::from_utf8(output.stdout)?.trim()))
+}
+
+/// The commit every tag points at, by tag name.
 #[instrument]
-pub fn tag_commit_with_version(hash: &str, version: &Version, path: &Path) -> Result<()> {
-    debug!("Tagging commit with version: {}", version);
-    let tag = format!("{}", version);
+pub fn tag_targets(path: &Path) -> Result<HashMap<String, String>> {
+    debug!("Reading tag targets");
     let output = Command::new("git")
-        .args(["tag", tag.as_str(), hash])
+        .args([
+            "for-each-ref",
+            "--format=%(refname:strip=2) %(objectname) %(*objectname)",
+            "refs/tags",
+        ])
         .current_dir(path)
         .output()?;
 
     if !output.status.success() {
-        return Err(eyre!("Failed to tag commit with version: {}", version));
+        return Err(eyre!(
+            "Could not list tags: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
     }
 
-    if let Some(stderr) = String::from_utf8(output.stderr).ok()
-        && !stderr.is_empty()
-    {
-        warn!("Failed to tag commit with version: {}", stderr);
+    // annotated tags are peeled to the commit they tag
+    let targets = String::from_utf8(output.stdout)?
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            let (name, object) = (fields.next()?, fields.next()?);
+            let commit = fields.next().filter(|peeled| !peeled.is_empty());
+            Some((name.to_string(), commit.unwrap_or(object).to_string()))
+        })
+        .collect::<HashMap<_, _>>();
+    debug!("Read {} tag(s)", targets.len());
+    Ok(targets)
+}
+
+/// The name of every branch.
+#[instrument]
+pub fn branches(path: &Path) -> Result<Vec<String>> {
+    debug!("Listing branches");
+    let output = Command::new("git")
+        .args(["for-each-ref", "--format=%(refname:strip=2)", "refs/heads"])
+        .current_dir(path)
+        .output()?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "Could not list branches: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
     }
+    Ok(String::from_utf8(output.stdout)?
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
 
-    if let Some(stdout) = String::from_utf8(output.stdout).ok()
-        && !stdout.is_empty()
-    {
-        debug!("Tagged commit with version: {}", stdout);
+/// The tags reachable from a branch, by name, with the commit each points at
+/// and that commit's committer time.
+#[instrument]
+pub fn branch_tags(path: &Path) -> Result<HashMap<String, (String, i64)>> {
+    debug!("Reading the tags reachable from branches");
+    let branches = Command::new("git")
+        .args([
+            "for-each-ref",
+            "--format=--merged=%(objectname)",
+            "refs/heads",
+        ])
+        .current_dir(path)
+        .output()?;
+    if !branches.status.success() {
+        return Err(eyre!(
+            "Could not list branches: {}",
+            String::from_utf8_lossy(&branches.stderr).trim()
+        ));
+    }
+    let merged = String::from_utf8(branches.stdout)?;
+    if merged.trim().is_empty() {
+        return Ok(HashMap::new());
     }
 
-    debug!("Tagged commit with version: {}", version);
-    Ok(())
+    let output = Command::new("git")
+        .arg("for-each-ref")
+        .arg("--format=%(refname:strip=2) %(objectname) %(*objectname) %(committerdate:unix) %(*committerdate:unix)")
+        .args(merged.lines())
+        .arg("refs/tags")
+        .current_dir(path)
+        .output()?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "Could not list tags: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    // annotated tags are peeled to the commit they tag
+    let tags = String::from_utf8(output.stdout)?
+        .lines()
+        .filter_map(|line| {
+            let fields = line.split(' ').collect::<Vec<_>>();
+            let [name, object, peeled, time, peeled_time] = fields[..] else {
+                return None;
+            };
+            let (commit, time) = match peeled {
+                "" => (object, time),
+                peeled => (peeled, peeled_time),
+            };
+            Some((name.to_string(), (commit.to_string(), time.parse().ok()?)))
+        })
+        .collect::<HashMap<_, _>>();
+    debug!("Read {} tag(s)", tags.len());
+    Ok(tags)
+}
+
+/// The messages of annotated tags by tag name, without their signatures.
+/// Lightweight tags are left out.
+#[instrument]
+pub fn tag_messages(path: &Path) -> Result<HashMap<String, String>> {
+    debug!("Reading annotated tag messages");
+    let output = Command::new("git")
+        .args([
+            "for-each-ref",
+            "--format=%(refname:strip=2)%00%(objecttype)%00%(contents:signature)%00%(contents)%1e",
+            "refs/tags",
+        ])
+        .current_dir(path)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(eyre!(
+            "Could not list tags: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let messages = String::from_utf8(output.stdout)?
+        .split('\u{1e}')
+        .filter_map(|record| {
+            let mut fields = record.trim_start_matches('\n').splitn(4, '\0');
+            let (name, kind, signature, contents) = (
+                fields.next()?,
+                fields.next()?,
+                fields.next()?,
+                fields.next()?,
+            );
+            let message = contents.strip_suffix(signature).unwrap_or(contents);
+            (kind == "tag").then(|| (name.to_string(), message.trim_end().to_string()))
+        })
+        .collect::<HashMap<_, _>>();
+    debug!("Read {} annotated tag message(s)", messages.len());
+    Ok(messages)
 }
 
 #[instrument]
@@ -133,6 +590,73 @@ pub fn head_hash(path: &Path) -> Result<String> {
     Ok(hash)
 }
 
+#[instrument]
+pub fn rev_parse(path: &Path, rev: &str) -> Result<String> {
+    debug!("Resolving revision: {}", rev);
+    let output = Command::new("git")
+        .args([
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{}^{{commit}}", rev),
+        ])
+        .current_dir(path)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(eyre!("Could not resolve revision `{}` to a commit", rev));
+    }
+
+    let hash = String::from_utf8(output.stdout)?.trim().to_string();
+    debug!(hash = %hash, "Resolved revision");
+    Ok(hash)
+}
+
+/// The git directory every worktree of the repository shares.
+#[instrument]
+pub fn common_dir(path: &Path) -> Result<PathBuf> {
+    debug!("Locating common git directory");
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-common-dir"])
+        .current_dir(path)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(eyre!(
+            "{} is not a git repository: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let common_dir = path.join(String::from_utf8(output.stdout)?.trim());
+    debug!(common_dir = ?common_dir, "Located common git directory");
+    Ok(common_dir)
+}
+
+/// The hooks directory git runs hooks from, honouring `core.hooksPath` and
+/// worktrees.
+#[instrument]
+pub fn hooks_dir(path: &Path) -> Result<PathBuf> {
+    debug!("Locating hooks directory");
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-path", "hooks"])
+        .current_dir(path)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(eyre!(
+            "{} is not a git repository: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let hooks = path.join(String::from_utf8(output.stdout)?.trim());
+    debug!(hooks = ?hooks, "Located hooks directory");
+    Ok(hooks)
+}
+
 #[instrument]
 pub fn tree_hash(path: &Path) -> Result<String> {
     debug!("Getting tree hash");
@@ -163,20 +687,32 @@ pub fn git_installed() -> Result<()> {
     }
 }
 
+/// The history of every branch, for [`Logs::from_log_str`](crate::logs::Logs::from_log_str)
+/// to borrow from.
+#[instrument]
+pub fn formatted_logs(path: &Path) -> Result<String> {
+    formatted_logs_with_args(path, &[])
+}
+
+/// Like [`formatted_logs`], but also lists the files each commit changed.
 #[instrument]
-pub fn formatted_logs(path: &Path) -> Result<&'static mut str> {
+pub fn formatted_logs_with_changed_paths(path: &Path) -> Result<String> {
+    formatted_logs_with_args(path, &GIT_CHANGED_PATHS_ARGS)
+}
+
+fn formatted_logs_with_args(path: &Path, extra_args: &[&str]) -> Result<String> {
     let start = std::time::Instant::now();
     info!("Fetching formatted git logs");
-    debug!("Git format args: {:?}", GIT_FORMAT_ARGS);
+    debug!("Git format args: {:?} {:?}", GIT_FORMAT_ARGS, extra_args);
 
     let logs = String::from_utf8(
         Command::new("git")
             .args(GIT_FORMAT_ARGS)
+            .args(extra_args)
             .current_dir(path)
             .output()?
             .stdout,
-    )?
-    .leak();
+    )?;
 
     let duration = start.elapsed();
     info!(
@@ -192,14 +728,79 @@ pub fn formatted_logs(path: &Path) -> Result<&'static mut str> {
     Ok(logs)
 }
 
+/// Like [`formatted_logs`] (with changed paths when `changed_paths` is set,
+/// and the history of the commit `tip` when it is given), but without the
+/// history `boundaries` are built on: each boundary commit is listed, its
+/// ancestors are not.
+#[instrument(skip(boundaries))]
+pub fn formatted_logs_after(
+    path: &Path,
+    changed_paths: bool,
+    tip: Option<&str>,
+    boundaries: &[String],
+) -> Result<String> {
+    info!(
+        "Fetching formatted git logs after {} commit(s)",
+        boundaries.len()
+    );
+    let extra_args: &[&str] = match changed_paths {
+        true => &GIT_CHANGED_PATHS_ARGS,
+        false => &[],
+    };
+    let args = [&GIT_FORMAT_ARGS[..], extra_args, &["--stdin"]].concat();
+    // `^<commit>^@` leaves out every parent of the commit and what they reach
+    let revisions = tip
+        .map(|tip| format!("{}\n", tip))
+        .into_iter()
+        .chain(boundaries.iter().map(|commit| format!("^{}^@\n", commit)))
+        .collect::<String>();
+    let output = run_with_stdin(path, &args, revisions.as_bytes())?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "Could not list commits: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let logs = String::from_utf8(output.stdout)?;
+    debug!("Retrieved {} characters of log data", logs.len());
+    Ok(logs)
+}
+
+/// Like [`formatted_logs`], but for the commits in `range` (e.g.
+/// `origin/main..HEAD`) instead of every local branch. Returns an empty
+/// string when the range is empty.
+#[instrument]
+pub fn formatted_logs_in_range(path: &Path, range: &str) -> Result<String> {
+    info!("Fetching formatted git logs for {}", range);
+    let output = Command::new("git")
+        .args(GIT_FORMAT_ARGS.iter().filter(|arg| **arg != "--branches"))
+        .arg(range)
+        .current_dir(path)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(eyre!(
+            "Could not list commits in {}: {}",
+            range,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let logs = String::from_utf8(output.stdout)?;
+    debug!("Retrieved {} characters of log data", logs.len());
+    Ok(logs)
+}
+
 #[cfg(test)]
 mod test_commands {
+    use crate::test_repo::{ScratchDir, TestRepo};
     use std::env::current_dir;
 
     #[test]
     fn test_git_installed() {
         // I guess this is an assumption that git is installed on the system that runs the tests but how else would you get the tests
-        assert_eq!(super::git_installed().is_ok(), true);
+        assert!(super::git_installed().is_ok());
     }
 
     #[test]
@@ -233,6 +834,173 @@ mod test_commands {
         Ok(())
     }
 
+    #[test]
+    fn rev_parse_head() -> eyre::Result<()> {
+        let head = super::rev_parse(&current_dir().unwrap(), "HEAD")?;
+        assert_eq!(head.len(), 40);
+        assert!(super::rev_parse(&current_dir().unwrap(), "not-a-real-ref").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn create_tags_is_all_or_nothing() -> eyre::Result<()> {
+        use super::{NewTag, TagAnnotation, create_tags, tag_messages, tag_targets};
+
+        let test_repo = TestRepo::new("tags");
+        let repo = test_repo.path();
+        let first = test_repo.commit("feat: first")?;
+        test_repo.git(&["tag", "v2", &first])?;
+        let second = test_repo.commit("fix: second")?;
+
+        let annotation = TagAnnotation {
+            message: "## v1\n".to_string(),
+            sign: false,
+        };
+        let v1 = NewTag {
+            hash: &second,
+            tag: "v1",
+            annotation: Some(&annotation),
+        };
+        let v2 = NewTag {
+            hash: &second,
+            tag: "v2",
+            annotation: None,
+        };
+        assert!(create_tags(&[v1, v2], repo).is_err());
+        let targets = tag_targets(repo)?;
+        assert!(!targets.contains_key("v1"));
+        assert_eq!(targets["v2"], first);
+
+        let v3 = NewTag { tag: "v3", ..v2 };
+        create_tags(&[v1, v3], repo)?;
+        let targets = tag_targets(repo)?;
+        assert_eq!(targets["v1"], second);
+        assert_eq!(targets["v3"], second);
+        assert_eq!(tag_messages(repo)?["v1"], "## v1");
+        Ok(())
+    }
+
+    #[test]
+    fn create_tags_lets_git_sign() -> eyre::Result<()> {
+        use super::{NewTag, TagAnnotation, create_tags, tag_messages};
+        use std::process::Command;
+
+        let test_repo = TestRepo::new("signed");
+        let (repo, git) = (test_repo.path(), |args: &[&str]| test_repo.git(args));
+        let key = repo.join(".git").join("signing-key");
+        let generated = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-C", "ccver", "-f"])
+            .arg(&key)
+            .status()?;
+        assert!(generated.success());
+        let public = std::fs::read_to_string(key.with_extension("pub"))?;
+        let allowed = repo.join(".git").join("allowed-signers");
+        std::fs::write(&allowed, format!("ccver@example.com {}", public))?;
+        git(&["config", "gpg.format", "ssh"])?;
+        git(&["config", "user.signingKey", key.to_str().unwrap()])?;
+        git(&[
+            "config",
+            "gpg.ssh.allowedSignersFile",
+            allowed.to_str().unwrap(),
+        ])?;
+        let head = test_repo.commit("feat: first")?;
+
+        let annotation = TagAnnotation {
+            message: "## v1\n\nNotes\n".to_string(),
+            sign: true,
+        };
+        create_tags(
+            &[NewTag {
+                hash: &head,
+                tag: "v1",
+                annotation: Some(&annotation),
+            }],
+            repo,
+        )?;
+        git(&["tag", "-v", "v1"])?;
+        assert_eq!(tag_messages(repo)?["v1"], "## v1\n\nNotes");
+        // only the tag ccver asked for exists
+        assert_eq!(git(&["tag", "--list"])?, "v1");
+        Ok(())
+    }
+
+    #[test]
+    fn create_tags_rejects_a_repeated_name() -> eyre::Result<()> {
+        use super::{NewTag, create_tags, tag_targets};
+
+        let repo = TestRepo::new("noop-merge");
+        repo.commit("feat: first")?;
+        repo.git(&["checkout", "--quiet", "-b", "docs"])?;
+        repo.commit("docs: usage")?;
+        repo.git(&["checkout", "--quiet", "main"])?;
+        let feat = repo.commit("feat: second")?;
+        repo.git(&[
+            "merge",
+            "--quiet",
+            "--no-ff",
+            "docs",
+            "-m",
+            "Merge branch 'docs'",
+        ])?;
+        let merge = repo.git(&["rev-parse", "HEAD"])?;
+
+        // the merge changes nothing, so `tag --all` gives it its parent's
+        // version; the tag plan skips it, and a batch repeating it fails
+        let tag = |hash| NewTag {
+            hash,
+            tag: "v0.2.0",
+            annotation: None,
+        };
+        let error = create_tags(&[tag(&feat), tag(&merge)], repo.path()).unwrap_err();
+        assert!(error.to_string().contains("given twice"), "{error}");
+        assert!(tag_targets(repo.path())?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn push_tags_reports_rejections() -> eyre::Result<()> {
+        use super::{PushOutcome, push_tags};
+
+        let remote = ScratchDir::new("push-remote");
+        let test_repo = TestRepo::new("push");
+        let (repo, git) = (test_repo.path(), |args: &[&str]| test_repo.git(args));
+        git(&["init", "--quiet", "--bare", remote.path().to_str().unwrap()])?;
+        git(&["remote", "add", "origin", remote.path().to_str().unwrap()])?;
+        let first = test_repo.commit("feat: first")?;
+        test_repo.commit("fix: second")?;
+        git(&["tag", "v1"])?;
+        git(&["tag", "v2"])?;
+        // another job already released v2, at a different commit
+        let refspec = format!("{first}:refs/tags/v2");
+        git(&["push", "--quiet", "origin", &refspec])?;
+
+        let outcomes = push_tags("origin", &["v1", "v2"], repo)?;
+        assert_eq!(outcomes["v1"], PushOutcome::Pushed);
+        assert_eq!(
+            outcomes["v2"],
+            PushOutcome::Rejected {
+                reason: "already exists".to_string(),
+                commit: Some(first),
+            }
+        );
+        assert_eq!(
+            push_tags("origin", &["v1"], repo)?["v1"],
+            PushOutcome::UpToDate
+        );
+        assert!(push_tags("no-such-remote", &["v1"], repo).is_err());
+
+        // the same release, annotated twice
+        git(&["tag", "-a", "v3", "-m", "first notes"])?;
+        git(&["push", "--quiet", "origin", "v3"])?;
+        git(&["tag", "-d", "v3"])?;
+        git(&["tag", "-a", "v3", "-m", "second notes"])?;
+        assert_eq!(
+            push_tags("origin", &["v3"], repo)?["v3"],
+            PushOutcome::UpToDate
+        );
+        Ok(())
+    }
+
     #[test]
     fn current_branch_exists() -> eyre::Result<()> {
         let current_branch = super::current_branch(std::path::Path::new("."))?;
@@ -1,28 +1,34 @@
 use crate::{
+    config::{Bump, CCVerConfig},
     graph::{
         GraphOps,
         commit::CommitExt,
+        derivation::{BaseSource, Derivation, DerivationExt, SetDerivationExt},
         head::HasHead,
         node::CommitGraphNodeWeight,
         parents_and_children::HasParentsAndChildren,
+        tag::TagExt,
         tail::HasTail,
-        version::{ExistingVersionExt, SetVersionExt},
+        version::{ExistingVersionExt, SetVersionExt, TaggedVersionExt as _},
     },
     logs::LogEntry,
     version::Version,
     version_format::VersionFormat,
 };
+use eyre::*;
 use petgraph::{
     Direction, EdgeType, Graph,
     csr::IndexType,
     graph::{EdgeIndex, NodeIndex},
     visit::{DfsPostOrder, Walker},
 };
-use tracing::debug;
+use std::collections::HashMap;
+use tracing::{debug, warn};
 
 pub struct WithCCVerVersions<T> {
     inner: T,
     version_format: VersionFormat,
+    config: CCVerConfig,
 }
 
 pub trait AsLogEntry {
@@ -42,63 +48,204 @@ impl<'a> ExistingVersionExt for CommitGraphNodeWeight<'a> {
     }
 }
 
-impl<'a, T> WithCCVerVersions<T> {
-    pub fn new<N, E, Ty, Ix>(mut inner: T, version_format: VersionFormat) -> Self
+impl<T> WithCCVerVersions<T> {
+    /// Assigns every ancestor of HEAD its version. `seeds` holds versions
+    /// already known for commits, by hash, which are used like version tags
+    /// when their parents were not loaded.
+    pub fn new<N, E, Ty, Ix>(
+        mut inner: T,
+        version_format: VersionFormat,
+        config: CCVerConfig,
+        seeds: &HashMap<String, Version>,
+    ) -> Result<Self>
     where
         T: GraphOps<N, E, Ty, Ix>
             + HasParentsAndChildren<N, E, Ty, Ix>
             + HasTail<N, E, Ty, Ix>
             + HasHead<N, E, Ty, Ix>,
-        N: ExistingVersionExt + AsLogEntry + SetVersionExt,
+        N: ExistingVersionExt + AsLogEntry + SetVersionExt + SetDerivationExt,
         Ix: IndexType,
         Ty: EdgeType,
     {
+        let head_idx = inner
+            .head_idx()
+            .ok_or_eyre("HEAD is not in the history; is it detached off every branch?")?;
         let base = inner.base_graph();
 
         let mut last_version =
-            version_format.as_default_version(&inner.head().unwrap().as_log_entry());
+            version_format.as_default_version(&inner.node_weight(head_idx).unwrap().as_log_entry());
+        let mut last_commit: Option<String> = None;
         // let reversed = Reversed(base);
-        let versions = DfsPostOrder::new(base, inner.head_idx().unwrap())
+        let derivations = DfsPostOrder::new(base, head_idx)
             .iter(base)
             .map(|idx| {
                 let weight = inner.node_weight(idx).unwrap();
                 let log_entry = weight.as_log_entry();
-                let version = inner
+                let parents = inner
                     .parents(idx)
                     .iter()
-                    .filter_map(|p| p.as_existing_version())
-                    .max()
-                    .unwrap_or_else(|| last_version.clone())
-                    .next_version(&log_entry, &version_format);
-                last_version = version.clone();
-                (idx, version)
+                    .map(|p| {
+                        (
+                            p.as_log_entry().commit_hash.to_string(),
+                            p.as_existing_version(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                // the walk starts at a version tag, or a commit whose version
+                // is known, whose parents were not loaded
+                if idx != head_idx
+                    && parents.is_empty()
+                    && !log_entry.parent_hashes.is_empty()
+                    && let Some((known, base_source)) = log_entry
+                        .as_tagged_version()
+                        .map(|tag| (tag.clone(), BaseSource::Tag))
+                        .or_else(|| {
+                            let seed = seeds.get(log_entry.commit_hash)?;
+                            Some((seed.clone(), BaseSource::Seed))
+                        })
+                {
+                    last_version = known.clone();
+                    last_commit = Some(log_entry.commit_hash.to_string());
+                    let derivation = Derivation {
+                        parents,
+                        subject_class: known.implied_class(&log_entry, &config),
+                        base: known.clone(),
+                        base_source,
+                        branch_class: config.branch_class(log_entry.branch),
+                        merge: log_entry.parent_hashes.len() == 2,
+                        forced: None,
+                        release_as: None,
+                        release_as_error: None,
+                        computed: known,
+                        line_violation: None,
+                    };
+                    return Ok((idx, derivation));
+                }
+                let (base, base_source) = parents
+                    .iter()
+                    .filter_map(|(hash, version)| Some((version.clone()?, hash)))
+                    .max_by(|(a, _), (b, _)| a.cmp(b))
+                    .map(|(version, hash)| (version, BaseSource::Parent(hash.clone())))
+                    .unwrap_or_else(|| {
+                        (
+                            last_version.clone(),
+                            BaseSource::PreviousInWalk(last_commit.clone()),
+                        )
+                    });
+                let forced = (idx == head_idx).then(|| base.head_bump(&config)).flatten();
+                let derivation = derive(
+                    parents,
+                    base,
+                    base_source,
+                    forced,
+                    &log_entry,
+                    &version_format,
+                    &config,
+                );
+                last_version = derivation.computed.clone();
+                last_commit = Some(log_entry.commit_hash.to_string());
+                Ok((idx, derivation))
             })
-            .collect::<Vec<_>>();
+            .collect::<Result<Vec<_>>>()?;
 
-        for (idx, version) in versions {
+        for (idx, derivation) in derivations {
             let log_entry = inner.node_weight(idx).unwrap().as_log_entry();
             debug!(
                 "Setting version {} for node: {:?} {} {:?}",
-                version, idx, log_entry.commit_hash, log_entry.parent_hashes
+                derivation.computed, idx, log_entry.commit_hash, log_entry.parent_hashes
             );
-            inner.node_weight_mut(idx).unwrap().set_version(version);
+            let weight = inner.node_weight_mut(idx).unwrap();
+            weight.set_version(derivation.computed.clone());
+            weight.set_derivation(derivation);
         }
 
-        Self {
+        Ok(Self {
             inner,
             version_format,
-        }
+            config,
+        })
+    }
+}
+
+/// How a commit whose history was loaded takes its version from `base`,
+/// bumped by `forced` instead of its subject when that is given.
+fn derive(
+    parents: Vec<(String, Option<Version>)>,
+    base: Version,
+    base_source: BaseSource,
+    forced: Option<Bump>,
+    log_entry: &LogEntry,
+    version_format: &VersionFormat,
+    config: &CCVerConfig,
+) -> Derivation {
+    let release_as = base
+        .pinned_version(log_entry, version_format)
+        .filter(|_| forced.is_none());
+    let computed = base.forced_next_version(forced, log_entry, version_format, config);
+    // a tag records a release that was already made
+    let untagged = log_entry.as_tagged_version().is_none();
+    let release_as_error = (untagged && forced.is_none())
+        .then(|| base.release_as(log_entry, version_format).err())
+        .flatten()
+        .map(|e| e.to_string());
+    let line_violation = untagged
+        .then(|| base.check_line(&computed, log_entry, config).err())
+        .flatten()
+        .map(|e| e.to_string());
+    if let Some(violation) = &line_violation {
+        warn!("{}", violation);
+    }
+    Derivation {
+        parents,
+        subject_class: forced
+            .map(Into::into)
+            .unwrap_or_else(|| base.implied_class(log_entry, config)),
+        base,
+        base_source,
+        branch_class: config.branch_class(log_entry.branch),
+        merge: log_entry.parent_hashes.len() == 2,
+        forced,
+        release_as,
+        release_as_error,
+        computed,
+        line_violation,
     }
 }
 
 impl<N, E, Ty, Ix, T> GraphOps<N, E, Ty, Ix> for WithCCVerVersions<T>
 where
-    T: GraphOps<N, E, Ty, Ix> + HasParentsAndChildren<N, E, Ty, Ix>,
-    N: ExistingVersionExt + AsLogEntry + SetVersionExt,
-    Ix: Copy,
+    T: GraphOps<N, E, Ty, Ix> + HasParentsAndChildren<N, E, Ty, Ix> + HasHead<N, E, Ty, Ix>,
+    N: ExistingVersionExt + AsLogEntry + SetVersionExt + DerivationExt + SetDerivationExt,
+    Ix: Copy + PartialEq,
 {
     fn add_node(&mut self, weight: N) -> NodeIndex<Ix> {
+        let old_head = self.inner.head_idx();
         let idx = self.inner.add_node(weight);
+        let is_head = self.inner.head_idx() == Some(idx);
+        // the forced bump moves on to the new HEAD, so the old HEAD takes the
+        // version its commits imply
+        if is_head
+            && let Some(old_head) = old_head.filter(|old_head| *old_head != idx)
+            && let Some(derivation) = self
+                .inner
+                .node_weight(old_head)
+                .and_then(|weight| weight.derivation())
+                .filter(|derivation| derivation.forced.is_some())
+        {
+            let log_entry = self.inner.node_weight(old_head).unwrap().as_log_entry();
+            let derivation = derive(
+                derivation.parents,
+                derivation.base,
+                derivation.base_source,
+                None,
+                &log_entry,
+                &self.version_format,
+                &self.config,
+            );
+            let weight = self.inner.node_weight_mut(old_head).unwrap();
+            weight.set_version(derivation.computed.clone());
+            weight.set_derivation(derivation);
+        }
         let weight = self.inner.node_weight(idx).unwrap();
         let log_entry = weight.as_log_entry();
         let max_parent = self
@@ -108,7 +255,12 @@ where
             .filter_map(|p| p.as_existing_version())
             .max()
             .unwrap();
-        let version = max_parent.next_version(&log_entry, &self.version_format);
+        // a new HEAD takes a forced bump like the HEAD of the walk does
+        let forced = is_head
+            .then(|| max_parent.head_bump(&self.config))
+            .flatten();
+        let version =
+            max_parent.forced_next_version(forced, &log_entry, &self.version_format, &self.config);
         self.inner
             .node_weight_mut(idx)
             .unwrap()
@@ -251,3 +403,21 @@ where
         self.inner.commit_idx_by_hash(commit)
     }
 }
+
+impl<N, E, Ty, Ix, T> TagExt<N, E, Ty, Ix> for WithCCVerVersions<T>
+where
+    T: TagExt<N, E, Ty, Ix>,
+{
+    fn version_tag(&self, tag: &Version) -> Option<&N> {
+        self.inner.version_tag(tag)
+    }
+    fn text_tag(&self, tag: &str) -> Option<&N> {
+        self.inner.text_tag(tag)
+    }
+    fn text_tag_idx(&self, tag: &str) -> Option<NodeIndex<Ix>> {
+        self.inner.text_tag_idx(tag)
+    }
+    fn version_tag_idx(&self, tag: &Version) -> Option<NodeIndex<Ix>> {
+        self.inner.version_tag_idx(tag)
+    }
+}
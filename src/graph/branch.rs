@@ -8,9 +8,10 @@ use petgraph::{
 use crate::{
     graph::{
         GraphOps, assign_versions::AsLogEntry, commit::CommitExt, head::HasHead,
-        parents_and_children::HasParentsAndChildren, tail::HasTail,
+        parents_and_children::HasParentsAndChildren, tag::TagExt, tail::HasTail,
     },
     logs::Decoration,
+    version::Version,
 };
 
 pub trait HasBranches {
@@ -263,3 +264,21 @@ where
         self.inner.commit_idx_by_hash(commit)
     }
 }
+
+impl<N, E, Ty, Ix, T> TagExt<N, E, Ty, Ix> for BranchMemo<T, Ix>
+where
+    T: TagExt<N, E, Ty, Ix>,
+{
+    fn version_tag(&self, tag: &Version) -> Option<&N> {
+        self.inner.version_tag(tag)
+    }
+    fn text_tag(&self, tag: &str) -> Option<&N> {
+        self.inner.text_tag(tag)
+    }
+    fn text_tag_idx(&self, tag: &str) -> Option<NodeIndex<Ix>> {
+        self.inner.text_tag_idx(tag)
+    }
+    fn version_tag_idx(&self, tag: &Version) -> Option<NodeIndex<Ix>> {
+        self.inner.version_tag_idx(tag)
+    }
+}
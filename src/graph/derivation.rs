@@ -0,0 +1,67 @@
+use crate::{
+    config::{BranchClass, Bump, SubjectClass},
+    graph::{CommitGraphNodeWeight, node::CommitGraphNodeData},
+    version::Version,
+};
+
+/// Records how [`crate::graph::assign_versions::WithCCVerVersions`] arrived at
+/// the computed version of a commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Derivation {
+    /// Every parent with the version it had when this commit was visited.
+    pub parents: Vec<(String, Option<Version>)>,
+    /// The version `next_version` was applied to.
+    pub base: Version,
+    pub base_source: BaseSource,
+    pub subject_class: SubjectClass,
+    pub branch_class: BranchClass,
+    pub merge: bool,
+    pub forced: Option<Bump>,
+    /// The version a `Release-As:` footer pinned the commit to.
+    pub release_as: Option<Version>,
+    /// Why a `Release-As:` footer on a commit without a tag was ignored.
+    pub release_as_error: Option<String>,
+    pub computed: Version,
+    /// Why the computed version leaves the line a maintenance branch keeps.
+    pub line_violation: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BaseSource {
+    /// The parent with the greatest existing version.
+    Parent(String),
+    /// No parent had a version yet, so the walk continued from the commit
+    /// visited before this one (`None` for the default starting version).
+    PreviousInWalk(Option<String>),
+    /// The commit's own version tag: the history before it was not loaded.
+    Tag,
+    /// A version known for the commit, such as a cached one: the history
+    /// before it was not loaded.
+    Seed,
+}
+
+pub trait DerivationExt {
+    fn derivation(&self) -> Option<Derivation>;
+}
+
+pub trait SetDerivationExt {
+    fn set_derivation(&mut self, derivation: Derivation);
+}
+
+impl DerivationExt for CommitGraphNodeData<'_> {
+    fn derivation(&self) -> Option<Derivation> {
+        self.derivation.clone()
+    }
+}
+
+impl DerivationExt for CommitGraphNodeWeight<'_> {
+    fn derivation(&self) -> Option<Derivation> {
+        self.lock().unwrap().derivation()
+    }
+}
+
+impl SetDerivationExt for CommitGraphNodeWeight<'_> {
+    fn set_derivation(&mut self, derivation: Derivation) {
+        self.lock().unwrap().derivation = Some(derivation);
+    }
+}
@@ -3,9 +3,10 @@ use std::ops::{Deref, DerefMut};
 use crate::{
     graph::{
         GraphOps, commit::CommitExt, node::CommitGraphNodeWeight,
-        parents_and_children::HasParentsAndChildren, tail::HasTail,
+        parents_and_children::HasParentsAndChildren, tag::TagExt, tail::HasTail,
     },
     logs::{Decoration, LogEntry},
+    version::Version,
 };
 use petgraph::{
     Direction, Graph,
@@ -13,11 +14,13 @@ use petgraph::{
 };
 
 pub struct HeadMemo<T, Ix> {
-    head_idx: NodeIndex<Ix>,
+    head_idx: Option<NodeIndex<Ix>>,
     inner: T,
 }
 
 impl<T, Ix> HeadMemo<T, Ix> {
+    /// Remembers the commit HEAD points at, if the history has it: a
+    /// detached HEAD off every branch is not listed by `git log --branches`.
     pub fn new<N, E, Ty>(inner: T) -> Self
     where
         T: GraphOps<N, E, Ty, Ix>,
@@ -27,8 +30,7 @@ impl<T, Ix> HeadMemo<T, Ix> {
         let head_idx = inner
             .node_identifiers()
             .into_iter()
-            .find(|idx| inner.node_weight(*idx).unwrap().is_current_head())
-            .unwrap();
+            .find(|idx| inner.node_weight(*idx).unwrap().is_current_head());
         Self { head_idx, inner }
     }
 }
@@ -53,11 +55,11 @@ pub trait Headed {
 }
 
 impl Headed for LogEntry<'_> {
+    /// `HEAD -> <branch>`, or a bare `HEAD` when it is detached.
     fn is_current_head(&self) -> bool {
-        self.decorations.iter().any(|d| match d {
-            Decoration::HeadIndicator(_) => true,
-            _ => false,
-        })
+        self.decorations
+            .iter()
+            .any(|d| matches!(d, Decoration::HeadIndicator(_) | Decoration::Branch("HEAD")))
     }
     fn current_head_branch(&self) -> Option<String> {
         self.decorations.iter().find_map(|d| match d {
@@ -85,7 +87,7 @@ where
     fn add_node(&mut self, weight: N) -> NodeIndex<Ix> {
         if weight.is_current_head() {
             let head_idx = self.inner.add_node(weight);
-            self.head_idx = head_idx;
+            self.head_idx = Some(head_idx);
             head_idx
         } else {
             self.inner.add_node(weight)
@@ -167,7 +169,7 @@ where
     Ix: Copy,
 {
     fn head_idx(&self) -> Option<NodeIndex<Ix>> {
-        Some(self.head_idx)
+        self.head_idx
     }
     fn head(&self) -> Option<&N> {
         self.inner.node_weight(self.head_idx()?)
@@ -215,3 +217,21 @@ where
         self.inner.commit_idx_by_hash(commit)
     }
 }
+
+impl<N, E, Ty, Ix, T> TagExt<N, E, Ty, Ix> for HeadMemo<T, Ix>
+where
+    T: TagExt<N, E, Ty, Ix>,
+{
+    fn version_tag(&self, tag: &Version) -> Option<&N> {
+        self.inner.version_tag(tag)
+    }
+    fn text_tag(&self, tag: &str) -> Option<&N> {
+        self.inner.text_tag(tag)
+    }
+    fn text_tag_idx(&self, tag: &str) -> Option<NodeIndex<Ix>> {
+        self.inner.text_tag_idx(tag)
+    }
+    fn version_tag_idx(&self, tag: &Version) -> Option<NodeIndex<Ix>> {
+        self.inner.version_tag_idx(tag)
+    }
+}
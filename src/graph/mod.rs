@@ -1,9 +1,11 @@
 use std::{
+    collections::HashMap,
     marker::PhantomData,
     ops::{Deref, DerefMut},
     sync::{Arc, Mutex},
 };
 
+use eyre::OptionExt as _;
 use petgraph::{
     Directed, Direction, EdgeType, Graph,
     csr::IndexType,
@@ -19,6 +21,7 @@ pub mod assign_versions;
 pub mod branch;
 pub mod branch_heads;
 pub mod commit;
+pub mod derivation;
 pub mod head;
 pub mod node;
 pub mod parents_and_children;
@@ -26,6 +29,7 @@ pub mod tag;
 pub mod tail;
 pub mod version;
 use crate::{
+    config::CCVerConfig,
     graph::{
         assign_versions::WithCCVerVersions,
         branch::BranchMemo,
@@ -33,9 +37,12 @@ use crate::{
         head::{HasHead, HeadMemo},
         node::{CommitGraphNodeData, CommitGraphNodeWeight},
         parents_and_children::{HasParentsAndChildren, WithParentsAndChildEdges},
+        tag::{TagExt, TagMemo},
         tail::{HasTail, TailMemo},
+        version::ExistingVersionExt as _,
     },
-    logs::Logs,
+    logs::{LogEntry, Logs, PeekLogEntry as _},
+    version::Version,
     version_format::VersionFormat,
 };
 
@@ -47,6 +54,7 @@ pub trait CommitGraph<N, E, Ty, Ix>:
     + HasTail<N, E, Ty, Ix>
     + HasParentsAndChildren<N, E, Ty, Ix>
     + CommitExt<N, E, Ty, Ix>
+    + TagExt<N, E, Ty, Ix>
 {
 }
 
@@ -56,6 +64,7 @@ impl<N, E, Ty, Ix, T> CommitGraph<N, E, Ty, Ix> for T where
         + HasTail<N, E, Ty, Ix>
         + HasParentsAndChildren<N, E, Ty, Ix>
         + CommitExt<N, E, Ty, Ix>
+        + TagExt<N, E, Ty, Ix>
 {
 }
 
@@ -122,6 +131,21 @@ impl<'a, N, E, Ty, Ix> CommitExt<N, E, Ty, Ix> for MemoizedCommitGraph<'a, N, E,
     }
 }
 
+impl<'a, N, E, Ty, Ix> TagExt<N, E, Ty, Ix> for MemoizedCommitGraph<'a, N, E, Ty, Ix> {
+    fn version_tag(&self, tag: &Version) -> Option<&N> {
+        self.inner.version_tag(tag)
+    }
+    fn text_tag(&self, tag: &str) -> Option<&N> {
+        self.inner.text_tag(tag)
+    }
+    fn text_tag_idx(&self, tag: &str) -> Option<NodeIndex<Ix>> {
+        self.inner.text_tag_idx(tag)
+    }
+    fn version_tag_idx(&self, tag: &Version) -> Option<NodeIndex<Ix>> {
+        self.inner.version_tag_idx(tag)
+    }
+}
+
 impl<'a, N, E, Ty, Ix> GraphOps<N, E, Ty, Ix> for MemoizedCommitGraph<'a, N, E, Ty, Ix> {
     fn add_node(&mut self, weight: N) -> NodeIndex<Ix> {
         self.inner.add_node(weight)
@@ -188,11 +212,28 @@ impl<'a, N, E, Ty, Ix> GraphOps<N, E, Ty, Ix> for MemoizedCommitGraph<'a, N, E,
 }
 
 impl<'a> MemoizedCommitGraph<'a> {
-    pub fn new(logs: Logs<'a>, version_format: &VersionFormat) -> MemoizedCommitGraph<'a> {
+    pub fn new(
+        logs: Logs<'a>,
+        version_format: &VersionFormat,
+        config: &CCVerConfig,
+    ) -> eyre::Result<MemoizedCommitGraph<'a>> {
+        Self::with_seeds(logs, version_format, config, &HashMap::new())
+    }
+
+    /// Like [`MemoizedCommitGraph::new`], but commits in `seeds` whose
+    /// parents were not loaded take the version given for them.
+    pub fn with_seeds(
+        logs: Logs<'a>,
+        version_format: &VersionFormat,
+        config: &CCVerConfig,
+        seeds: &HashMap<String, Version>,
+    ) -> eyre::Result<MemoizedCommitGraph<'a>> {
         let graph: Graph<Arc<Mutex<CommitGraphNodeData<'a>>>, ()> = CommitGraphT::new();
         debug!("CommitGraphT created");
         let graph = CommitMemo::new(graph);
         debug!("CommitMemo created");
+        let graph = TagMemo::new(graph);
+        debug!("TagMemo created");
         let mut graph = WithParentsAndChildEdges::new(graph);
         debug!("WithParentsAndChildEdges created");
         for log in logs {
@@ -206,12 +247,45 @@ impl<'a> MemoizedCommitGraph<'a> {
         let graph = BranchMemo::new(graph);
         debug!("BranchMemo created");
 
-        let graph = WithCCVerVersions::new(graph, version_format.clone());
+        let graph = WithCCVerVersions::new(graph, version_format.clone(), config.clone(), seeds)?;
 
-        MemoizedCommitGraph {
+        Ok(MemoizedCommitGraph {
             inner: Box::new(graph),
             _marker: PhantomData,
+        })
+    }
+
+    /// Adds the commit `message` would make on top of HEAD as the new HEAD,
+    /// which takes any forced bump, and returns its version with the commit
+    /// it is made on. Errors when its `Release-As:` footer can't be followed
+    /// or its version leaves the line of a maintenance branch.
+    pub fn peek(
+        &mut self,
+        message: &'a str,
+        version_format: &VersionFormat,
+        config: &CCVerConfig,
+    ) -> eyre::Result<(Version, LogEntry<'a>)> {
+        let head = self.head().ok_or_eyre("No Head Found")?.clone();
+        let parent = head.lock().unwrap().log_entry.clone();
+        let next_entry = message.into_peek_log_entry(parent.commit_hash, parent.branch)?;
+        if head.as_existing_version().is_none() {
+            return Ok((version_format.as_default_version(&next_entry), parent));
         }
+        let idx = self.add_node(Arc::new(Mutex::new(CommitGraphNodeData::from(
+            next_entry.clone(),
+        ))));
+        // read after the peeked commit took over any forced bump
+        let base = head
+            .as_existing_version()
+            .ok_or_eyre("Current Branch Head Was Not Assigned a Version")?;
+        base.release_as(&next_entry, version_format)?;
+        let next = self
+            .node_weight(idx)
+            .and_then(|next| next.lock().unwrap().version.clone())
+            .ok_or_eyre("Peeked Commit Was Not Assigned a Version")?;
+        base.check_line(&next, &next_entry, config)?;
+        debug!(version = %next, "Peek result");
+        Ok((next, parent))
     }
 }
 
@@ -345,18 +419,26 @@ where
 mod graph_tests {
 
     use crate::{
-        graph::{head::HasHead, parents_and_children::HasParentsAndChildren},
-        logs::Logs,
+        backend::{GitBackend as _, MemoryBackend},
+        config::{Bump, CCVerConfig},
+        graph::{
+            GraphOps as _, head::HasHead, node::CommitGraphNodeData,
+            parents_and_children::HasParentsAndChildren,
+        },
+        logs::{Logs, PeekLogEntry as _},
         version_format::VersionFormat,
     };
     use eyre::*;
     use petgraph::visit::{Bfs, Walker as _};
+    use std::sync::{Arc, Mutex};
 
     #[test]
     fn layered_graph_construction() -> Result<()> {
-        let logs = Logs::default();
+        let raw = crate::git::formatted_logs(&std::env::current_dir()?)?;
+        let logs = Logs::from_log_str(&raw)?;
         let version_format = VersionFormat::default();
-        let graph = super::MemoizedCommitGraph::new(logs, &version_format);
+        let graph =
+            super::MemoizedCommitGraph::new(logs, &version_format, &CCVerConfig::default())?;
 
         let head_idx = graph.head_idx().unwrap();
 
@@ -375,9 +457,14 @@ mod graph_tests {
 
     #[test]
     fn test_graph_walk() -> Result<()> {
-        let logs = Logs::default();
+        let raw = crate::git::formatted_logs(&std::env::current_dir()?)?;
+        let logs = Logs::from_log_str(&raw)?;
         let version_format = VersionFormat::default();
-        let graph = super::MemoizedCommitGraph::new(logs.clone(), &version_format);
+        let graph = super::MemoizedCommitGraph::new(
+            logs.clone(),
+            &version_format,
+            &CCVerConfig::default(),
+        )?;
 
         assert_ne!(logs.len(), 0);
 
@@ -398,4 +485,63 @@ mod graph_tests {
 
         Ok(())
     }
+
+    #[test]
+    fn added_head_takes_the_forced_bump() -> Result<()> {
+        let repo = MemoryBackend::new("main");
+        repo.commit("feat: initial");
+        repo.tag("v0.1.0")?;
+        let head = repo.commit("fix: a bug");
+        let raw = repo.log(false, None)?;
+        let config = CCVerConfig {
+            force_bump: Some(Bump::Major),
+            ..Default::default()
+        };
+        let mut graph = super::MemoizedCommitGraph::new(
+            Logs::from_log_str(&raw)?,
+            &VersionFormat::default(),
+            &config,
+        )?;
+
+        let next = "fix: another bug".into_peek_log_entry(&head, "main")?;
+        let idx = graph.add_node(Arc::new(Mutex::new(CommitGraphNodeData::from(next))));
+        assert_eq!(graph.head_idx(), Some(idx));
+        let version = graph
+            .head()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .version
+            .clone()
+            .unwrap();
+        assert_eq!(version.to_string(), "v1.0.0");
+        Ok(())
+    }
+
+    #[test]
+    fn records_commits_off_their_maintenance_line() -> Result<()> {
+        use crate::graph::derivation::DerivationExt as _;
+
+        let repo = MemoryBackend::new("main");
+        repo.commit("feat: initial");
+        repo.tag("v1.0.0")?;
+        repo.branch("release/1.x")?;
+        repo.checkout("release/1.x")?;
+        repo.commit("feat!: drop the old flag");
+        let raw = repo.log(false, None)?;
+        let graph = super::MemoizedCommitGraph::new(
+            Logs::from_log_str(&raw)?,
+            &VersionFormat::default(),
+            &CCVerConfig::default(),
+        )?;
+
+        let derivation = graph.head().unwrap().derivation().unwrap();
+        assert_eq!(derivation.computed.to_string(), "v2.0.0");
+        assert!(
+            derivation
+                .line_violation
+                .is_some_and(|violation| violation.contains("leaving the 1.x line"))
+        );
+        Ok(())
+    }
 }
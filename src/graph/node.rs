@@ -1,5 +1,8 @@
 use std::sync::{Arc, Mutex};
 
+use eyre::{OptionExt as _, Result, eyre};
+
+use crate::graph::derivation::Derivation;
 use crate::logs::LogEntry;
 use crate::version::Version;
 
@@ -9,6 +12,7 @@ pub type CommitGraphNodeWeight<'a> = Arc<Mutex<CommitGraphNodeData<'a>>>;
 pub struct CommitGraphNodeData<'a> {
     pub log_entry: LogEntry<'a>,
     pub version: Option<Version>,
+    pub derivation: Option<Derivation>,
 }
 
 impl<'a> From<LogEntry<'a>> for CommitGraphNodeData<'a> {
@@ -16,6 +20,24 @@ impl<'a> From<LogEntry<'a>> for CommitGraphNodeData<'a> {
         CommitGraphNodeData {
             log_entry,
             version: None,
+            derivation: None,
+        }
+    }
+}
+
+impl CommitGraphNodeData<'_> {
+    /// The version assigned to the commit, to be released. Errors when it
+    /// has none, or when the commit's `Release-As:` footer had to be ignored.
+    pub fn release_version(&self) -> Result<Version> {
+        if let Some(error) = self
+            .derivation
+            .as_ref()
+            .and_then(|derivation| derivation.release_as_error.as_ref())
+        {
+            return Err(eyre!("{}", error));
         }
+        self.version
+            .clone()
+            .ok_or_eyre("Current Branch Head Was Not Assigned a Version")
     }
 }
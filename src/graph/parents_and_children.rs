@@ -6,9 +6,11 @@ use crate::{
         GraphOps,
         commit::{CommitExt, HasCommitHash},
         node::CommitGraphNodeWeight,
+        tag::TagExt,
         tail::HasTail,
     },
     logs::LogEntry,
+    version::Version,
 };
 use petgraph::{
     Direction, Graph,
@@ -240,3 +242,21 @@ where
         self.inner.commit_idx_by_hash(commit)
     }
 }
+
+impl<N, E, Ty, Ix, T> TagExt<N, E, Ty, Ix> for WithParentsAndChildEdges<T, Ix>
+where
+    T: TagExt<N, E, Ty, Ix>,
+{
+    fn version_tag(&self, tag: &Version) -> Option<&N> {
+        self.inner.version_tag(tag)
+    }
+    fn text_tag(&self, tag: &str) -> Option<&N> {
+        self.inner.text_tag(tag)
+    }
+    fn text_tag_idx(&self, tag: &str) -> Option<NodeIndex<Ix>> {
+        self.inner.text_tag_idx(tag)
+    }
+    fn version_tag_idx(&self, tag: &Version) -> Option<NodeIndex<Ix>> {
+        self.inner.version_tag_idx(tag)
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use crate::{
-    graph::GraphOps,
+    graph::{GraphOps, commit::CommitExt, node::CommitGraphNodeWeight},
     logs::{Decoration, LogEntry, Tag},
     version::Version,
 };
@@ -42,12 +42,50 @@ pub trait HasTextTags {
     fn text_tags(&self) -> Vec<String>;
 }
 
+impl HasVersionTags for CommitGraphNodeWeight<'_> {
+    fn version_tags(&self) -> Vec<Version> {
+        self.lock().unwrap().log_entry.version_tags()
+    }
+}
+
+impl HasTextTags for CommitGraphNodeWeight<'_> {
+    fn text_tags(&self) -> Vec<String> {
+        self.lock().unwrap().log_entry.text_tags()
+    }
+}
+
 pub struct TagMemo<T, Ix> {
     inner: T,
     version_tag_map: HashMap<Version, NodeIndex<Ix>>,
     text_tag_map: HashMap<String, NodeIndex<Ix>>,
 }
 
+impl<Ix, T> TagMemo<T, Ix> {
+    pub fn new<N, E, Ty>(graph: T) -> TagMemo<T, Ix>
+    where
+        T: GraphOps<N, E, Ty, Ix>,
+        N: HasVersionTags + HasTextTags,
+        Ix: Copy,
+    {
+        let mut version_tag_map = HashMap::new();
+        let mut text_tag_map = HashMap::new();
+        for idx in graph.node_identifiers() {
+            let weight = graph.node_weight(idx).unwrap();
+            for version in weight.version_tags() {
+                version_tag_map.insert(version, idx);
+            }
+            for text in weight.text_tags() {
+                text_tag_map.insert(text, idx);
+            }
+        }
+        TagMemo {
+            inner: graph,
+            version_tag_map,
+            text_tag_map,
+        }
+    }
+}
+
 impl<N, E, Ty, Ix, T> GraphOps<N, E, Ty, Ix> for TagMemo<T, Ix>
 where
     T: GraphOps<N, E, Ty, Ix>,
@@ -171,3 +209,15 @@ where
         self.version_tag_map.get(tag).cloned()
     }
 }
+
+impl<N, E, Ty, Ix, T> CommitExt<N, E, Ty, Ix> for TagMemo<T, Ix>
+where
+    T: CommitExt<N, E, Ty, Ix>,
+{
+    fn commit_by_hash(&self, commit: &str) -> Option<&N> {
+        self.inner.commit_by_hash(commit)
+    }
+    fn commit_idx_by_hash(&self, commit: &str) -> Option<NodeIndex<Ix>> {
+        self.inner.commit_idx_by_hash(commit)
+    }
+}
@@ -1,7 +1,11 @@
 use std::ops::{Deref, DerefMut};
 
-use crate::graph::{
-    GraphOps, commit::CommitExt, head::HasHead, parents_and_children::HasParentsAndChildren,
+use crate::{
+    graph::{
+        GraphOps, commit::CommitExt, head::HasHead, parents_and_children::HasParentsAndChildren,
+        tag::TagExt,
+    },
+    version::Version,
 };
 use petgraph::graph::NodeIndex;
 
@@ -194,3 +198,21 @@ where
         self.inner.head()
     }
 }
+
+impl<N, E, Ty, Ix, T> TagExt<N, E, Ty, Ix> for TailMemo<T, Ix>
+where
+    T: TagExt<N, E, Ty, Ix>,
+{
+    fn version_tag(&self, tag: &Version) -> Option<&N> {
+        self.inner.version_tag(tag)
+    }
+    fn text_tag(&self, tag: &str) -> Option<&N> {
+        self.inner.text_tag(tag)
+    }
+    fn text_tag_idx(&self, tag: &str) -> Option<NodeIndex<Ix>> {
+        self.inner.text_tag_idx(tag)
+    }
+    fn version_tag_idx(&self, tag: &Version) -> Option<NodeIndex<Ix>> {
+        self.inner.version_tag_idx(tag)
+    }
+}
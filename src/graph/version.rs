@@ -1,4 +1,5 @@
 use crate::{
+    config::CCVerConfig,
     graph::{CommitGraphNodeWeight, node::CommitGraphNodeData},
     logs::{Decoration, LogEntry, Tag},
     version::Version,
@@ -54,30 +55,50 @@ impl<'a> ExistingVersionExt for CommitGraphNodeData<'a> {
 }
 
 pub trait NextVersionExt<'a> {
-    fn as_next_version(&'a self, max_parent: &Version, version_format: &VersionFormat) -> Version;
+    fn as_next_version(
+        &'a self,
+        max_parent: &Version,
+        version_format: &VersionFormat,
+        config: &CCVerConfig,
+    ) -> Version;
 }
 
 impl<'a> NextVersionExt<'a> for &CommitGraphNodeWeight<'a> {
-    fn as_next_version(&'a self, max_parent: &Version, version_format: &VersionFormat) -> Version {
+    fn as_next_version(
+        &'a self,
+        max_parent: &Version,
+        version_format: &VersionFormat,
+        config: &CCVerConfig,
+    ) -> Version {
         let data = self.lock().unwrap();
         let existing_version = data.log_entry.as_existing_version();
         let prev_version = existing_version.unwrap_or(max_parent.clone());
-        prev_version.next_version(&self.lock().unwrap().log_entry, version_format)
+        prev_version.next_version(&self.lock().unwrap().log_entry, version_format, config)
     }
 }
 
 impl<'a> NextVersionExt<'a> for &CommitGraphNodeData<'a> {
-    fn as_next_version(&'a self, max_parent: &Version, version_format: &VersionFormat) -> Version {
+    fn as_next_version(
+        &'a self,
+        max_parent: &Version,
+        version_format: &VersionFormat,
+        config: &CCVerConfig,
+    ) -> Version {
         let existing_version = self.as_existing_version();
         let prev_version = existing_version.unwrap_or(max_parent.clone());
-        prev_version.next_version(&self.log_entry, version_format)
+        prev_version.next_version(&self.log_entry, version_format, config)
     }
 }
 
 impl<'a> NextVersionExt<'a> for LogEntry<'a> {
-    fn as_next_version(&'a self, max_parent: &Version, version_format: &VersionFormat) -> Version {
+    fn as_next_version(
+        &'a self,
+        max_parent: &Version,
+        version_format: &VersionFormat,
+        config: &CCVerConfig,
+    ) -> Version {
         let existing_version = self.as_existing_version();
         let prev_version = existing_version.unwrap_or(max_parent.clone());
-        prev_version.next_version(self, version_format)
+        prev_version.next_version(self, version_format, config)
     }
 }
@@ -0,0 +1,466 @@
+//! Loads only the part of the history the version of HEAD depends on: the
+//! commits after the nearest version tags and cached versions.
+
+use std::collections::{HashMap, HashSet};
+
+use eyre::Result;
+use tracing::{debug, info, instrument};
+
+use crate::{
+    backend::GitBackend,
+    cache::{self, VersionCache},
+    config::CCVerConfig,
+    graph::version::TaggedVersionExt as _,
+    logs::{LogEntry, Logs},
+    parser,
+    tag_pattern::TagPattern,
+    version::Version,
+    version_format::VersionFormat,
+};
+
+/// The variables CI services name the branch being built in, as their
+/// checkouts leave HEAD detached.
+const CI_BRANCH_VARIABLES: [&str; 2] = ["GITHUB_REF_NAME", "CI_COMMIT_BRANCH"];
+
+/// How many times the walk is retried with other boundaries, such as the
+/// tags before the ones it stopped at, before the whole history is loaded
+/// instead.
+const MAX_DEEPENING: usize = 3;
+
+/// The commits reachable from a branch that have a version tag in
+/// `tag_pattern`, with the commit's time and the format its tag's version
+/// implies. Of several version tags on a commit, the one `git log` decorates
+/// it with first counts.
+fn version_tags(
+    branch_tags: &HashMap<String, (String, i64)>,
+    tag_pattern: &TagPattern,
+    version_format: &VersionFormat,
+) -> HashMap<String, (i64, VersionFormat)> {
+    let mut tags = branch_tags
+        .iter()
+        .filter_map(|(tag, (commit, time))| {
+            // read the way the log parser and `TagPattern::logs` read them
+            let version = match tag_pattern.is_default() {
+                true => parser::parse_exact_version(tag, VersionFormat::default()).ok(),
+                false => tag_pattern.parse(tag, version_format),
+            }?;
+            Some((tag, commit, *time, version))
+        })
+        .collect::<Vec<_>>();
+    // `git log` decorates a commit with its tags in reverse order
+    tags.sort_by(|a, b| a.0.cmp(b.0));
+    tags.into_iter()
+        .map(|(_, commit, time, version)| (commit.clone(), (time, version.into())))
+        .collect()
+}
+
+/// The format [`InfersVersionFormat`](crate::logs::InfersVersionFormat)
+/// reads from the whole history: the one the oldest version tag implies, or
+/// `None` if version tags on commits as old imply different formats.
+fn oldest_format(tags: &HashMap<String, (i64, VersionFormat)>) -> Option<VersionFormat> {
+    let Some(oldest) = tags.values().map(|(time, _)| *time).min() else {
+        return Some(VersionFormat::default());
+    };
+    let mut formats = tags
+        .values()
+        .filter(|(time, _)| *time == oldest)
+        .map(|(_, format)| format);
+    let format = formats.next()?;
+    formats.all(|other| other == format).then(|| format.clone())
+}
+
+/// The commit to version in place of the tip of the checked out branch: a
+/// revision given with `--rev`, or a detached HEAD.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub commit: String,
+    /// The branch whose role the commit takes, see [`Logs::headed_at`].
+    pub branch: Option<String>,
+}
+
+impl Target {
+    /// The commit `rev` names when it is given, and otherwise HEAD when it
+    /// is detached or `branch` is given. Without a `branch`, a detached HEAD
+    /// takes the role of the branch a CI service says it is building.
+    #[instrument(skip(backend))]
+    pub fn new(
+        backend: &dyn GitBackend,
+        rev: Option<&str>,
+        branch: Option<&str>,
+    ) -> Result<Option<Target>> {
+        let branch = branch.map(str::to_string);
+        if let Some(rev) = rev {
+            let commit = backend.resolve(rev)?;
+            info!("Versioning {} ({})", rev, commit);
+            return Ok(Some(Target { commit, branch }));
+        }
+        let detached = backend
+            .current_branch()
+            .is_ok_and(|current| current == "HEAD");
+        if !detached && branch.is_none() {
+            return Ok(None);
+        }
+        let branch = match (branch, detached) {
+            (None, true) => ci_branch(),
+            (branch, _) => branch,
+        };
+        info!("Versioning HEAD as a commit on {:?}", branch);
+        Ok(Some(Target {
+            commit: backend.head_hash()?,
+            branch,
+        }))
+    }
+}
+
+impl Target {
+    /// `logs` with this commit as HEAD, see [`Logs::headed_at`].
+    pub fn head<'a>(&'a self, logs: &Logs<'a>) -> Result<Logs<'a>> {
+        logs.headed_at(&self.commit, self.branch.as_deref())
+    }
+}
+
+/// The branch the first of [`CI_BRANCH_VARIABLES`] that is set names.
+fn ci_branch() -> Option<String> {
+    CI_BRANCH_VARIABLES.iter().find_map(|variable| {
+        let branch = std::env::var(variable).ok()?;
+        let branch = branch.trim();
+        (!branch.is_empty()).then(|| {
+            debug!("Using branch {} from {}", branch, variable);
+            branch.to_string()
+        })
+    })
+}
+
+/// The log [`recent_log`] loaded.
+#[derive(Debug, Default)]
+pub struct RecentLog {
+    /// The output of `git log`, for [`Logs::from_log_str`].
+    pub raw: String,
+    /// The version format the whole history implies, when none was given:
+    /// the oldest version tag may not be in `raw`.
+    pub version_format: Option<VersionFormat>,
+    /// The cached versions the walk starts from, for
+    /// [`MemoizedCommitGraph::with_seeds`](crate::graph::MemoizedCommitGraph::with_seeds).
+    pub seeds: HashMap<String, Version>,
+    /// The cache to [`store`](VersionCache::store) the new versions in.
+    pub cache: Option<VersionCache>,
+}
+
+impl From<String> for RecentLog {
+    fn from(raw: String) -> Self {
+        RecentLog {
+            raw,
+            ..Default::default()
+        }
+    }
+}
+
+/// The log of the commits the version of HEAD, or of the commit `tip` when
+/// it is given, is computed from. Every
+/// version tag on a branch and every cached version is a boundary:
+/// its commit takes that version, so the history before it is not loaded.
+/// The version of HEAD is the one the whole history gives, so the walk goes
+/// further back when a commit after a boundary has a parent before it, and
+/// the whole history is loaded when HEAD reaches a root commit past one. A
+/// tag lower than the version computed for its commit changes nothing: the
+/// commits after it start from the tag in the whole history too.
+#[instrument(skip(backend, config, version_format))]
+pub fn recent_log(
+    backend: &dyn GitBackend,
+    config: &CCVerConfig,
+    version_format: Option<&VersionFormat>,
+    changed_paths: bool,
+    tip: Option<&str>,
+) -> Result<RecentLog> {
+    let whole_history = |version_format, cache| {
+        info!("Loading the whole history");
+        Ok(RecentLog {
+            raw: backend.log(changed_paths, tip)?,
+            version_format,
+            seeds: HashMap::new(),
+            cache,
+        })
+    };
+    let Ok(head) = tip.map_or_else(|| backend.head_hash(), |tip| Ok(tip.to_string())) else {
+        return whole_history(None, None);
+    };
+    let branch_tags = backend.branch_tags()?;
+    let parse_format = version_format.cloned().unwrap_or_default();
+    let mut tags = version_tags(&branch_tags, &config.tag_pattern, &parse_format);
+    let Some(format) = version_format.cloned().or_else(|| oldest_format(&tags)) else {
+        debug!("The oldest version tags imply different formats");
+        return whole_history(None, None);
+    };
+    let inferred = version_format.is_none().then(|| format.clone());
+    let cache = VersionCache::open(backend, cache::key(&format, config), &branch_tags)?;
+    // HEAD's own version is computed, not taken from its tag
+    tags.remove(&head);
+    let mut seeds = cache
+        .as_ref()
+        .map(|cache| cache.seeds(&head, &format))
+        .unwrap_or_default();
+    seeds.retain(|commit, _| !tags.contains_key(commit));
+
+    let mut boundaries = tags
+        .into_keys()
+        .chain(seeds.keys().cloned())
+        .collect::<Vec<_>>();
+    for _ in 0..=MAX_DEEPENING {
+        if boundaries.is_empty() {
+            break;
+        }
+        let raw = backend.log_after(changed_paths, tip, &boundaries)?;
+        let logs = config
+            .tag_pattern
+            .logs(Logs::from_log_str(&raw)?, &parse_format);
+        let listed = logs
+            .iter()
+            .map(|log_entry| (log_entry.commit_hash, log_entry.branch))
+            .collect::<HashMap<_, _>>();
+
+        // a cached version holds for the branch it was computed on
+        let moved = seeds
+            .keys()
+            .filter(|commit| {
+                listed.get(commit.as_str()).is_some_and(|branch| {
+                    cache.as_ref().and_then(|cache| cache.branch(commit)) != Some(*branch)
+                })
+            })
+            .cloned()
+            .collect::<HashSet<_>>();
+        if !moved.is_empty() {
+            debug!(
+                "{} cached commit(s) are reached from another branch",
+                moved.len()
+            );
+            seeds.retain(|commit, _| !moved.contains(commit));
+            boundaries.retain(|commit| !moved.contains(commit));
+            continue;
+        }
+
+        if let Some(reached) = reached_boundaries(&logs, &head, &seeds) {
+            // a cached version is the one the whole history gives when the
+            // walk reaches nothing else before it
+            if reached.len() > 1 && reached.iter().any(|commit| seeds.contains_key(*commit)) {
+                debug!("The walk reaches cached versions next to other boundaries");
+                boundaries.retain(|commit| !seeds.contains_key(commit));
+                seeds.clear();
+                continue;
+            }
+            seeds.retain(|commit, _| reached.contains(&commit.as_str()));
+            info!(
+                "Loaded {} commit(s) after {} version tag(s) and {} cached version(s)",
+                logs.len(),
+                reached.len() - seeds.len(),
+                seeds.len()
+            );
+            return Ok(RecentLog {
+                raw,
+                version_format: inferred,
+                seeds,
+                cache,
+            });
+        }
+        debug!("A commit after the boundaries has a parent before them");
+        // cached commits off every branch leave out history they do not
+        // version, and those behind other boundaries leave out nothing
+        let count = boundaries.len();
+        boundaries
+            .retain(|commit| listed.contains_key(commit.as_str()) || !seeds.contains_key(commit));
+        if boundaries.len() < count {
+            continue;
+        }
+        // go back past every boundary the walk stopped at
+        boundaries.retain(|commit| !listed.contains_key(commit.as_str()));
+        if boundaries.len() == count {
+            break;
+        }
+    }
+    whole_history(inferred, cache)
+}
+
+/// The version tagged or seeded commits the ancestors of `head` stop at, or
+/// `None` if `head` reaches a commit with some of its parents left out or a
+/// root commit, whose version would depend on what was left out.
+fn reached_boundaries<'a>(
+    logs: &'a Logs<'a>,
+    head: &str,
+    seeds: &HashMap<String, Version>,
+) -> Option<Vec<&'a str>> {
+    let by_hash = logs
+        .iter()
+        .map(|log_entry| (log_entry.commit_hash, log_entry))
+        .collect::<HashMap<_, _>>();
+    let head = logs
+        .iter()
+        .find(|log_entry| log_entry.commit_hash == head)?;
+    let mut reached = vec![];
+    let mut seen = HashSet::from([head.commit_hash]);
+    let mut pending = vec![head];
+    while let Some(log_entry) = pending.pop() {
+        let parents = log_entry
+            .parent_hashes
+            .iter()
+            .filter_map(|parent| by_hash.get(parent).copied())
+            .collect::<Vec<&LogEntry>>();
+        let is_head = log_entry.commit_hash == head.commit_hash;
+        match (parents.len(), log_entry.parent_hashes.len()) {
+            (listed, all) if listed == all && (all > 0 || is_head) => {}
+            (0, 1..) if !is_head && log_entry.as_tagged_version().is_some() => {
+                reached.push(log_entry.commit_hash);
+                continue;
+            }
+            // a cached root commit has the version it is computed to have
+            (0, _) if !is_head && seeds.contains_key(log_entry.commit_hash) => {
+                reached.push(log_entry.commit_hash);
+                continue;
+            }
+            _ => return None,
+        }
+        for parent in parents {
+            if seen.insert(parent.commit_hash) {
+                pending.push(parent);
+            }
+        }
+    }
+    Some(reached)
+}
+
+#[cfg(test)]
+mod history_tests {
+    use super::{Target, recent_log};
+    use crate::{
+        backend::{GitBackend as _, MemoryBackend},
+        config::CCVerConfig,
+        graph::MemoizedCommitGraph,
+        logs::{InfersVersionFormat as _, Logs},
+        version_format::VersionFormat,
+    };
+
+    fn head_version(raw: &str, version_format: &VersionFormat) -> String {
+        let logs = Logs::from_log_str(raw).unwrap();
+        let graph =
+            MemoizedCommitGraph::new(logs, version_format, &CCVerConfig::default()).unwrap();
+        let head = graph.head().unwrap();
+        let version = head.lock().unwrap().version.clone().unwrap();
+        version.to_string()
+    }
+
+    #[test]
+    fn versions_head_like_the_whole_history() {
+        let repo = MemoryBackend::new("main");
+        repo.commit("feat: initial");
+        repo.tag("v0.1.0-beta.0").unwrap();
+        repo.commit("fix: a bug");
+        repo.tag("v0.1.0").unwrap();
+        repo.branch("feature").unwrap();
+        repo.checkout("feature").unwrap();
+        repo.commit("feat: something new");
+        repo.checkout("main").unwrap();
+        repo.commit("fix: another bug");
+        repo.tag("v0.1.1").unwrap();
+        repo.merge("feature", "Merge branch 'feature'").unwrap();
+
+        let whole = repo.log(false, None).unwrap();
+        let whole_format = Logs::from_log_str(&whole).unwrap().infer_version_format();
+        let recent = recent_log(&repo, &CCVerConfig::default(), None, false, None).unwrap();
+        // the feature branch starts before v0.1.1, so the walk goes back to v0.1.0
+        assert_eq!(Logs::from_log_str(&recent.raw).unwrap().len(), 4);
+        // and the oldest tag, which implies the format, is not loaded
+        assert_eq!(recent.version_format.as_ref(), Some(&whole_format));
+        assert_eq!(
+            head_version(&recent.raw, &whole_format),
+            head_version(&whole, &whole_format)
+        );
+    }
+
+    fn target_version(repo: &MemoryBackend, target: Target) -> String {
+        let config = CCVerConfig::default();
+        let recent = recent_log(repo, &config, None, false, Some(&target.commit)).unwrap();
+        let logs = target
+            .head(&Logs::from_log_str(&recent.raw).unwrap())
+            .unwrap();
+        let version_format = logs.infer_version_format();
+        let graph = MemoizedCommitGraph::new(logs, &version_format, &config).unwrap();
+        let head = graph.head().unwrap();
+        let version = head.lock().unwrap().version.clone().unwrap();
+        version.to_string()
+    }
+
+    fn rev_version(repo: &MemoryBackend, rev: &str, branch: Option<&str>) -> String {
+        target_version(repo, Target::new(repo, Some(rev), branch).unwrap().unwrap())
+    }
+
+    #[test]
+    fn versions_revisions_on_the_branch_that_reaches_them() {
+        let repo = MemoryBackend::new("main");
+        repo.commit("feat: initial");
+        repo.tag("v0.1.0").unwrap();
+        let fix = repo.commit("fix: a bug");
+        repo.branch("feature").unwrap();
+        repo.checkout("feature").unwrap();
+        repo.commit("feat: something new");
+        repo.checkout("main").unwrap();
+        repo.commit("feat: another feature");
+
+        assert_eq!(rev_version(&repo, "v0.1.0", None), "v0.1.0");
+        assert_eq!(rev_version(&repo, &fix, None), "v0.1.1");
+        assert_eq!(rev_version(&repo, "feature", None), "v0.2.0-feature.0");
+        assert_eq!(rev_version(&repo, "feature", Some("main")), "v0.2.0");
+
+        // a detached HEAD is on the branch that reaches it, or on `HEAD`
+        repo.detach(&fix).unwrap();
+        let detached = |commit: &str| Target {
+            commit: commit.to_string(),
+            branch: None,
+        };
+        assert_eq!(repo.current_branch().unwrap(), "HEAD");
+        assert_eq!(target_version(&repo, detached(&fix)), "v0.1.1");
+        let loose = repo.commit("feat: off every branch");
+        assert_eq!(repo.head_hash().unwrap(), loose);
+        assert_eq!(target_version(&repo, detached(&loose)), "v0.2.0-HEAD.0");
+        assert_eq!(rev_version(&repo, "HEAD", Some("main")), "v0.2.0");
+    }
+
+    #[test]
+    fn versions_head_past_tags_lower_than_their_commits() {
+        let repo = MemoryBackend::new("main");
+        repo.commit("chore: root");
+        repo.commit("feat: initial");
+        repo.tag("v0.1.0").unwrap();
+        repo.branch("base").unwrap();
+        repo.checkout("base").unwrap();
+        repo.commit("fix: base");
+        repo.tag("v0.1.1").unwrap();
+        repo.commit("fix: more on the base");
+        repo.branch("feature").unwrap();
+        repo.checkout("feature").unwrap();
+        repo.commit("fix: on the base");
+        repo.checkout("main").unwrap();
+        repo.commit("feat: before the tag");
+        // computed as v0.3.0, above its tag
+        let low = repo.commit("feat: tagged low");
+        repo.tag("v0.1.2").unwrap();
+        repo.checkout("feature").unwrap();
+        repo.merge("main", "Merge branch 'main'").unwrap();
+        repo.checkout("base").unwrap();
+        repo.commit("fix: still more on the base");
+        repo.checkout("feature").unwrap();
+        repo.merge("base", "Merge branch 'base'").unwrap();
+
+        let whole = repo.log(false, None).unwrap();
+        let whole_format = Logs::from_log_str(&whole).unwrap().infer_version_format();
+        let recent = recent_log(&repo, &CCVerConfig::default(), None, false, None).unwrap();
+        // the walk stops at the low tag and at v0.1.1
+        let logs = Logs::from_log_str(&recent.raw).unwrap();
+        assert_eq!(logs.len(), 7);
+        assert!(logs.iter().any(|log_entry| log_entry.commit_hash == low));
+        // the merge of main starts from the tag whatever the tagged commit
+        // is computed to be, as it does in the whole history
+        assert_eq!(
+            head_version(&recent.raw, &whole_format),
+            head_version(&whole, &whole_format)
+        );
+        assert_eq!(head_version(&whole, &whole_format), "v0.1.2-feature.1");
+    }
+}
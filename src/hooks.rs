@@ -0,0 +1,153 @@
+use std::path::{Path, PathBuf};
+
+use eyre::*;
+use serde::Serialize;
+use tracing::{debug, info, instrument};
+
+use crate::{backend::GitBackend, config::CCVerConfig};
+
+/// First line after the shebang of every hook `ccver install` writes.
+const MARKER: &str = "# Installed by `ccver install`.";
+
+/// A hook written (or left alone) by [`install`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InstalledHook {
+    pub name: &'static str,
+    pub path: String,
+    /// `false` when the hook already had this exact content.
+    pub written: bool,
+}
+
+/// Rejects commit messages that `ccver lint` does not accept.
+fn commit_msg_hook() -> String {
+    format!(
+        "#!/bin/sh\n{}\nexec \"${{CCVER:-ccver}}\" lint \"$1\"\n",
+        MARKER
+    )
+}
+
+/// Appends a commented reminder of the subject format and which commit types
+/// bump which part of the version to messages being written in an editor.
+fn prepare_commit_msg_hook(config: &CCVerConfig) -> String {
+    let types = &config.commit_types;
+    format!(
+        concat!(
+            "#!/bin/sh\n",
+            "{}\n",
+            "case \"$2\" in\n",
+            "  message|merge|squash|commit) exit 0 ;;\n",
+            "esac\n",
+            "cat >> \"$1\" <<'EOF'\n",
+            "#\n",
+            "# <type>[(<scope>)][!]: <description>\n",
+            "#   major: {}\n",
+            "#   minor: {}\n",
+            "#   patch: {}\n",
+            "# `!` after the type or a `BREAKING CHANGE: <note>` footer is a major bump.\n",
+            "EOF\n",
+        ),
+        MARKER,
+        types.major.join(", "),
+        types.minor.join(", "),
+        types.patch.join(", "),
+    )
+}
+
+/// Writes the `commit-msg` and `prepare-commit-msg` hooks into the
+/// repository's hooks directory. Hooks that already exist with different
+/// content are only replaced with `force`; nothing is written unless every
+/// hook can be.
+#[instrument(skip(backend, config))]
+pub fn install(
+    backend: &dyn GitBackend,
+    config: &CCVerConfig,
+    force: bool,
+) -> Result<Vec<InstalledHook>> {
+    let hooks_dir = backend.hooks_dir()?;
+    let hooks = [
+        ("commit-msg", commit_msg_hook()),
+        ("prepare-commit-msg", prepare_commit_msg_hook(config)),
+    ];
+
+    let mut planned: Vec<(&'static str, PathBuf, String, bool)> = vec![];
+    for (name, content) in hooks {
+        let hook_path = hooks_dir.join(name);
+        let written = match std::fs::read_to_string(&hook_path) {
+            std::result::Result::Ok(existing) if existing == content => false,
+            std::result::Result::Ok(_) if !force => {
+                return Err(eyre!(
+                    "{} already exists; pass --force to replace it",
+                    hook_path.display()
+                ));
+            }
+            std::result::Result::Ok(_) => true,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => true,
+            Err(e) => {
+                return Err(e).wrap_err_with(|| format!("Could not read {}", hook_path.display()));
+            }
+        };
+        planned.push((name, hook_path, content, written));
+    }
+
+    std::fs::create_dir_all(&hooks_dir)?;
+    planned
+        .into_iter()
+        .map(|(name, hook_path, content, written)| {
+            if written {
+                debug!(hook = name, "Writing hook");
+                std::fs::write(&hook_path, content)
+                    .wrap_err_with(|| format!("Could not write {}", hook_path.display()))?;
+                make_executable(&hook_path)?;
+                info!("Installed {}", hook_path.display());
+            }
+            Ok(InstalledHook {
+                name,
+                path: hook_path.display().to_string(),
+                written,
+            })
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt as _;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod hooks_tests {
+    use super::install;
+    use crate::{backend::SubprocessBackend, config::CCVerConfig, test_repo::TestRepo};
+
+    #[test]
+    fn install_refuses_to_clobber_without_force() {
+        let test_repo = TestRepo::new("hooks");
+        let repo = test_repo.path();
+        let backend = SubprocessBackend::new(repo);
+        let config = CCVerConfig::default();
+
+        let installed = install(&backend, &config, false).unwrap();
+        assert!(installed.iter().all(|hook| hook.written));
+        let commit_msg = std::fs::read_to_string(repo.join(".git/hooks/commit-msg")).unwrap();
+        assert!(commit_msg.contains("lint \"$1\""));
+
+        // Re-installing identical hooks is a no-op.
+        let installed = install(&backend, &config, false).unwrap();
+        assert!(installed.iter().all(|hook| !hook.written));
+
+        std::fs::write(repo.join(".git/hooks/commit-msg"), "#!/bin/sh\nexit 0\n").unwrap();
+        assert!(install(&backend, &config, false).is_err());
+        assert_eq!(
+            std::fs::read_to_string(repo.join(".git/hooks/commit-msg")).unwrap(),
+            "#!/bin/sh\nexit 0\n"
+        );
+        assert!(install(&backend, &config, true).unwrap()[0].written);
+    }
+}
@@ -1,52 +1,229 @@
-#![feature(decl_macro, lock_value_accessors, iterator_try_collect)]
+#![feature(decl_macro)]
 
-use std::path::Path;
 pub mod args;
+pub mod backend;
+pub mod cache;
 pub mod changelog;
+pub mod changelog_format;
+pub mod check;
+pub mod config;
+pub mod explain;
 pub mod git;
 pub mod graph;
+pub mod history;
+pub mod hooks;
+pub mod lint;
 pub mod logs;
+pub mod output;
+pub mod package;
 pub mod parser;
-pub mod pattern_macros;
+pub mod tag_pattern;
+pub mod tag_plan;
+#[cfg(test)]
+mod test_repo;
 pub mod version;
 pub mod version_format;
 
-use eyre::Result;
+use backend::GitBackend;
+use config::CCVerConfig;
+use eyre::{OptionExt as _, Result};
+use history::{RecentLog, Target};
 use logs::Logs;
 use tracing::{debug, instrument};
 use version::Version;
 use version_format::VersionFormat;
 
-use crate::{
-    graph::{MemoizedCommitGraph, version::ExistingVersionExt as _},
-    logs::PeekLogEntry as _,
-};
+use crate::{graph::MemoizedCommitGraph, logs::InfersVersionFormat as _};
 
-#[instrument]
+/// The version of HEAD in the repository `backend` reads, as `ccver` prints
+/// it: a build version when the work tree is dirty. The version format is
+/// inferred from the tags when `version_format` is `None`. A detached HEAD
+/// takes the role of the branch CI says it builds, see [`Target::new`].
+/// Versions kept by earlier runs are used, but none are written; see
+/// [`version_caching`].
+#[instrument(skip(backend, config))]
+pub fn version(
+    backend: &dyn GitBackend,
+    config: &CCVerConfig,
+    version_format: Option<&VersionFormat>,
+) -> Result<Version> {
+    version_of(backend, config, version_format, false)
+}
+
+/// Like [`version()`], but keeps the versions it computes in the repository's
+/// [`VersionCache`](cache::VersionCache), as `ccver` does, so later runs
+/// load fewer commits.
+#[instrument(skip(backend, config))]
+pub fn version_caching(
+    backend: &dyn GitBackend,
+    config: &CCVerConfig,
+    version_format: Option<&VersionFormat>,
+) -> Result<Version> {
+    version_of(backend, config, version_format, true)
+}
+
+fn version_of(
+    backend: &dyn GitBackend,
+    config: &CCVerConfig,
+    version_format: Option<&VersionFormat>,
+    keep: bool,
+) -> Result<Version> {
+    let target = Target::new(backend, None, None)?;
+    let mut recent = RecentLog::default();
+    let (graph, version_format) = recent_graph(
+        backend,
+        config,
+        target.as_ref(),
+        version_format,
+        keep,
+        &mut recent,
+    )?;
+    let head = graph.head().ok_or_eyre("No Head Found")?;
+    let head = head.lock().unwrap();
+    let version = head.release_version()?;
+    debug!(%version, "Version of HEAD");
+    match backend.is_dirty()? {
+        true => Ok(version.build(&head.log_entry, &version_format)),
+        false => Ok(version),
+    }
+}
+
+/// The version HEAD would take with one more commit, `commit_message`, on
+/// top. Like [`version()`], it writes no cache; see [`peek_caching`].
+#[instrument(skip(backend, config))]
 pub fn peek(
-    repo_path: &Path,
+    backend: &dyn GitBackend,
+    config: &CCVerConfig,
+    commit_message: String,
+    version_format: &VersionFormat,
+) -> Result<Version, eyre::Error> {
+    peek_at(backend, config, commit_message, version_format, false)
+}
+
+/// Like [`peek()`], but keeps the versions it computes in the repository's
+/// [`VersionCache`](cache::VersionCache).
+#[instrument(skip(backend, config))]
+pub fn peek_caching(
+    backend: &dyn GitBackend,
+    config: &CCVerConfig,
+    commit_message: String,
+    version_format: &VersionFormat,
+) -> Result<Version, eyre::Error> {
+    peek_at(backend, config, commit_message, version_format, true)
+}
+
+fn peek_at(
+    backend: &dyn GitBackend,
+    config: &CCVerConfig,
     commit_message: String,
     version_format: &VersionFormat,
+    keep: bool,
 ) -> Result<Version, eyre::Error> {
-    let logs = Logs::from_path(repo_path)?;
-    let graph = MemoizedCommitGraph::new(logs, version_format);
-
-    let parent_commit = graph.head().unwrap().lock().unwrap().log_entry.commit_hash;
-    let branch = graph.head().unwrap().lock().unwrap().log_entry.branch;
-    let next_entry = commit_message
-        .leak()
-        .into_peek_log_entry(parent_commit, branch);
-    let next_version = graph
-        .head()
-        .unwrap()
-        .as_existing_version()
-        .map(|v| v.next_version(&next_entry, &version_format))
-        .unwrap_or_else(|| version_format.as_default_version(&next_entry));
-
-    debug!(version = %next_version, "Peek result");
+    let target = Target::new(backend, None, None)?;
+    let mut recent = RecentLog::default();
+    let (mut graph, _) = recent_graph(
+        backend,
+        config,
+        target.as_ref(),
+        Some(version_format),
+        keep,
+        &mut recent,
+    )?;
+    let (next_version, _) = graph.peek(&commit_message, version_format, config)?;
     if version_format.prerelease.is_none() {
         Ok(next_version.no_pre())
     } else {
         Ok(next_version)
     }
 }
+
+/// The graph of the history HEAD, or `target`, is versioned from, read into
+/// `recent`, with the version format it is versioned in. Keeps the versions
+/// it computes in the cache when `keep` is set.
+fn recent_graph<'a>(
+    backend: &dyn GitBackend,
+    config: &CCVerConfig,
+    target: Option<&'a Target>,
+    version_format: Option<&VersionFormat>,
+    keep: bool,
+    recent: &'a mut RecentLog,
+) -> Result<(MemoizedCommitGraph<'a>, VersionFormat)> {
+    *recent = history::recent_log(
+        backend,
+        config,
+        version_format,
+        false,
+        target.map(|target| target.commit.as_str()),
+    )?;
+    let RecentLog {
+        raw,
+        version_format: cached_format,
+        seeds,
+        cache,
+    } = recent;
+    let logs = Logs::from_log_str(raw)?;
+    let logs = match target {
+        Some(target) => target.head(&logs)?,
+        None => logs,
+    };
+    let logs = config
+        .tag_pattern
+        .logs(logs, version_format.unwrap_or(&Default::default()));
+    let version_format = version_format
+        .cloned()
+        .or(cached_format.take())
+        .unwrap_or_else(|| logs.infer_version_format());
+    let graph = MemoizedCommitGraph::with_seeds(logs, &version_format, config, seeds)?;
+    if let Some(cache) = cache.take().filter(|_| keep) {
+        cache.store(&graph, &version_format);
+    }
+    Ok((graph, version_format))
+}
+
+#[cfg(test)]
+mod lib_tests {
+    use eyre::Result;
+
+    use crate::{
+        backend::MemoryBackend,
+        config::{Bump, CCVerConfig},
+    };
+
+    #[test]
+    fn version_rejects_an_invalid_release_as_on_head() -> Result<()> {
+        let repo = MemoryBackend::new("main");
+        repo.commit("feat: initial");
+        repo.tag("v1.0.0")?;
+        repo.commit("fix: a bug\n\nRelease-As: 0.9.0");
+        let config = CCVerConfig::default();
+
+        let error = super::version(&repo, &config, None).unwrap_err();
+        assert!(error.to_string().contains("would go backwards"), "{error}");
+
+        // older commits keep warning, and HEAD continues from them
+        repo.commit("fix: another bug");
+        assert_eq!(super::version(&repo, &config, None)?.to_string(), "v1.0.2");
+        Ok(())
+    }
+
+    #[test]
+    fn peek_moves_a_forced_bump_onto_the_peeked_commit() -> Result<()> {
+        let repo = MemoryBackend::new("main");
+        repo.commit("feat: initial");
+        repo.tag("v1.0.0")?;
+        repo.commit("fix: a bug");
+        let config = CCVerConfig {
+            force_bump: Some(Bump::Major),
+            ..Default::default()
+        };
+
+        let next = super::peek(
+            &repo,
+            &config,
+            "fix: another bug".into(),
+            &Default::default(),
+        )?;
+        assert_eq!(next.to_string(), "v2.0.0");
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+use serde::Serialize;
+use std::fmt::Display;
+
+use crate::parser::parse_conventional_subject;
+
+/// Subjects git writes itself, or that will be squashed away, which are
+/// accepted without being conventional.
+const EXEMPT_PREFIXES: [&str; 5] = ["Merge ", "Revert \"", "fixup! ", "squash! ", "amend! "];
+
+/// Everything below this line is dropped by `git commit --verbose`.
+const SCISSORS: &str = "# ------------------------ >8 ------------------------";
+
+/// A problem with a commit message, located by 1-based line and column.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LintProblem {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+impl Display for LintProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\n  help: {}", suggestion)?;
+        }
+        std::fmt::Result::Ok(())
+    }
+}
+
+/// Checks a commit message as git would record it: comment lines and
+/// anything below the `--verbose` scissors line are ignored, the first
+/// remaining line must be a conventional subject and, if a body follows, it
+/// must be separated from the subject by a blank line.
+pub fn lint_message(message: &str) -> Vec<LintProblem> {
+    let mut lines = message
+        .lines()
+        .enumerate()
+        .take_while(|(_, line)| *line != SCISSORS)
+        .filter(|(_, line)| !line.starts_with('#'))
+        .map(|(idx, line)| (idx + 1, line))
+        .skip_while(|(_, line)| line.trim().is_empty());
+
+    let Some((line, subject)) = lines.next() else {
+        return vec![LintProblem {
+            line: 1,
+            column: 1,
+            message: "commit message is empty".to_string(),
+            suggestion: Some("write a subject such as `fix: <description>`".to_string()),
+        }];
+    };
+    let subject = subject.trim_end();
+    if is_exempt(subject) {
+        return vec![];
+    }
+
+    let mut problems = vec![];
+    if let Err(e) = parse_conventional_subject(subject) {
+        let column = match e.line_col {
+            pest::error::LineColLocation::Pos((_, column))
+            | pest::error::LineColLocation::Span((_, column), _) => column,
+        };
+        problems.push(LintProblem {
+            line,
+            column,
+            message: e.variant.message().to_string(),
+            suggestion: Some(suggestion(subject)),
+        });
+    }
+
+    if let Some((line, body)) = lines.next()
+        && !body.trim().is_empty()
+    {
+        problems.push(LintProblem {
+            line,
+            column: 1,
+            message: "the body must be separated from the subject by a blank line".to_string(),
+            suggestion: Some(format!("insert an empty line before line {}", line)),
+        });
+    }
+
+    problems
+}
+
+/// Whether `subject` is one git writes itself or one that will be squashed.
+pub fn is_exempt(subject: &str) -> bool {
+    EXEMPT_PREFIXES
+        .iter()
+        .any(|prefix| subject.starts_with(prefix))
+}
+
+/// Guesses a fix for a subject that failed to parse.
+fn suggestion(subject: &str) -> String {
+    let Some((tag, description)) = subject.split_once(':') else {
+        return format!(
+            "start the subject with a type and a colon, e.g. `fix: {}`",
+            subject.trim()
+        );
+    };
+    let tag = tag.trim_end();
+    let (commit_type, scope) = match tag.split_once('(') {
+        Some((commit_type, scope)) => (commit_type, Some(scope.trim_end_matches('!'))),
+        None => (tag.trim_end_matches('!'), None),
+    };
+    let scope_char = |c: char| c.is_alphanumeric() || "-_/.".contains(c);
+
+    if commit_type.is_empty() || !commit_type.chars().all(char::is_alphabetic) {
+        format!(
+            "the type may only contain letters; `{}` is not a valid type",
+            commit_type
+        )
+    } else if let Some(scope) = scope
+        && !scope.ends_with(')')
+    {
+        "close the scope with `)` before the colon".to_string()
+    } else if let Some(scope) = scope
+        && !scope.trim_end_matches(')').chars().all(scope_char)
+    {
+        "a scope may only contain letters, numbers, `-`, `_`, `/` and `.`".to_string()
+    } else if description.trim().is_empty() {
+        "add a description after the colon".to_string()
+    } else {
+        "use the form `<type>[(<scope>)][!]: <description>`".to_string()
+    }
+}
+
+#[cfg(test)]
+mod lint_tests {
+    use indoc::indoc;
+
+    use super::lint_message;
+
+    #[test]
+    fn accepts_conventional_messages() {
+        let message = indoc! {"
+            feat(lint)!: add a commit message linter
+
+            With a body.
+
+            BREAKING CHANGE: something
+            # Please enter the commit message for your changes.
+        "};
+        assert_eq!(lint_message(message), vec![]);
+        assert_eq!(lint_message("Merge branch 'main' into dev\n"), vec![]);
+    }
+
+    #[test]
+    fn reports_line_column_and_suggestion() {
+        let message = indoc! {"
+            # leading comment
+            feat(lint: add a linter
+        "};
+        let problems = lint_message(message);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].line, 2);
+        assert_eq!(problems[0].column, 5);
+        assert_eq!(
+            problems[0].suggestion.as_deref(),
+            Some("close the scope with `)` before the colon")
+        );
+
+        let problems = lint_message("Add a linter\nno blank line\n");
+        assert_eq!(problems.len(), 2);
+        assert_eq!(
+            problems[0].suggestion.as_deref(),
+            Some("start the subject with a type and a colon, e.g. `fix: Add a linter`")
+        );
+        assert_eq!((problems[1].line, problems[1].column), (2, 1));
+    }
+}
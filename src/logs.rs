@@ -1,18 +1,20 @@
+use crate::backend::GitBackend;
+use crate::config::{CCVerConfig, SubjectClass};
 use crate::parser::parse_log;
-use crate::pattern_macros::{major_subject, minor_subject, patch_subject};
 use crate::version::Version;
 use crate::version_format::VersionFormat;
 use crate::{git, parser};
 use eyre::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
 use std::ops::{Deref, DerefMut};
+use std::path::Path;
 use std::sync::Arc;
-use std::{env::current_dir, path::Path};
 use tracing::{debug, info, instrument};
 
 pub const PEEK_COMMIT_HASH: &str = "0000000000000000000000000000000000000000";
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Decoration<'a> {
     HeadIndicator(&'a str),
     Tag(Tag<'a>),
@@ -34,18 +36,45 @@ pub enum Subject<'a> {
     Text(&'a str),
 }
 
-impl Subject<'_> {
-    pub fn as_initial_version(&self, commit: &LogEntry, format: &VersionFormat) -> Version {
+impl Display for ConventionalSubject<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.commit_type)?;
+        if let Some(scope) = self.scope {
+            write!(f, "({})", scope)?;
+        }
+        if self.breaking {
+            write!(f, "!")?;
+        }
+        write!(f, ": {}", self.description)
+    }
+}
+
+impl Display for Subject<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            major_subject!() => format.as_default_version(commit).major(commit, format),
-            minor_subject!() => format.as_default_version(commit).minor(commit, format),
-            patch_subject!() => format.as_default_version(commit).patch(commit, format),
+            Subject::Conventional(subject) => write!(f, "{}", subject),
+            Subject::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+impl Subject<'_> {
+    pub fn as_initial_version(
+        &self,
+        commit: &LogEntry,
+        format: &VersionFormat,
+        config: &CCVerConfig,
+    ) -> Version {
+        match config.subject_class(self) {
+            SubjectClass::Major => format.as_default_version(commit).major(commit, format),
+            SubjectClass::Minor => format.as_default_version(commit).minor(commit, format),
+            SubjectClass::Patch => format.as_default_version(commit).patch(commit, format),
             _ => format.as_default_version(commit),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Tag<'input> {
     Text(&'input str),
     Version(Version),
@@ -56,72 +85,129 @@ pub struct LogEntry<'a> {
     pub name: &'a str,
     pub branch: &'a str,
     pub commit_hash: &'a str,
+    pub author: &'a str,
     pub commit_timezone: chrono::Utc,
     pub commit_datetime: chrono::DateTime<chrono::Utc>,
     pub parent_hashes: Arc<[&'a str]>,
     pub decorations: Arc<[Decoration<'a>]>,
     pub subject: Subject<'a>,
-    pub footers: std::collections::HashMap<&'a str, &'a str>,
+    /// The commit body without its footer paragraph.
+    pub body: &'a str,
+    pub footers: Footers<'a>,
+    /// Files the commit changed (relative to the repository root). Only
+    /// filled in when the log was read with [`GIT_CHANGED_PATHS_ARGS`].
+    pub changed_paths: Arc<[&'a str]>,
 }
 
+/// `(key, value)` pairs from the last paragraph of a commit body, in order.
+pub type Footers<'a> = Arc<[(&'a str, &'a str)]>;
+
+/// Footer keys rendered as issue references in the changelog.
+pub const REFERENCE_FOOTER_KEYS: [&str; 12] = [
+    "refs",
+    "ref",
+    "references",
+    "see-also",
+    "closes",
+    "close",
+    "closed",
+    "fixes",
+    "fixed",
+    "resolves",
+    "resolve",
+    "resolved",
+];
+
 impl LogEntry<'_> {
-    pub fn as_initial_version(&self, commit: &LogEntry, version_format: &VersionFormat) -> Version {
-        self.subject.as_initial_version(commit, version_format)
-    }
-}
-
-pub trait PeekLogEntry {
-    fn into_peek_log_entry(
-        self,
-        parent_commit: &'static str,
-        branch: &'static str,
-    ) -> LogEntry<'static>;
-}
-
-impl PeekLogEntry for &'static str {
-    fn into_peek_log_entry(
-        self,
-        parent_commit: &'static str,
-        branch: &'static str,
-    ) -> LogEntry<'static> {
-        let parsed_subject = parser::parse_subject(self).unwrap();
-
-        // For peek entries, we need to convert the subject to use the parent's lifetime
-        // Since this is a preview operation, we'll create a simplified subject
-        let subject = match parsed_subject {
-            Subject::Conventional(conv) => {
-                // Leak the strings to get 'static lifetime, then coerce to 'a
-                let commit_type: &str = Box::leak(conv.commit_type.to_string().into_boxed_str());
-                let description: &str = Box::leak(conv.description.to_string().into_boxed_str());
-                let scope: Option<&str> = conv.scope.map(|s| {
-                    let leaked: &'static str = Box::leak(s.to_string().into_boxed_str());
-                    leaked
-                });
-
-                Subject::Conventional(ConventionalSubject {
-                    commit_type,
-                    breaking: conv.breaking,
-                    scope,
-                    description,
-                })
-            }
-            Subject::Text(text) => {
-                let leaked_text: &str = Box::leak(text.to_string().into_boxed_str());
-                Subject::Text(leaked_text)
-            }
-        };
+    /// Migration notes from `BREAKING CHANGE:` / `BREAKING-CHANGE:` footers.
+    pub fn breaking_notes(&self) -> impl Iterator<Item = &str> {
+        self.footers
+            .iter()
+            .filter(|(key, _)| matches!(*key, "BREAKING CHANGE" | "BREAKING-CHANGE"))
+            .map(|(_, note)| *note)
+    }
+
+    /// A `!` after the subject's type or a breaking change footer.
+    pub fn is_breaking(&self) -> bool {
+        matches!(
+            self.subject,
+            Subject::Conventional(ConventionalSubject { breaking: true, .. })
+        ) || self.breaking_notes().next().is_some()
+    }
+
+    /// A `Graduate:` footer of `1.0.0` or `true` (e.g. `Graduate: 1.0.0`),
+    /// which ends initial development by releasing 1.0.0. Other values, such
+    /// as `Graduate: no`, do not graduate.
+    pub fn graduates(&self) -> bool {
+        self.footers.iter().any(|(key, value)| {
+            key.eq_ignore_ascii_case("graduate")
+                && matches!(
+                    value.trim().to_ascii_lowercase().as_str(),
+                    "1.0.0" | "v1.0.0" | "true" | "yes"
+                )
+        })
+    }
+
+    /// The value of a `Release-As:` footer (e.g. `Release-As: 3.0.0`), which
+    /// pins the commit's version.
+    pub fn release_as(&self) -> Option<&str> {
+        self.footers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("release-as"))
+            .map(|(_, value)| value.trim())
+    }
 
-        LogEntry {
+    /// `Refs`, `Closes`, `Fixes`, ... footers as `(key, value)` pairs.
+    pub fn references(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.footers
+            .iter()
+            .filter(|(key, _)| REFERENCE_FOOTER_KEYS.contains(&key.to_ascii_lowercase().as_str()))
+            .map(|(key, value)| (*key, *value))
+    }
+
+    pub fn as_initial_version(
+        &self,
+        commit: &LogEntry,
+        version_format: &VersionFormat,
+        config: &CCVerConfig,
+    ) -> Version {
+        match config.commit_class(self) {
+            SubjectClass::Major => version_format
+                .as_default_version(commit)
+                .major(commit, version_format),
+            _ => self
+                .subject
+                .as_initial_version(commit, version_format, config),
+        }
+    }
+}
+
+/// Turns a commit message into the entry of a commit that doesn't exist yet,
+/// borrowing from the message.
+pub trait PeekLogEntry<'a> {
+    fn into_peek_log_entry(self, parent_commit: &'a str, branch: &'a str) -> Result<LogEntry<'a>>;
+}
+
+impl<'a> PeekLogEntry<'a> for &'a str {
+    fn into_peek_log_entry(self, parent_commit: &'a str, branch: &'a str) -> Result<LogEntry<'a>> {
+        let (subject_line, body) = self.split_once('\n').unwrap_or((self, ""));
+        let subject = parser::parse_subject(subject_line.trim_end())?;
+        let (body, footers) = parser::parse_body(body)?;
+
+        Ok(LogEntry {
             name: "peek-next-commit",
             branch,
             commit_hash: PEEK_COMMIT_HASH,
+            author: "",
             commit_timezone: chrono::Utc,
             commit_datetime: chrono::Utc::now(),
             parent_hashes: vec![parent_commit].into(),
             decorations: Arc::new([Decoration::HeadIndicator(branch)]),
             subject,
-            footers: HashMap::new(),
-        }
+            body,
+            footers,
+            changed_paths: Arc::new([]),
+        })
     }
 }
 
@@ -167,10 +253,19 @@ pub const GIT_FORMAT_ARGS: [&str; 5] = [
     "--full-history",
     "--source",
     "--branches",
-    "--format=name=%n%f%nbranch=%n%S%ncommit=%n%H%ncommit-time=%n%cI%ndec=%n%d%nparent=%n%P%nsub=%n%s%ntrailers=%n%(trailers:only)%n",
+    "--format=name=%n%f%nbranch=%n%S%ncommit=%n%H%ncommit-time=%n%cI%nauthor=%n%an%ndec=%n%d%nparent=%n%P%nsub=%n%s%nbody=%n%b%x1E%n",
 ];
 
+/// Added to [`GIT_FORMAT_ARGS`] to list the files each commit changed, as
+/// needed to version packages. Merges list what they brought into their
+/// first parent.
+pub const GIT_CHANGED_PATHS_ARGS: [&str; 2] = ["--name-only", "--diff-merges=first-parent"];
+
 impl Logs<'_> {
+    /// Parses the output of `git log` with [`GIT_FORMAT_ARGS`], e.g. from
+    /// [`formatted_logs`](crate::git::formatted_logs) or [`GitBackend::log`](crate::backend::GitBackend::log).
+    /// The logs borrow from `raw`, so they, and any graph built from them,
+    /// are freed along with it.
     #[instrument(skip(raw))]
     pub fn from_log_str<'a>(raw: &'a str) -> Result<Logs<'a>> {
         debug!("Parsing logs from string ({} chars)", raw.len());
@@ -178,39 +273,123 @@ impl Logs<'_> {
         info!("Successfully parsed logs");
         Ok(logs)
     }
+}
 
+/// A raw log read from a repository, in the format [`Logs::from_log_str`]
+/// parses. The [`Logs`] parsed from it borrow it, so it has to outlive them.
+#[derive(Debug, Clone, Default)]
+pub struct LogBuffer(String);
+
+impl LogBuffer {
+    /// The log of the repository at `path`.
     #[instrument]
-    pub fn from_path(path: &Path) -> Result<Logs<'static>> {
+    pub fn from_path(path: &Path) -> Result<LogBuffer> {
         info!("Loading logs from path: {:?}", path);
-        let raw = git::formatted_logs(path)?;
-        Logs::from_log_str(raw)
+        Ok(LogBuffer(git::formatted_logs(path)?))
     }
-}
 
-impl<'a> Logs<'a> {
-    pub fn with_additional_log_entry<'b: 'a>(&self, log: LogEntry<'b>) -> Logs<'a> {
-        let mut next_logs = self.0.clone();
+    /// Like [`LogBuffer::from_path`], but with each entry's `changed_paths`.
+    #[instrument]
+    pub fn from_path_with_changed_paths(path: &Path) -> Result<LogBuffer> {
+        info!("Loading logs with changed paths from path: {:?}", path);
+        Ok(LogBuffer(git::formatted_logs_with_changed_paths(path)?))
+    }
 
-        next_logs.push(log);
+    /// The log `backend` reads, with each entry's `changed_paths` when
+    /// `changed_paths` is set.
+    #[instrument(skip(backend))]
+    pub fn from_backend(backend: &dyn GitBackend, changed_paths: bool) -> Result<LogBuffer> {
+        info!("Loading logs from {:?}", backend);
+        Ok(LogBuffer(backend.log(changed_paths, None)?))
+    }
 
-        Logs(next_logs)
+    /// The logs parsed from the buffer.
+    pub fn logs(&self) -> Result<Logs<'_>> {
+        Logs::from_log_str(&self.0)
     }
 }
 
-impl Default for Logs<'_> {
-    fn default() -> Self {
-        Logs::from_path(&current_dir().expect("could not get current dir")).unwrap()
+impl From<String> for LogBuffer {
+    fn from(raw: String) -> Self {
+        LogBuffer(raw)
     }
 }
 
-#[cfg(test)]
-mod logs_tests {
+impl<'a> Logs<'a> {
+    pub fn with_additional_log_entry<'b: 'a>(&self, log: LogEntry<'b>) -> Logs<'a> {
+        let mut next_logs = self.0.clone();
 
-    use super::*;
+        next_logs.push(log);
 
-    #[test]
-    fn test_logs_parsed() {
-        let _logs = Logs::default();
+        Logs(next_logs)
+    }
+
+    /// The logs with `commit` as HEAD, in place of the commit HEAD points
+    /// at, taking the role of `branch`. Without a `branch`, it keeps the
+    /// branch it was reached from, and a commit reached from no branch is
+    /// on `HEAD`, as git calls a detached HEAD; so are the commits only
+    /// `commit` reaches. `git log --source` attributes `commit`, and the
+    /// commits it reached first, to `commit` itself, so these take the
+    /// branch of the nearest descendant a branch reached.
+    pub fn headed_at(&self, commit: &str, branch: Option<&'a str>) -> Result<Logs<'a>> {
+        if !self.iter().any(|log_entry| log_entry.commit_hash == commit) {
+            return Err(eyre!("Commit {} is not in the history", commit));
+        }
+        let log_entries: &[LogEntry<'a>] = self;
+        let by_hash = log_entries
+            .iter()
+            .map(|log_entry| (log_entry.commit_hash, log_entry))
+            .collect::<HashMap<_, _>>();
+        let mut inherited = HashMap::new();
+        let mut queue = log_entries
+            .iter()
+            .filter(|log_entry| log_entry.branch != commit)
+            .map(|log_entry| (log_entry, log_entry.branch))
+            .collect::<VecDeque<_>>();
+        while let Some((log_entry, source)) = queue.pop_front() {
+            for parent in log_entry.parent_hashes.iter() {
+                if let Some(parent) = by_hash.get(parent)
+                    && parent.branch == commit
+                    && !inherited.contains_key(parent.commit_hash)
+                {
+                    inherited.insert(parent.commit_hash, source);
+                    queue.push_back((parent, source));
+                }
+            }
+        }
+        Ok(log_entries
+            .iter()
+            .map(|log_entry| {
+                let mut decorations = log_entry
+                    .decorations
+                    .iter()
+                    .filter_map(|decoration| match decoration {
+                        Decoration::HeadIndicator(branch) => Some(Decoration::Branch(branch)),
+                        Decoration::Branch("HEAD") => None,
+                        decoration => Some(decoration.clone()),
+                    })
+                    .collect::<Vec<_>>();
+                let is_head = log_entry.commit_hash == commit;
+                let source = match log_entry.branch == commit {
+                    true => inherited.get(log_entry.commit_hash).copied(),
+                    false => Some(log_entry.branch),
+                };
+                let branch = match (branch, source) {
+                    (Some(branch), _) if is_head => branch,
+                    (Some(branch), None) => branch,
+                    (_, Some(source)) => source,
+                    (None, None) => "HEAD",
+                };
+                if is_head {
+                    decorations.insert(0, Decoration::HeadIndicator(branch));
+                }
+                LogEntry {
+                    branch,
+                    decorations: decorations.into(),
+                    ..log_entry.clone()
+                }
+            })
+            .collect())
     }
 }
 
@@ -221,7 +400,7 @@ pub trait InfersVersionFormat {
 impl<'a> InfersVersionFormat for Logs<'a> {
     fn infer_version_format(&self) -> VersionFormat {
         let mut log_entries: Vec<_> = self.iter().collect();
-        log_entries.sort_by(|a, b| a.commit_datetime.cmp(&b.commit_datetime));
+        log_entries.sort_by_key(|a| a.commit_datetime);
 
         log_entries
             .into_iter()
@@ -236,3 +415,43 @@ impl<'a> InfersVersionFormat for Logs<'a> {
             .unwrap_or_default()
     }
 }
+
+#[cfg(test)]
+mod logs_tests {
+
+    use super::*;
+
+    #[test]
+    fn test_logs_parsed() -> Result<()> {
+        let raw = crate::git::formatted_logs(&std::env::current_dir()?)?;
+        let logs = Logs::from_log_str(&raw)?;
+        assert!(!logs.is_empty());
+        let buffer = LogBuffer::from_path(&std::env::current_dir()?)?;
+        assert_eq!(buffer.logs()?.len(), logs.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_logs_from_backend() -> Result<()> {
+        let repo = crate::backend::MemoryBackend::new("main");
+        repo.commit("feat: initial");
+        repo.commit_with_paths("fix: a bug", &["src/lib.rs"]);
+        let buffer = LogBuffer::from_backend(&repo, true)?;
+        let logs = buffer.logs()?;
+        assert_eq!(logs.len(), 2);
+        assert_eq!(&*logs[0].changed_paths, ["src/lib.rs"]);
+        Ok(())
+    }
+
+    #[test]
+    fn peek_entries_borrow_their_message() {
+        let message = String::from("feat(api)!: add\n\nRefs: #1");
+        let entry = message
+            .as_str()
+            .into_peek_log_entry(PEEK_COMMIT_HASH, "main")
+            .unwrap();
+        assert!(entry.is_breaking());
+        assert_eq!(entry.subject.to_string(), "feat(api)!: add");
+        assert_eq!(entry.references().collect::<Vec<_>>(), [("Refs", "#1")]);
+    }
+}
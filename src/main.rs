@@ -1,4 +1,4 @@
-#![feature(decl_macro, lock_value_accessors, iterator_try_collect)]
+#![feature(decl_macro, iterator_try_collect)]
 
 /// The main entry point for the `ccver` application.
 ///
@@ -18,7 +18,7 @@
 /// * The current directory cannot be determined.
 /// * The version format cannot be parsed.
 /// * The logs cannot be retrieved or processed.
-/// * The `git` command is not installed.
+/// * The repository cannot be opened with the selected git backend.
 ///
 /// # Example
 ///
@@ -26,34 +26,62 @@
 /// ccver --path /path/to/repo --format "vYY.CC.CC-pre.<short-sha>" tag
 /// ```
 pub mod args;
+pub mod backend;
+pub mod cache;
 pub mod changelog;
+pub mod changelog_format;
+pub mod check;
+pub mod config;
+pub mod explain;
 pub mod git;
 pub mod graph;
+pub mod history;
+pub mod hooks;
+pub mod lint;
 pub mod logs;
+pub mod output;
+pub mod package;
 pub mod parser;
-pub mod pattern_macros;
+pub mod tag_pattern;
+pub mod tag_plan;
+#[cfg(test)]
+mod test_repo;
 pub mod version;
 pub mod version_format;
 
+use std::collections::HashSet;
 use std::env::current_dir;
 use std::io::Read as _;
-use std::path::Path;
 use std::path::PathBuf;
 
 use crate::graph::version::TaggedVersionExt as _;
-use crate::logs::PeekLogEntry;
 use crate::version::Version;
 use crate::version_format::VersionFormat;
 use args::*;
-use ccver::git::is_dirty;
-use changelog::ChangeLogData;
+use backend::{BackendKind, GitBackend};
+use cache::VersionCache;
+use changelog::{ChangeLogData, ChangeLogRelease};
+use changelog_format::{ChangeLogFormat, ChangeLogRenderer, TagMessageRenderer, TemplateRenderer};
 use clap::Parser;
+use clap::ValueEnum as _;
+use config::{Bump, CCVerConfig};
+use explain::Explanation;
 use eyre::*;
-use git::git_installed;
-use logs::GIT_FORMAT_ARGS;
+use git::PushOutcome;
+use history::RecentLog;
 use logs::Logs;
+use logs::{GIT_CHANGED_PATHS_ARGS, GIT_FORMAT_ARGS};
+use output::{
+    CacheClearOutput, ChangeLogOutput, ChangeLogReleasesOutput, ChangeLogWriteOutput, CheckOutput,
+    CommitRef, GitFormatOutput, InstallOutput, LintOutput, PackageVersion, PackagesOutput,
+    PeekOutput, TagOutput, TagPlanOutput, TagResult, VersionOutput,
+};
+use package::Package;
+use petgraph::graph::NodeIndex;
 use petgraph::visit::DfsPostOrder;
 use petgraph::visit::Walker as _;
+use tag_pattern::TagPattern;
+use tag_plan::{PlannedTag, TagAction};
 use tracing::{Level, debug, error, info, instrument, span, warn};
 use tracing_error::ErrorLayer;
 use tracing_subscriber::Layer as _;
@@ -78,12 +106,6 @@ fn main() -> Result<()> {
     let _main_span = span!(Level::INFO, "ccver_main").entered();
     info!("Starting ccver application");
 
-    // Check git installation early
-    if let Err(e) = git_installed() {
-        error!(error = %e, "Git installation check failed");
-        return Err(e);
-    }
-
     let parsed_args = CCVerArgs::parse();
     debug!("Parsed command line arguments: {:?}", parsed_args);
 
@@ -100,12 +122,66 @@ fn main() -> Result<()> {
                         all: std::env::var("INPUT_COMMAND_TAG_ALL").unwrap_or_default() != "0"
                             && std::env::var("INPUT_COMMAND_TAG_ALL").unwrap_or_default()
                                 != "false",
+                        annotate: std::env::var("INPUT_COMMAND_TAG_ANNOTATE")
+                            .is_ok_and(|annotate| annotate == "1" || annotate == "true"),
+                        sign: std::env::var("INPUT_COMMAND_TAG_SIGN")
+                            .is_ok_and(|sign| sign == "1" || sign == "true"),
+                        dry_run: std::env::var("INPUT_COMMAND_TAG_DRY_RUN")
+                            .is_ok_and(|dry_run| dry_run == "1" || dry_run == "true"),
+                        message_template: std::env::var("INPUT_COMMAND_TAG_MESSAGE_TEMPLATE")
+                            .ok()
+                            .filter(|template| !template.is_empty()),
+                        push: std::env::var("INPUT_COMMAND_TAG_PUSH")
+                            .ok()
+                            .and_then(|push| match push.trim() {
+                                "" | "0" | "false" => None,
+                                "1" | "true" => Some("origin".to_string()),
+                                remote => Some(remote.to_string()),
+                            }),
+                    })),
+                    "changelog" => Some(CCVerSubCommand::ChangeLog(ChangeLogArgs {
+                        write: std::env::var("INPUT_COMMAND_CHANGELOG_WRITE")
+                            .ok()
+                            .filter(|write| !write.is_empty()),
+                        from: std::env::var("INPUT_COMMAND_CHANGELOG_FROM")
+                            .ok()
+                            .filter(|from| !from.is_empty()),
+                        to: std::env::var("INPUT_COMMAND_CHANGELOG_TO")
+                            .ok()
+                            .filter(|to| !to.is_empty()),
+                        all: std::env::var("INPUT_COMMAND_CHANGELOG_ALL")
+                            .is_ok_and(|all| all == "1" || all == "true"),
+                        format: std::env::var("INPUT_COMMAND_CHANGELOG_FORMAT")
+                            .ok()
+                            .filter(|format| !format.is_empty())
+                            .map(|format| ChangeLogFormat::from_str(&format, true))
+                            .transpose()
+                            .map_err(|e| eyre!("Invalid changelog format: {}", e))?,
+                        template: std::env::var("INPUT_COMMAND_CHANGELOG_TEMPLATE")
+                            .ok()
+                            .filter(|template| !template.is_empty()),
+                        tag_messages: std::env::var("INPUT_COMMAND_CHANGELOG_TAG_MESSAGES")
+                            .is_ok_and(|tag_messages| {
+                                tag_messages == "1" || tag_messages == "true"
+                            }),
                     })),
-                    "changelog" => Some(CCVerSubCommand::ChangeLog),
                     "git-format" => Some(CCVerSubCommand::GitFormat),
+                    "packages" => Some(CCVerSubCommand::Packages),
+                    "check" => Some(CCVerSubCommand::Check(CheckArgs {
+                        since: std::env::var("INPUT_COMMAND_CHECK_SINCE")
+                            .wrap_err("INPUT_COMMAND_CHECK_SINCE is required for check")?,
+                    })),
                     "peek" => Some(CCVerSubCommand::Peek(PeekArgs {
                         message: std::env::var("INPUT_COMMAND_PEEK_MESSAGE").unwrap_or_default(),
                     })),
+                    "explain" => Some(CCVerSubCommand::Explain(ExplainArgs {
+                        rev: std::env::var("INPUT_COMMAND_EXPLAIN_REV")
+                            .ok()
+                            .filter(|rev| !rev.is_empty()),
+                        depth: std::env::var("INPUT_COMMAND_EXPLAIN_DEPTH")
+                            .ok()
+                            .and_then(|depth| depth.parse().ok()),
+                    })),
                     _ => None,
                 }
             }
@@ -186,145 +262,849 @@ fn main() -> Result<()> {
         },
     };
 
-    let mut stdin_string = String::new();
+    let backend_kind = match parsed_args.git_backend {
+        Some(kind) => {
+            info!("Using git backend from args: {:?}", kind);
+            kind
+        }
+        None => match std::env::var("INPUT_GIT_BACKEND") {
+            std::result::Result::Ok(kind) if !kind.trim().is_empty() => {
+                info!("Using git backend from environment: {}", kind);
+                BackendKind::from_str(kind.trim(), true).map_err(|e| eyre!(e))?
+            }
+            _ => BackendKind::default(),
+        },
+    };
+    let backend = backend::open(backend_kind, &path).map_err(|e| {
+        error!(error = %e, "Failed to open the repository");
+        e
+    })?;
+
+    let config_path = match parsed_args.config {
+        Some(config) => {
+            info!("Using config from args: {:?}", config);
+            Some(PathBuf::from(config))
+        }
+        None => match std::env::var("INPUT_CONFIG") {
+            std::result::Result::Ok(config) if !config.is_empty() => {
+                info!("Using config from environment: {}", config);
+                Some(PathBuf::from(config))
+            }
+            _ => {
+                debug!("Searching repository root for config");
+                None
+            }
+        },
+    };
+
+    let force_bump = match (
+        parsed_args.force_major,
+        parsed_args.force_minor,
+        parsed_args.force_patch,
+    ) {
+        (true, _, _) => Some(Bump::Major),
+        (_, true, _) => Some(Bump::Minor),
+        (_, _, true) => Some(Bump::Patch),
+        _ => match std::env::var("INPUT_FORCE") {
+            std::result::Result::Ok(force) if !force.trim().is_empty() => {
+                info!("Using force from environment: {}", force);
+                Some(force.parse::<Bump>()?)
+            }
+            _ => None,
+        },
+    };
+    info!("Using force bump: {:?}", force_bump);
+
+    let graduate = parsed_args.graduate
+        || std::env::var("INPUT_GRADUATE").is_ok_and(|graduate| graduate.trim() == "true");
+    info!("Graduate to 1.0.0: {}", graduate);
+
+    let output = match parsed_args.output {
+        Some(output) => {
+            info!("Using output from args: {:?}", output);
+            output
+        }
+        None => match std::env::var("INPUT_OUTPUT") {
+            std::result::Result::Ok(output) if !output.trim().is_empty() => {
+                info!("Using output from environment: {}", output);
+                OutputFormat::from_str(output.trim(), true).map_err(|e| eyre!(e))?
+            }
+            _ => OutputFormat::default(),
+        },
+    };
+
+    let package_name = match parsed_args.package {
+        Some(package) => {
+            info!("Using package from args: {:?}", package);
+            Some(package)
+        }
+        None => match std::env::var("INPUT_PACKAGE") {
+            std::result::Result::Ok(package) if !package.trim().is_empty() => {
+                info!("Using package from environment: {}", package);
+                Some(package.trim().to_string())
+            }
+            _ => None,
+        },
+    };
+
+    let revision = match parsed_args.revision {
+        Some(revision) => {
+            info!("Using revision from args: {:?}", revision);
+            Some(revision)
+        }
+        None => match std::env::var("INPUT_REV") {
+            std::result::Result::Ok(revision) if !revision.trim().is_empty() => {
+                info!("Using revision from environment: {}", revision);
+                Some(revision.trim().to_string())
+            }
+            _ => None,
+        },
+    };
+    if revision.is_some()
+        && !matches!(
+            command,
+            None | Some(CCVerSubCommand::Peek(_)) | Some(CCVerSubCommand::ChangeLog(_))
+        )
+    {
+        return Err(eyre!(
+            "--rev is only supported by the default command, changelog and peek"
+        ));
+    }
+
+    let branch = match parsed_args.branch {
+        Some(branch) => {
+            info!("Using branch from args: {:?}", branch);
+            Some(branch)
+        }
+        None => match std::env::var("INPUT_BRANCH") {
+            std::result::Result::Ok(branch) if !branch.trim().is_empty() => {
+                info!("Using branch from environment: {}", branch);
+                Some(branch.trim().to_string())
+            }
+            _ => None,
+        },
+    };
+
+    let config = {
+        let _config_span = span!(Level::INFO, "load_config").entered();
+        let mut config = CCVerConfig::load(&path, config_path.as_deref()).map_err(|e| {
+            error!(error = %e, "Failed to load config");
+            e
+        })?;
+        config.force_bump = force_bump;
+        config.graduate = graduate;
+        config
+    };
+
+    let package = package_name
+        .as_deref()
+        .map(|name| config.package(name))
+        .transpose()?
+        .cloned();
 
-    let logs = {
+    // lint, check and install only look at their input, a commit range or
+    // the repository layout, so they run before (and without) building the
+    // full history.
+    match &command {
+        Some(CCVerSubCommand::Lint(args)) => {
+            let _lint_span = span!(Level::INFO, "lint_command").entered();
+            let message = match args.file.as_str() {
+                "-" => {
+                    let mut message = String::new();
+                    std::io::stdin().read_to_string(&mut message)?;
+                    message
+                }
+                file => std::fs::read_to_string(file)
+                    .wrap_err_with(|| format!("Could not read commit message from {}", file))?,
+            };
+            let problems = lint::lint_message(&message);
+            info!("Found {} problem(s) in commit message", problems.len());
+            let report = problems
+                .iter()
+                .map(|problem| format!("{}:{}", args.file, problem))
+                .collect::<Vec<_>>()
+                .join("\n");
+            if let OutputFormat::Json = output {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&LintOutput {
+                        valid: problems.is_empty(),
+                        problems,
+                    })?
+                );
+            }
+            return match report.is_empty() {
+                true => Ok(()),
+                false => Err(eyre!("Commit message is not conventional:\n{}", report)),
+            };
+        }
+        Some(CCVerSubCommand::Check(args)) => {
+            let _check_span = span!(Level::INFO, "check_command", since = %args.since).entered();
+            let range = format!("{}..HEAD", args.since);
+            let raw = backend.log_range(&range)?;
+            let logs = match raw.trim().is_empty() {
+                true => Logs::from_iter([]),
+                false => Logs::from_log_str(&raw)?,
+            };
+            let problems = check::check_commits(logs.iter(), &config);
+            info!(
+                "Checked {} commit(s), {} problem(s)",
+                logs.len(),
+                problems.len()
+            );
+            let report = problems
+                .iter()
+                .map(|problem| problem.to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            match output {
+                OutputFormat::Text if problems.is_empty() => {
+                    println!("{} commit(s) in {} are conventional", logs.len(), range)
+                }
+                OutputFormat::Text => {}
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&CheckOutput {
+                        checked: logs.len(),
+                        problems,
+                    })?
+                ),
+            }
+            return match report.is_empty() {
+                true => Ok(()),
+                false => Err(eyre!(
+                    "Commits in {} are not conventional:\n{}",
+                    range,
+                    report
+                )),
+            };
+        }
+        Some(CCVerSubCommand::Install(args)) => {
+            let _install_span = span!(Level::INFO, "install_command").entered();
+            let hooks = hooks::install(&*backend, &config, args.force)?;
+            match output {
+                OutputFormat::Text => hooks.iter().for_each(|hook| match hook.written {
+                    true => println!("installed {}", hook.path),
+                    false => println!("up to date {}", hook.path),
+                }),
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&InstallOutput { hooks })?
+                ),
+            }
+            return Ok(());
+        }
+        Some(CCVerSubCommand::Cache(CacheArgs {
+            command: CacheCommand::Clear,
+        })) => {
+            let _cache_span = span!(Level::INFO, "cache_clear_command").entered();
+            let cleared = cache::clear(&*backend)?;
+            info!("Cleared the version cache: {}", cleared);
+            match output {
+                OutputFormat::Text if cleared => println!("cleared the version cache"),
+                OutputFormat::Text => println!("no version cache to clear"),
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&CacheClearOutput { cleared })?
+                ),
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let explicit_format = {
+        let _format_span = span!(Level::INFO, "parse_version_format").entered();
+        format
+            .map(|format_str| {
+                info!(format = %format_str, "Parsing custom version format");
+                parser::parse_version_format(&format_str).map_err(|e| {
+                    error!(error = %e, format = %format_str, "Failed to parse version format");
+                    eyre!(e)
+                })
+            })
+            .transpose()?
+    };
+
+    // the commit versioned in place of HEAD, when it is another revision or
+    // a detached HEAD; raw logs are taken as given
+    let target = match parsed_args.raw {
+        true => None,
+        false => history::Target::new(&*backend, revision.as_deref(), branch.as_deref())?,
+    };
+    let tip = target.as_ref().map(|target| target.commit.as_str());
+    let worktree = revision.is_none();
+
+    // everything parsed from the logs borrows from this buffer
+    let mut recent = {
         let _logs_span = span!(Level::INFO, "load_logs", raw = parsed_args.raw).entered();
         if parsed_args.raw {
             info!("Reading logs from stdin");
+            let mut stdin_string = String::new();
             std::io::stdin()
                 .read_to_string(&mut stdin_string)
                 .map_err(|e| {
                     error!(error = %e, "Failed to read from stdin");
                     e
                 })?;
-            Logs::from_log_str(stdin_string.leak())?
+            RecentLog::from(stdin_string)
+        } else if package.is_none() && matches!(command, None | Some(CCVerSubCommand::Peek(_))) {
+            // the version of HEAD only depends on the history after the
+            // nearest version tags
+            info!(path = ?path, "Reading recent logs from path");
+            history::recent_log(
+                &*backend,
+                &config,
+                explicit_format.as_ref(),
+                !config.packages.is_empty(),
+                tip,
+            )?
+        } else if !config.packages.is_empty() {
+            info!(path = ?path, "Reading logs with changed paths from path");
+            RecentLog::from(backend.log(true, tip)?)
         } else {
             info!(path = ?path, "Reading logs from path");
-            Logs::from_path(&path)?
+            RecentLog::from(backend.log(false, tip)?)
         }
     };
+    let logs = Logs::from_log_str(&recent.raw)?;
+    let logs = match &target {
+        Some(target) => target.head(&logs)?,
+        None => logs,
+    };
 
     info!("Logs count: {}", logs.len());
 
-    let version_format = {
-        let _format_span = span!(Level::INFO, "parse_version_format").entered();
-        if let Some(format_str) = format {
-            info!(format = %format_str, "Parsing custom version format");
-            parser::parse_version_format(&format_str).map_err(|e| {
-                error!(error = %e, format = %format_str, "Failed to parse version format");
-                e
-            })?
-        } else {
+    if let Some(CCVerSubCommand::Packages) = &command {
+        let _packages_span = span!(Level::INFO, "packages_command").entered();
+        let packages = config
+            .packages
+            .iter()
+            .map(|package| {
+                package_version(package, &logs, explicit_format.as_ref(), &config, no_pre)
+            })
+            .try_collect::<Vec<_>>()?;
+        match output {
+            OutputFormat::Text => packages.iter().for_each(|package| match &package.tag {
+                Some(tag) => println!("{} {}", package.name, tag),
+                None => println!("{} (no commits)", package.name),
+            }),
+            OutputFormat::Json => println!(
+                "{}",
+                serde_json::to_string_pretty(&PackagesOutput { packages })?
+            ),
+        }
+        return Ok(());
+    }
+
+    let logs = match &package {
+        Some(package) => {
+            let _package_span =
+                span!(Level::INFO, "package_logs", package = %package.name).entered();
+            let logs = package.logs(
+                &logs,
+                explicit_format.as_ref().unwrap_or(&Default::default()),
+            )?;
+            info!("Package {} has {} commit(s)", package.name, logs.len());
+            logs
+        }
+        None => config.tag_pattern.logs(
+            logs,
+            explicit_format.as_ref().unwrap_or(&Default::default()),
+        ),
+    };
+
+    let version_format = explicit_format
+        .or(recent.version_format.take())
+        .unwrap_or_else(|| {
             debug!("Using default version format");
             logs.infer_version_format()
-        }
+        });
+
+    let tag_pattern = match &package {
+        Some(package) => package.tag_pattern.clone(),
+        None => config.tag_pattern.clone(),
     };
+    // versions print as the tags they are released under
+    let tag_name = |version: &Version| tag_pattern.render(version);
 
-    let graph = {
+    // a peeked commit joins the graph, so its message has to outlive it
+    let peek_message = match &command {
+        Some(CCVerSubCommand::Peek(args)) => args.message.clone(),
+        _ => String::new(),
+    };
+    let mut graph = {
         let _graph_span = span!(Level::INFO, "build_commit_graph").entered();
         info!("Building commit graph");
-        let graph = MemoizedCommitGraph::new(logs.clone(), &version_format);
+        let graph =
+            MemoizedCommitGraph::with_seeds(logs.clone(), &version_format, &config, &recent.seeds)?;
         info!(
             "Commit graph node count: {} edge count: {}",
             graph.node_count(),
             graph.edge_count()
         );
         debug!("Commit graph created successfully");
+        if let Some(cache) = recent.cache.take() {
+            cache.store(&graph, &version_format);
+        }
         graph
     };
 
     let stdout = {
         let _command_span = span!(Level::INFO, "execute_command").entered();
         match command {
-            None => format!(
-                "{}",
-                get_current_version(&graph, &path, ci, no_pre, &version_format)?
-            ),
+            None => {
+                let version =
+                    get_current_version(&graph, &*backend, worktree, ci, no_pre, &version_format)?;
+                match output {
+                    OutputFormat::Text => tag_name(&version),
+                    OutputFormat::Json => serde_json::to_string_pretty(&VersionOutput {
+                        tag: (package.is_some() || !tag_pattern.is_default())
+                            .then(|| tag_name(&version)),
+                        version,
+                        commit: CommitRef::from(
+                            &graph
+                                .head()
+                                .ok_or_eyre("No Head Found")?
+                                .lock()
+                                .unwrap()
+                                .log_entry,
+                        ),
+                    })?,
+                }
+            }
             Some(command) => match command {
                 CCVerSubCommand::Peek(args) => {
                     let _peek_span =
                         span!(Level::INFO, "peek_command", message = %args.message).entered();
-                    let parent_commit = graph.head().unwrap().lock().unwrap().log_entry.commit_hash;
-                    let branch = graph.head().unwrap().lock().unwrap().log_entry.branch;
-                    let next_entry = args
-                        .message
-                        .leak()
-                        .into_peek_log_entry(parent_commit, branch);
-                    let next_version = graph
-                        .head()
-                        .unwrap()
-                        .as_existing_version()
-                        .map(|v| v.next_version(&next_entry, &version_format))
-                        .unwrap_or_else(|| version_format.as_default_version(&next_entry));
-
-                    debug!(version = %next_version, "Peek result");
-                    if no_pre {
-                        format!("{}", next_version.no_pre())
+                    let (next_version, parent) =
+                        graph.peek(&peek_message, &version_format, &config)?;
+                    let next_version = if no_pre {
+                        next_version.no_pre()
                     } else {
-                        format!("{}", next_version)
+                        next_version
+                    };
+                    match output {
+                        OutputFormat::Text => tag_name(&next_version),
+                        OutputFormat::Json => serde_json::to_string_pretty(&PeekOutput {
+                            version: next_version,
+                            parent: CommitRef::from(&parent),
+                            message: args.message,
+                        })?,
                     }
                 }
-                CCVerSubCommand::ChangeLog => {
+                CCVerSubCommand::ChangeLog(args)
+                    if args.write.is_some()
+                        || args.all
+                        || args.from.is_some()
+                        || args.to.is_some()
+                        || args.format.is_some()
+                        || args.template.is_some()
+                        || args.tag_messages =>
+                {
+                    let _changelog_span = span!(Level::INFO, "changelog_range_command").entered();
+                    let to = match &args.to {
+                        Some(rev) => {
+                            resolve_commit(&graph, &*backend, rev, &tag_pattern, &version_format)?
+                        }
+                        None => graph
+                            .head_idx()
+                            .ok_or_eyre("Repository has no HEAD commit")?,
+                    };
+                    let from = args
+                        .from
+                        .as_deref()
+                        .map(|rev| {
+                            resolve_commit(&graph, &*backend, rev, &tag_pattern, &version_format)
+                        })
+                        .transpose()?;
+                    let mut releases = ChangeLogData::between(&graph, to, from, &config)?;
+                    info!("Changelog covers {} section(s)", releases.len());
+                    if args.tag_messages {
+                        let tag_messages = backend.tag_messages()?;
+                        for release in releases.iter_mut().filter(|release| release.released) {
+                            release.annotation =
+                                tag_messages.get(&tag_name(&release.version)).cloned();
+                        }
+                    }
+                    let renderer: Box<dyn ChangeLogRenderer> = match (&args.template, args.format) {
+                        (Some(template), _) => {
+                            Box::new(TemplateRenderer::from_path(&path.join(template))?)
+                        }
+                        (None, format) => format.unwrap_or_default().renderer(),
+                    };
+                    let renderer: Box<dyn ChangeLogRenderer> = match args.tag_messages {
+                        true => Box::new(TagMessageRenderer(renderer)),
+                        false => renderer,
+                    };
+
+                    match &args.write {
+                        Some(file) => {
+                            let file = path.join(file);
+                            releases.retain(|release| release.released);
+                            let written = changelog::write_changelog(
+                                &file,
+                                &releases,
+                                renderer.as_ref(),
+                                &tag_pattern,
+                            )
+                            .map_err(|e| {
+                                error!(error = %e, "Failed to write changelog");
+                                e
+                            })?;
+                            info!("Added {} release(s) to {}", written.len(), file.display());
+                            match output {
+                                OutputFormat::Text => written
+                                    .iter()
+                                    .map(|release| renderer.section(release))
+                                    .collect::<Result<Vec<_>>>()?
+                                    .join("\n"),
+                                OutputFormat::Json => {
+                                    serde_json::to_string_pretty(&ChangeLogWriteOutput {
+                                        path: file.display().to_string(),
+                                        releases: written,
+                                    })?
+                                }
+                            }
+                        }
+                        None => match output {
+                            OutputFormat::Text => {
+                                renderer.render(&releases.iter().collect::<Vec<_>>())?
+                            }
+                            OutputFormat::Json => {
+                                serde_json::to_string_pretty(&ChangeLogReleasesOutput {
+                                    releases: releases.iter().collect(),
+                                })?
+                            }
+                        },
+                    }
+                }
+                CCVerSubCommand::ChangeLog(_) => {
                     let _changelog_span = span!(Level::INFO, "changelog_command").entered();
                     info!("Generating changelog");
-                    let changelog = ChangeLogData::new(graph).map_err(|e| {
+                    let changelog = ChangeLogData::new(graph, &config).map_err(|e| {
                         error!(error = %e, "Failed to generate changelog");
                         e
                     })?;
                     debug!("Changelog generated successfully");
-                    format!("{}", changelog)
+                    match output {
+                        OutputFormat::Text => format!("{}", changelog),
+                        OutputFormat::Json => serde_json::to_string_pretty(&ChangeLogOutput {
+                            entries: &changelog,
+                        })?,
+                    }
+                }
+                CCVerSubCommand::Explain(args) => {
+                    let _explain_span = span!(Level::INFO, "explain_command").entered();
+                    let idx = match &args.rev {
+                        Some(rev) => {
+                            resolve_commit(&graph, &*backend, rev, &tag_pattern, &version_format)?
+                        }
+                        None => graph
+                            .head_idx()
+                            .ok_or_eyre("Repository has no HEAD commit")?,
+                    };
+                    let explanation = Explanation::new(&graph, idx, args.depth)?;
+                    match output {
+                        OutputFormat::Text => format!("{}", explanation).trim_end().to_string(),
+                        OutputFormat::Json => serde_json::to_string_pretty(&explanation)?,
+                    }
+                }
+                CCVerSubCommand::Lint(_)
+                | CCVerSubCommand::Install(_)
+                | CCVerSubCommand::Check(_) => {
+                    unreachable!("handled before loading logs")
+                }
+                CCVerSubCommand::Packages => unreachable!("handled before building the graph"),
+                CCVerSubCommand::Cache(CacheArgs {
+                    command: CacheCommand::Clear,
+                }) => unreachable!("handled before loading logs"),
+                CCVerSubCommand::Cache(CacheArgs {
+                    command: CacheCommand::Verify,
+                }) => {
+                    let _cache_span = span!(Level::INFO, "cache_verify_command").entered();
+                    if package.is_some() {
+                        return Err(eyre!("The version cache is not kept for packages"));
+                    }
+                    let Some(mut cache) = VersionCache::read(&*backend)? else {
+                        return Err(eyre!("The repository keeps no version cache"));
+                    };
+                    let key = cache::key(&version_format, &config);
+                    // the next run drops the versions the tags changed since
+                    cache.retag(&*backend, &backend.branch_tags()?)?;
+                    let report = cache::verify(&cache, &key, &graph, &version_format);
+                    info!(
+                        "Checked {} cached version(s), skipped {}, {} mismatch(es)",
+                        report.checked,
+                        report.skipped,
+                        report.mismatches.len()
+                    );
+                    if !report.mismatches.is_empty() {
+                        if let OutputFormat::Json = output {
+                            println!("{}", serde_json::to_string_pretty(&report)?);
+                        }
+                        return Err(eyre!(
+                            "{} cached version(s) differ from the whole history, run `ccver cache clear`:\n{}",
+                            report.mismatches.len(),
+                            report
+                                .mismatches
+                                .iter()
+                                .map(ToString::to_string)
+                                .collect::<Vec<_>>()
+                                .join("\n")
+                        ));
+                    }
+                    match output {
+                        OutputFormat::Text if !report.current => {
+                            "the version cache is stale and the next run starts it over".to_string()
+                        }
+                        OutputFormat::Text => format!(
+                            "{} cached version(s) match the whole history",
+                            report.checked
+                        ),
+                        OutputFormat::Json => serde_json::to_string_pretty(&report)?,
+                    }
                 }
                 CCVerSubCommand::GitFormat => {
                     let _git_format_span = span!(Level::DEBUG, "git_format_command").entered();
                     info!("Outputting git format args");
-                    format!(
-                        "{} {} {} {} {}",
-                        GIT_FORMAT_ARGS[0],
-                        GIT_FORMAT_ARGS[1],
-                        GIT_FORMAT_ARGS[2],
-                        GIT_FORMAT_ARGS[3],
-                        GIT_FORMAT_ARGS[4]
-                    )
+                    // packages need the files each commit changed
+                    let args = match config.packages.is_empty() {
+                        true => GIT_FORMAT_ARGS.to_vec(),
+                        false => [&GIT_FORMAT_ARGS[..], &GIT_CHANGED_PATHS_ARGS[..]].concat(),
+                    };
+                    match output {
+                        OutputFormat::Text => args.join(" "),
+                        OutputFormat::Json => {
+                            serde_json::to_string_pretty(&GitFormatOutput { args })?
+                        }
+                    }
                 }
                 CCVerSubCommand::Tag(args) => {
-                    if is_dirty(&path)? {
+                    if backend.is_dirty()? {
                         return Err(eyre!("Repo is dirty while tag is true"));
                     }
                     let _tag_span = span!(Level::INFO, "tag_command", all = args.all).entered();
                     info!("Tagging with all: {}", args.all);
-                    let version = get_current_version(&graph, &path, ci, no_pre, &version_format)?;
-                    if !args.all {
-                        git::tag_commit_with_version(
-                            &graph.head().unwrap().lock().unwrap().log_entry.commit_hash,
-                            &version,
-                            &path,
-                        )?;
-                        format!("{}", version)
+                    let version = get_current_version(
+                        &graph,
+                        &*backend,
+                        worktree,
+                        ci,
+                        no_pre,
+                        &version_format,
+                    )?;
+                    let annotate = args.annotate || args.sign || config.annotate_tags;
+                    let message_renderer: Option<Box<dyn ChangeLogRenderer>> = match args
+                        .message_template
+                        .as_ref()
+                        .or(config.tag_message_template.as_ref())
+                    {
+                        _ if !annotate => None,
+                        Some(template) => {
+                            Some(Box::new(TemplateRenderer::from_path(&path.join(template))?))
+                        }
+                        None => Some(ChangeLogFormat::Markdown.renderer()),
+                    };
+                    // the changelog of the release at `idx`, rendered as a tag message
+                    let annotation = |idx: NodeIndex, version: &Version| {
+                        let Some(renderer) = &message_renderer else {
+                            return Ok(None);
+                        };
+                        let release = ChangeLogRelease {
+                            released: true,
+                            version: version.clone(),
+                            ..ChangeLogData::release(&graph, idx, &config)?
+                        };
+                        Ok(Some(git::TagAnnotation {
+                            message: renderer.section(&release)?,
+                            sign: args.sign,
+                        }))
+                    };
+                    let existing = backend.tag_targets()?;
+                    let head_idx = graph.head_idx().unwrap();
+                    let candidates = if !args.all {
+                        vec![(head_idx, version)]
                     } else {
-                        let new_versions =
-                            DfsPostOrder::new(graph.base_graph(), graph.head_idx().unwrap())
-                                .iter(graph.base_graph())
-                                .map(|idx| {
-                                    let weight = graph.node_weight(idx).unwrap().lock().unwrap();
-                                    let version = weight.as_existing_version().expect(
-                                        "A version was not assigned to a node in the graph",
-                                    );
-
-                                    let tagged_version = weight.log_entry.as_tagged_version();
-                                    if tagged_version.is_none() {
-                                        let _ = git::tag_commit_with_version(
-                                            &weight.log_entry.commit_hash,
-                                            &version,
-                                            &path,
-                                        );
-                                    }
-
-                                    Ok(format!("{}", version))
+                        DfsPostOrder::new(graph.base_graph(), head_idx)
+                            .iter(graph.base_graph())
+                            .map(|idx| {
+                                let version = graph
+                                    .node_weight(idx)
+                                    .unwrap()
+                                    .as_existing_version()
+                                    .expect("A version was not assigned to a node in the graph");
+                                (idx, version)
+                            })
+                            .collect()
+                    };
+                    let mut planned = HashSet::new();
+                    let plan = candidates
+                        .into_iter()
+                        .map(|(idx, version)| {
+                            let tag = tag_name(&version);
+                            let (commit, already_tagged, line_violation, release_as_error) = {
+                                let weight = graph.node_weight(idx).unwrap().lock().unwrap();
+                                let derivation = weight.derivation.as_ref();
+                                (
+                                    CommitRef::from(&weight.log_entry),
+                                    // HEAD gets its computed tag even next to other tags
+                                    args.all && weight.log_entry.as_tagged_version().is_some(),
+                                    derivation.and_then(|d| d.line_violation.clone()),
+                                    derivation.and_then(|d| d.release_as_error.clone()),
+                                )
+                            };
+                            let action = TagAction::new(
+                                &commit.hash,
+                                already_tagged,
+                                &tag,
+                                &existing,
+                                &planned,
+                            )
+                            .within_line(line_violation.as_deref())
+                            .with_valid_release_as(release_as_error.as_deref());
+                            // parents come first, so the oldest commit keeps the tag
+                            if action == TagAction::Create {
+                                planned.insert(tag.clone());
+                            }
+                            let annotation = match (&action, args.dry_run) {
+                                (TagAction::Create, false) => annotation(idx, &version)?,
+                                _ => None,
+                            };
+                            Ok(PlannedTag {
+                                tag,
+                                version,
+                                commit,
+                                action,
+                                annotation,
+                            })
+                        })
+                        .try_collect::<Vec<_>>()?;
+                    if args.dry_run {
+                        info!("Dry run, planned {} tag(s)", plan.len());
+                        match output {
+                            OutputFormat::Text => plan
+                                .iter()
+                                .map(ToString::to_string)
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                            OutputFormat::Json => {
+                                serde_json::to_string_pretty(&TagPlanOutput { tags: plan })?
+                            }
+                        }
+                    } else {
+                        let conflicts = plan
+                            .iter()
+                            .filter_map(PlannedTag::conflict)
+                            .collect::<Vec<_>>();
+                        // all tags are created at once, or none are
+                        let batch = if conflicts.is_empty() {
+                            let new_tags = plan
+                                .iter()
+                                .filter(|planned| planned.action == TagAction::Create)
+                                .map(|planned| git::NewTag {
+                                    hash: &planned.commit.hash,
+                                    tag: &planned.tag,
+                                    annotation: planned.annotation.as_ref(),
                                 })
-                                .try_collect::<Vec<_>>()?;
-
-                        format!("{}", new_versions.join("\n"))
+                                .collect::<Vec<_>>();
+                            backend.create_tags(&new_tags)
+                        } else {
+                            Err(eyre!(
+                                "{} tag(s) can't be created, none were created:\n{}",
+                                conflicts.len(),
+                                conflicts.join("\n")
+                            ))
+                        };
+                        let batch_error = batch.as_ref().err().map(ToString::to_string);
+                        let mut tags = plan
+                            .into_iter()
+                            .map(|planned| {
+                                let error = match &planned.action {
+                                    TagAction::Create => batch_error.clone(),
+                                    TagAction::Skip => None,
+                                    TagAction::Conflict { .. }
+                                    | TagAction::OffLine { .. }
+                                    | TagAction::InvalidReleaseAs { .. } => planned.conflict(),
+                                };
+                                if let Some(error) = &error {
+                                    error!(tag = %planned.tag, commit = %planned.commit.hash, "{}", error);
+                                }
+                                TagResult {
+                                    created: planned.action == TagAction::Create
+                                        && error.is_none(),
+                                    tag: planned.tag,
+                                    version: planned.version,
+                                    commit: planned.commit,
+                                    action: planned.action,
+                                    error,
+                                    push: None,
+                                }
+                            })
+                            .collect::<Vec<_>>();
+                        let failed = match (batch, &args.push) {
+                            (Err(failed), _) => Some(failed),
+                            (std::result::Result::Ok(()), None) => None,
+                            (std::result::Result::Ok(()), Some(remote)) => {
+                                let created = tags
+                                    .iter()
+                                    .filter(|tag| tag.created)
+                                    .map(|tag| tag.tag.as_str())
+                                    .collect::<Vec<_>>();
+                                match backend.push_tags(remote, &created) {
+                                    std::result::Result::Ok(outcomes) => {
+                                        let mut rejected = vec![];
+                                        for tag in &mut tags {
+                                            tag.push = outcomes.get(&tag.tag).cloned();
+                                            let Some(PushOutcome::Rejected { reason, commit }) =
+                                                &tag.push
+                                            else {
+                                                continue;
+                                            };
+                                            let error = match commit {
+                                                Some(commit) => format!(
+                                                    "{} already has tag {} on {}",
+                                                    remote, tag.tag, commit
+                                                ),
+                                                None => format!(
+                                                    "{} rejected tag {}: {}",
+                                                    remote, tag.tag, reason
+                                                ),
+                                            };
+                                            error!(tag = %tag.tag, "{}", error);
+                                            rejected.push(error.clone());
+                                            tag.error = Some(error);
+                                        }
+                                        (!rejected.is_empty()).then(|| {
+                                            eyre!(
+                                                "{} tag(s) were created but rejected by {}:\n{}",
+                                                rejected.len(),
+                                                remote,
+                                                rejected.join("\n")
+                                            )
+                                        })
+                                    }
+                                    Err(e) => Some(e),
+                                }
+                            }
+                        };
+                        let stdout = match output {
+                            OutputFormat::Text => tags
+                                .iter()
+                                .filter(|tag| tag.created || tag.action == TagAction::Skip)
+                                .map(|tag| tag.tag.clone())
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                            OutputFormat::Json => {
+                                serde_json::to_string_pretty(&TagOutput { tags })?
+                            }
+                        };
+                        if let Some(failed) = failed {
+                            // still report what happened to each tag
+                            println!("{}", stdout);
+                            return Err(failed);
+                        }
+                        stdout
                     }
                 }
             },
@@ -337,36 +1117,112 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Resolves a version or version tag name (looked up among the version tags,
+/// then the versions ccver assigned), a tag name or any other git revision to
+/// a commit in the graph.
+#[instrument(skip(graph, backend, version_format))]
+fn resolve_commit(
+    graph: &MemoizedCommitGraph,
+    backend: &dyn GitBackend,
+    rev: &str,
+    tag_pattern: &TagPattern,
+    version_format: &VersionFormat,
+) -> Result<NodeIndex> {
+    if let Some(version) = tag_pattern
+        .parse(rev, version_format)
+        .or_else(|| parser::parse_version(rev, version_format.clone()).ok())
+        && let Some(idx) = graph.version_tag_idx(&version)
+    {
+        debug!(%version, "Resolved revision from version tag");
+        return Ok(idx);
+    }
+    if let Some(idx) = graph.text_tag_idx(rev) {
+        debug!("Resolved revision from text tag");
+        return Ok(idx);
+    }
+    if let Some((idx, _)) = graph.node_references().into_iter().find(|(_, node)| {
+        node.as_existing_version()
+            .is_some_and(|version| version.to_string() == rev)
+    }) {
+        debug!("Resolved revision from assigned version");
+        return Ok(idx);
+    }
+    let hash = backend.resolve(rev)?;
+    graph
+        .commit_idx_by_hash(&hash)
+        .ok_or_else(|| eyre!("Commit {} is not in the history ccver walked", hash))
+}
+
+/// The version at HEAD of one package for `packages`, computed the way
+/// `--package` would.
+#[instrument(skip(logs, version_format, config), fields(package = %package.name))]
+fn package_version(
+    package: &Package,
+    logs: &Logs,
+    version_format: Option<&VersionFormat>,
+    config: &CCVerConfig,
+    no_pre: bool,
+) -> Result<PackageVersion> {
+    let package_logs = match package.logs(logs, version_format.unwrap_or(&Default::default())) {
+        std::result::Result::Ok(package_logs) => package_logs,
+        Err(e) => {
+            warn!(error = %e, "Package has no version");
+            return Ok(PackageVersion {
+                name: package.name.clone(),
+                path: package.path.clone(),
+                tag: None,
+                version: None,
+                commit: None,
+            });
+        }
+    };
+    let version_format = version_format
+        .cloned()
+        .unwrap_or_else(|| package_logs.infer_version_format());
+    let graph = MemoizedCommitGraph::new(package_logs, &version_format, config)?;
+    let head = graph.head().ok_or_eyre("No Head Found")?;
+    let head = head.lock().unwrap();
+    let version = head.version.clone().map(|version| match no_pre {
+        true => version.release(&head.log_entry, &version_format),
+        false => version,
+    });
+    Ok(PackageVersion {
+        name: package.name.clone(),
+        path: package.path.clone(),
+        tag: version.as_ref().map(|version| package.tag_name(version)),
+        version,
+        commit: Some(CommitRef::from(&head.log_entry)),
+    })
+}
+
 #[instrument(skip(graph))]
 fn get_current_version(
     graph: &MemoizedCommitGraph,
-    path: &Path,
+    backend: &dyn GitBackend,
+    worktree: bool,
     ci: bool,
     no_pre: bool,
     version_format: &VersionFormat,
 ) -> Result<Version> {
     debug!("Using default command to get current version");
-    return match is_dirty(&path) {
+    let head = graph.head().ok_or_eyre("No Head Found")?;
+    debug!("Head: {:#?}", head);
+    let head = head.lock().unwrap();
+    // the worktree only dirties the version of the commit checked out
+    let dirty = match worktree {
+        true => backend.is_dirty(),
+        false => Ok(false),
+    };
+    match dirty {
         Result::Ok(dirty) => {
             if ci && dirty {
                 Err(eyre!("Repo is dirty while ci is true"))
-            } else if dirty {
-                let head = graph.head();
-                debug!("Head: {:#?}", head);
-                let head = head.unwrap().lock().unwrap();
-                let version = head
-                    .version
-                    .clone()
-                    .ok_or_eyre(eyre!("Current Branch Head Was Not Assigned a Version"));
-                version.map(|v| v.build(&head.log_entry, &version_format))
             } else {
-                let head = graph.head().ok_or_eyre("No Head Found")?;
-                let head = head.lock().unwrap();
-                let version = head
-                    .version
-                    .clone()
-                    .ok_or_eyre("Current Branch Head Was Not Assigned a Version")?;
-                Ok(version)
+                let version = head.release_version();
+                match dirty {
+                    true => version.map(|v| v.build(&head.log_entry, version_format)),
+                    false => version,
+                }
             }
         }
         Err(e) => Err(e),
@@ -374,10 +1230,7 @@ fn get_current_version(
     .map(|v| {
         info!("Version: {:?}", v);
         if no_pre {
-            v.release(
-                &graph.head().unwrap().lock().unwrap().log_entry,
-                &version_format,
-            )
+            v.release(&head.log_entry, version_format)
         } else {
             v
         }
@@ -385,5 +1238,5 @@ fn get_current_version(
     .map_err(|e| {
         error!(error = %e, "Failed to get current version");
         e
-    });
+    })
 }
@@ -0,0 +1,143 @@
+use serde::Serialize;
+
+use crate::{
+    changelog::{ChangeLogData, ChangeLogRelease},
+    check::CheckProblem,
+    git::PushOutcome,
+    hooks::InstalledHook,
+    lint::LintProblem,
+    logs::LogEntry,
+    tag_plan::{PlannedTag, TagAction},
+    version::Version,
+};
+
+/// `{"hash", "branch"}` identifying the commit a result belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommitRef {
+    pub hash: String,
+    pub branch: String,
+}
+
+impl From<&LogEntry<'_>> for CommitRef {
+    fn from(log_entry: &LogEntry<'_>) -> Self {
+        CommitRef {
+            hash: log_entry.commit_hash.to_string(),
+            branch: log_entry.branch.to_string(),
+        }
+    }
+}
+
+/// Output of the default command.
+#[derive(Debug, Serialize)]
+pub struct VersionOutput {
+    pub version: Version,
+    pub commit: CommitRef,
+    /// The tag name the version gets, only set with `--package`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+/// Output of `peek`. `parent` is the HEAD the message was peeked on top of.
+#[derive(Debug, Serialize)]
+pub struct PeekOutput {
+    pub version: Version,
+    pub parent: CommitRef,
+    pub message: String,
+}
+
+/// Output of `tag`, one entry per commit considered.
+#[derive(Debug, Serialize)]
+pub struct TagOutput {
+    pub tags: Vec<TagResult>,
+}
+
+/// Output of `tag --dry-run`.
+#[derive(Debug, Serialize)]
+pub struct TagPlanOutput {
+    pub tags: Vec<PlannedTag>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagResult {
+    pub tag: String,
+    pub version: Version,
+    pub commit: CommitRef,
+    /// What the plan called for; see [`TagAction`].
+    pub action: TagAction,
+    /// `true` only for tags this run created.
+    pub created: bool,
+    /// Why a planned tag could not be created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Only set with `--push`, for the tags this run created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub push: Option<PushOutcome>,
+}
+
+/// Output of `changelog`.
+#[derive(Debug, Serialize)]
+pub struct ChangeLogOutput<'a> {
+    pub entries: &'a ChangeLogData,
+}
+
+/// Output of `changelog --from/--to/--all`, one section per release.
+#[derive(Debug, Serialize)]
+pub struct ChangeLogReleasesOutput<'a> {
+    pub releases: Vec<&'a ChangeLogRelease>,
+}
+
+/// Output of `changelog --write`, listing only the sections that were added.
+#[derive(Debug, Serialize)]
+pub struct ChangeLogWriteOutput<'a> {
+    pub path: String,
+    pub releases: Vec<&'a ChangeLogRelease>,
+}
+
+/// Output of `git-format`.
+#[derive(Debug, Serialize)]
+pub struct GitFormatOutput {
+    pub args: Vec<&'static str>,
+}
+
+/// Output of `lint`.
+#[derive(Debug, Serialize)]
+pub struct LintOutput {
+    pub valid: bool,
+    pub problems: Vec<LintProblem>,
+}
+
+/// Output of `check`.
+#[derive(Debug, Serialize)]
+pub struct CheckOutput {
+    pub checked: usize,
+    pub problems: Vec<CheckProblem>,
+}
+
+/// Output of `packages`.
+#[derive(Debug, Serialize)]
+pub struct PackagesOutput {
+    pub packages: Vec<PackageVersion>,
+}
+
+/// A package with the version at HEAD. `tag`, `version` and `commit` are
+/// `null` when no commit reachable from HEAD touches the package.
+#[derive(Debug, Serialize)]
+pub struct PackageVersion {
+    pub name: String,
+    pub path: String,
+    pub tag: Option<String>,
+    pub version: Option<Version>,
+    pub commit: Option<CommitRef>,
+}
+
+/// Output of `cache clear`.
+#[derive(Debug, Serialize)]
+pub struct CacheClearOutput {
+    pub cleared: bool,
+}
+
+/// Output of `install`.
+#[derive(Debug, Serialize)]
+pub struct InstallOutput {
+    pub hooks: Vec<InstalledHook>,
+}
@@ -0,0 +1,308 @@
+use std::collections::{HashMap, HashSet};
+
+use eyre::*;
+use serde::Serialize;
+use tracing::{debug, instrument};
+
+use crate::{
+    logs::{Decoration, LogEntry, Logs},
+    tag_pattern::TagPattern,
+    version::Version,
+    version_format::VersionFormat,
+};
+
+/// A directory of the repository that is versioned on its own.
+///
+/// ```toml
+/// [packages.api]
+/// path = "services/api"
+/// tag_prefix = "api@"   # defaults to "api-", giving tags like `api-v1.2.3`
+/// ```
+///
+/// The prefix goes in front of the repository's `[tags] pattern`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Package {
+    pub name: String,
+    /// Path prefix relative to the repository root, without a trailing `/`.
+    pub path: String,
+    /// Put in front of the tag pattern to name the package's tags.
+    pub tag_prefix: String,
+    /// The repository's tag pattern with `tag_prefix` applied.
+    pub tag_pattern: TagPattern,
+}
+
+impl Package {
+    pub fn new(name: &str, path: &str, tag_prefix: Option<&str>, tag_pattern: &TagPattern) -> Self {
+        let tag_prefix = tag_prefix
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{}-", name));
+        Package {
+            name: name.to_string(),
+            path: path
+                .trim_start_matches("./")
+                .trim_end_matches('/')
+                .to_string(),
+            tag_pattern: tag_pattern.prefixed(&tag_prefix),
+            tag_prefix,
+        }
+    }
+
+    /// Whether `file` lies within the package.
+    pub fn contains(&self, file: &str) -> bool {
+        self.path.is_empty()
+            || file
+                .strip_prefix(self.path.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    pub fn touches(&self, log_entry: &LogEntry) -> bool {
+        log_entry
+            .changed_paths
+            .iter()
+            .any(|file| self.contains(file))
+    }
+
+    pub fn tag_name(&self, version: &Version) -> String {
+        self.tag_pattern.render(version)
+    }
+
+    /// The package's own history: the commits that touch its files, each
+    /// pointing at its nearest such ancestors instead of its git parents.
+    /// Tags are read through the package's tag pattern, so the package's
+    /// tags become version tags and every other version tag is dropped.
+    /// When HEAD does not touch the package, the HEAD marker moves to the
+    /// nearest package commit along its first parents.
+    ///
+    /// `logs` must have been read with changed paths.
+    #[instrument(skip(self, logs, version_format), fields(package = %self.name))]
+    pub fn logs<'a>(
+        &self,
+        logs: &[LogEntry<'a>],
+        version_format: &VersionFormat,
+    ) -> Result<Logs<'a>> {
+        let by_hash: HashMap<&str, &LogEntry<'a>> = logs
+            .iter()
+            .map(|entry| (entry.commit_hash, entry))
+            .collect();
+        let kept: HashSet<&str> = logs
+            .iter()
+            .filter(|entry| self.touches(entry))
+            .map(|entry| entry.commit_hash)
+            .collect();
+        debug!(
+            "{} of {} commits touch {}",
+            kept.len(),
+            logs.len(),
+            self.path
+        );
+        if kept.is_empty() {
+            return Err(eyre!(
+                "No commits touch package `{}` ({})",
+                self.name,
+                self.path
+            ));
+        }
+
+        let head = logs
+            .iter()
+            .find_map(|entry| {
+                entry.decorations.iter().find_map(|d| match d {
+                    Decoration::HeadIndicator(branch) => Some((entry.commit_hash, *branch)),
+                    _ => None,
+                })
+            })
+            .ok_or_eyre("Repository has no HEAD commit")?;
+        let head_target = std::iter::successors(Some(head.0), |hash| {
+            by_hash.get(hash)?.parent_hashes.first().copied()
+        })
+        .find(|hash| kept.contains(hash))
+        .ok_or_else(|| {
+            eyre!(
+                "No commit reachable from HEAD touches package `{}` ({})",
+                self.name,
+                self.path
+            )
+        })?;
+
+        let package_logs = logs
+            .iter()
+            .filter(|entry| kept.contains(entry.commit_hash))
+            .map(|entry| {
+                let mut parent_hashes: Vec<&'a str> = vec![];
+                for parent in nearest_kept(&entry.parent_hashes, &by_hash, &kept) {
+                    if !parent_hashes.contains(&parent) {
+                        parent_hashes.push(parent);
+                    }
+                }
+                let mut decorations: Vec<Decoration<'a>> = entry
+                    .decorations
+                    .iter()
+                    .filter_map(|decoration| match decoration {
+                        Decoration::Tag(tag) => self
+                            .tag_pattern
+                            .retag(tag, version_format)
+                            .map(Decoration::Tag),
+                        other => Some(other.clone()),
+                    })
+                    .collect();
+                if entry.commit_hash == head_target && entry.commit_hash != head.0 {
+                    decorations.push(Decoration::HeadIndicator(head.1));
+                }
+                LogEntry {
+                    parent_hashes: parent_hashes.into(),
+                    decorations: decorations.into(),
+                    ..entry.clone()
+                }
+            })
+            .collect();
+        Ok(package_logs)
+    }
+}
+
+/// The kept commits first reached from `parents` when walking through
+/// commits that are not kept, in the order the parents are listed.
+fn nearest_kept<'a>(
+    parents: &[&'a str],
+    by_hash: &HashMap<&str, &LogEntry<'a>>,
+    kept: &HashSet<&str>,
+) -> Vec<&'a str> {
+    let mut found = vec![];
+    let mut seen = HashSet::new();
+    let mut stack: Vec<&'a str> = parents.iter().rev().copied().collect();
+    while let Some(hash) = stack.pop() {
+        if !seen.insert(hash) {
+            continue;
+        }
+        if kept.contains(hash) {
+            found.push(hash);
+        } else if let Some(entry) = by_hash.get(hash) {
+            stack.extend(entry.parent_hashes.iter().rev());
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod package_tests {
+    use indoc::indoc;
+
+    use super::Package;
+    use crate::{
+        config::CCVerConfig,
+        graph::{MemoizedCommitGraph, head::HasHead},
+        logs::{InfersVersionFormat as _, Logs},
+        version_format::VersionFormat,
+    };
+
+    const LOG: &str = indoc! {"
+        name=
+        docs
+        branch=
+        main
+        commit=
+        4444444444444444444444444444444444444444
+        commit-time=
+        2024-01-04T00:00:00Z
+        author=
+        dev
+        dec=
+         (HEAD -> main)
+        parent=
+        3333333333333333333333333333333333333333
+        sub=
+        feat(web): new page
+        body=
+        \u{1E}
+
+        web/index.html
+        name=
+        api
+        branch=
+        main
+        commit=
+        3333333333333333333333333333333333333333
+        commit-time=
+        2024-01-03T00:00:00Z
+        author=
+        dev
+        dec=
+
+        parent=
+        2222222222222222222222222222222222222222
+        sub=
+        fix(api): handle errors
+        body=
+        \u{1E}
+
+        services/api/src/main.rs
+        name=
+        root
+        branch=
+        main
+        commit=
+        2222222222222222222222222222222222222222
+        commit-time=
+        2024-01-02T00:00:00Z
+        author=
+        dev
+        dec=
+         (tag: api@1.4.0, tag: v0.3.0)
+        parent=
+        1111111111111111111111111111111111111111
+        sub=
+        feat: both
+        body=
+        \u{1E}
+
+        services/api/Cargo.toml
+        web/Cargo.toml
+        name=
+        init
+        branch=
+        main
+        commit=
+        1111111111111111111111111111111111111111
+        commit-time=
+        2024-01-01T00:00:00Z
+        author=
+        dev
+        dec=
+
+        parent=
+
+        sub=
+        chore: init
+        body=
+        \u{1E}
+
+        README.md
+    "};
+
+    #[test]
+    fn versions_only_the_package_history() {
+        let logs = Logs::from_log_str(LOG).unwrap();
+        assert_eq!(&logs[0].changed_paths[..], ["web/index.html"]);
+
+        let api = Package::new("api", "./services/api/", Some("api@"), &Default::default());
+        let format = VersionFormat::default();
+        let api_logs = api.logs(&logs, &format).unwrap();
+        assert_eq!(api_logs.len(), 2);
+        assert!(api_logs[1].parent_hashes.is_empty());
+
+        let format = api_logs.infer_version_format();
+        let graph = MemoizedCommitGraph::new(api_logs, &format, &CCVerConfig::default()).unwrap();
+        let head = graph.head().unwrap().lock().unwrap();
+        assert_eq!(head.log_entry.commit_hash, "3".repeat(40));
+        let version = head.version.clone().unwrap();
+        assert_eq!(version.to_string(), "1.4.1");
+        assert_eq!(api.tag_name(&version), "api@1.4.1");
+
+        let web = Package::new("web", "web", None, &Default::default());
+        assert_eq!(web.tag_name(&version), "web-1.4.1");
+        assert!(
+            Package::new("docs", "docs", None, &Default::default())
+                .logs(&logs, &format)
+                .is_err()
+        );
+    }
+}
@@ -1,10 +1,11 @@
-use std::collections::HashMap;
+#![allow(clippy::result_large_err)]
+
 use std::sync::Arc;
 
-use pest_consume::{Node as PestNode, *};
+use pest_consume::{Node as PestNode, Parser as _, *};
 
-use crate::logs::{ConventionalSubject, Decoration, LogEntry, Subject, Tag};
-use crate::version::{PreTag, VersionNumber};
+use crate::logs::{ConventionalSubject, Decoration, Footers, LogEntry, Subject, Tag};
+use crate::version::{BuildKind, Identifier};
 use crate::version_format::CalVerFormat;
 use crate::version_format::{
     CalVerFormatSegment::{self, *},
@@ -32,104 +33,54 @@ pub type Node<'input> = PestNode<'input, Rule, ParserInputs>;
 
 pub type InterpreterResult<T> = eyre::Result<T, pest_consume::Error<Rule>>;
 
-macro pre_format($input:expr) {{
-    let pre_format = log_parsing_context!($input)
-        .unwrap_or_default()
-        .prerelease
-        .unwrap_or_default();
-    pre_format.version_format()
-}}
-
 #[pest_consume::parser]
 impl Parser {
     pub fn CCVER_VERSION(input: Node) -> InterpreterResult<Version> {
         let parser_input = log_parsing_context!(input).unwrap_or_default();
+        let number = |format: &VersionNumberFormat, data: &str| {
+            format.parse(data).ok_or_else(|| {
+                input.error(format!("`{}` is not a `{}` version number", data, format))
+            })
+        };
         match_nodes!(input.children();
-            [V_PREFIX(v_prefix), VERSION_NUMBER(major), VERSION_NUMBER(minor), VERSION_NUMBER(patch)] => Ok(Version {
+            [V_PREFIX(v_prefix), VERSION_NUMBER(major), VERSION_NUMBER(minor), VERSION_NUMBER(patch), PRERELEASE(prerelease), BUILD_METADATA(build)] => Ok(Version {
                 v_prefix,
-                major: parser_input.major.parse(major),
-                minor: parser_input.minor.parse(minor),
-                patch: parser_input.patch.parse(patch),
-                prerelease: None
-            }),
-            [V_PREFIX(v_prefix), VERSION_NUMBER(major), VERSION_NUMBER(minor), VERSION_NUMBER(patch), PRE_TAG(pretag)] => Ok(Version {
-                v_prefix,
-                major: parser_input.major.parse(major),
-                minor: parser_input.minor.parse(minor),
-                patch: parser_input.patch.parse(patch),
-                prerelease: Some(pretag)
+                major: number(&parser_input.major, major)?,
+                minor: number(&parser_input.minor, minor)?,
+                patch: number(&parser_input.patch, patch)?,
+                build_kind: build_kind(parser_input.prerelease.as_ref(), &prerelease, &build),
+                prerelease,
+                build
             })
         )
     }
 
-    pub fn PRE_TAG<'a>(input: Node<'a>) -> InterpreterResult<PreTag> {
-        match_nodes!(input.children();
-            [SHA_PRE_TAG(s)] => Ok(s),
-            [RC_PRE_TAG(r)] => Ok(r),
-            [BETA_PRE_TAG(b)] => Ok(b),
-            [ALPHA_PRE_TAG(a)] => Ok(a),
-            [BUILD_PRE_TAG(b)] => Ok(b),
-            [NAMED_PRE_TAG(n)] => Ok(n)
-        )
-    }
-
-    pub fn SHA_PRE_TAG<'a>(input: Node<'a>) -> InterpreterResult<PreTag> {
-        match_nodes!(input.children();
-            [SHA(s)] => Ok(PreTag::Sha(VersionNumber::Sha(s.to_string()))),
-            [SHORT_SHA(s)] => Ok(PreTag::ShortSha(VersionNumber::ShortSha(s.to_string())))
+    pub fn EXACT_VERSION(input: Node) -> InterpreterResult<Version> {
+        match_nodes!(input.into_children();
+            [CCVER_VERSION(version), EOI(_)] => Ok(version)
         )
     }
 
-    pub fn SHORT_SHA<'a>(input: Node<'a>) -> InterpreterResult<&'a str> {
-        Ok(input.as_str())
-    }
-
     pub fn VERSION_NUMBER<'a>(input: Node<'a>) -> InterpreterResult<&'a str> {
         Ok(input.as_str())
     }
 
-    pub fn RC_PRE_TAG<'a>(input: Node<'a>) -> InterpreterResult<PreTag> {
-        let pre_format = pre_format!(input);
-
-        match_nodes!(input.children();
-            [VERSION_NUMBER(v)] => Ok(PreTag::Rc(pre_format.parse(v)))
-        )
-    }
-
-    pub fn BETA_PRE_TAG<'a>(input: Node<'a>) -> InterpreterResult<PreTag> {
-        let pre_format = pre_format!(input);
+    pub fn PRERELEASE(input: Node) -> InterpreterResult<Vec<Identifier>> {
         match_nodes!(input.children();
-            [VERSION_NUMBER(v)] => Ok(PreTag::Beta(pre_format.parse(v)))
+            [IDENTIFIER(identifiers)..] => identifiers
+                .map(|identifier| Identifier::parse(identifier).map_err(|e| input.error(e)))
+                .collect()
         )
     }
 
-    pub fn ALPHA_PRE_TAG<'a>(input: Node<'a>) -> InterpreterResult<PreTag> {
-        let pre_format = pre_format!(input);
-
-        match_nodes!(input.children();
-            [VERSION_NUMBER(v)] => Ok(PreTag::Alpha(pre_format.parse(v)))
-        )
-    }
-
-    pub fn BUILD_PRE_TAG<'a>(input: Node<'a>) -> InterpreterResult<PreTag> {
-        let pre_format = pre_format!(input);
-
-        match_nodes!(input.children();
-            [VERSION_NUMBER(v)] => Ok(PreTag::Build(pre_format.parse(v)))
+    pub fn BUILD_METADATA(input: Node) -> InterpreterResult<Vec<String>> {
+        match_nodes!(input.into_children();
+            [IDENTIFIER(identifiers)..] => Ok(identifiers.map(str::to_string).collect())
         )
     }
 
-    pub fn NAMED_PRE_TAG<'a>(input: Node<'a>) -> InterpreterResult<PreTag> {
-        let pre_format = pre_format!(input);
-
-        match_nodes!(input.children();
-            [NAME(n), VERSION_NUMBER(v)] => Ok(
-                PreTag::Named(
-                    n.to_string(),
-                    pre_format.parse(v)
-                )
-            )
-        )
+    pub fn IDENTIFIER<'a>(input: Node<'a>) -> InterpreterResult<&'a str> {
+        Ok(input.as_str())
     }
 
     pub fn NAME<'a>(input: Node<'a>) -> InterpreterResult<&'a str> {
@@ -143,23 +94,27 @@ impl Parser {
                 SCOPE(branch),
                 COMMIT_HASHLINE(commit_hash),
                 ISO8601_DATE(commit_datetime),
+                AUTHOR(author),
                 DECORATIONS_LINE(decorations),
                 PARENT_HASHLINE(parents),
                 SUBJECT(subject),
-                FOOTER_SECTION(footers),
-
+                COMMIT_BODY((body, footers)),
+                CHANGED_PATHS(changed_paths),
             ] => {
                 Ok(
                    LogEntry {
                         name,
                         branch,
                         commit_hash,
+                        author,
                         commit_datetime,
                         commit_timezone: commit_datetime.timezone(),
                         parent_hashes: parents,
+                        body,
                         footers,
                         decorations,
-                        subject
+                        subject,
+                        changed_paths
                     }
                 )
             },
@@ -168,28 +123,36 @@ impl Parser {
                 SCOPE(branch),
                 COMMIT_HASHLINE(commit_hash),
                 ISO8601_DATE(commit_datetime),
+                AUTHOR(author),
                 PARENT_HASHLINE(parents),
                 SUBJECT(subject),
-                FOOTER_SECTION(footers),
-
+                COMMIT_BODY((body, footers)),
+                CHANGED_PATHS(changed_paths),
             ] => {
                 Ok(
                     LogEntry {
                         name,
                         branch,
                         commit_hash,
+                        author,
                         commit_datetime,
                         commit_timezone: commit_datetime.timezone(),
                         parent_hashes: parents,
+                        body,
                         footers,
                         decorations: Arc::new([]),
-                        subject
+                        subject,
+                        changed_paths
                     }
                 )
             }
         )
     }
 
+    pub fn AUTHOR<'a>(input: Node<'a>) -> InterpreterResult<&'a str> {
+        Ok(input.as_str().trim())
+    }
+
     pub fn ISO8601_DATE(input: Node) -> InterpreterResult<chrono::DateTime<chrono::Utc>> {
         Ok(chrono::DateTime::parse_from_rfc3339(input.as_str())
             .unwrap()
@@ -241,19 +204,45 @@ impl Parser {
 
     pub fn FOOTER<'a>(input: Node<'a>) -> InterpreterResult<(&'a str, &'a str)> {
         match_nodes!(input.children();
-            [SCOPE(k),DESCRIPTION(v)] => Ok((k, v))
+            [FOOTER_KEY(k), FOOTER_VALUE(v)] => Ok((k, v))
         )
     }
 
-    pub fn FOOTER_SECTION<'a>(input: Node<'a>) -> InterpreterResult<HashMap<&'a str, &'a str>> {
-        if input.children().count() == 0 {
-            return Ok(HashMap::new());
-        } else {
-            Ok(input
-                .children()
-                .map(Parser::FOOTER)
-                .map(|f| f.unwrap())
-                .collect())
+    pub fn FOOTER_KEY<'a>(input: Node<'a>) -> InterpreterResult<&'a str> {
+        Ok(input.as_str())
+    }
+
+    pub fn FOOTER_VALUE<'a>(input: Node<'a>) -> InterpreterResult<&'a str> {
+        Ok(input.as_str().trim())
+    }
+
+    pub fn FOOTER_SECTION<'a>(input: Node<'a>) -> InterpreterResult<Footers<'a>> {
+        match_nodes!(input.children();
+            [FOOTER(footers)..] => Ok(footers.collect())
+        )
+    }
+
+    pub fn FOOTER_PARAGRAPH<'a>(input: Node<'a>) -> InterpreterResult<Footers<'a>> {
+        match_nodes!(input.children();
+            [FOOTER(footers).., EOI(_)] => Ok(footers.collect())
+        )
+    }
+
+    /// Splits a commit body into its prose and, when its last paragraph
+    /// consists only of `Key: value` / `Key #value` lines, its footers.
+    pub fn COMMIT_BODY<'a>(input: Node<'a>) -> InterpreterResult<(&'a str, Footers<'a>)> {
+        let body = input.as_str().trim();
+        let (prose, last) = match body.rsplit_once("\n\n") {
+            Some((prose, last)) => (prose.trim_end(), last),
+            None => ("", body),
+        };
+        let footers =
+            Parser::parse_with_userdata(Rule::FOOTER_PARAGRAPH, last, input.user_data().clone())
+                .and_then(|parsed| parsed.single())
+                .and_then(Parser::FOOTER_PARAGRAPH);
+        match footers {
+            Ok(footers) => Ok((prose, footers)),
+            Err(_) => Ok((body, Arc::new([]))),
         }
     }
 
@@ -273,6 +262,18 @@ impl Parser {
         )
     }
 
+    /// Files listed after a commit by `git log --name-only`; empty when the
+    /// log was written without it.
+    pub fn CHANGED_PATHS<'a>(input: Node<'a>) -> InterpreterResult<Arc<[&'a str]>> {
+        match_nodes!(input.children();
+            [CHANGED_PATH(p)..] => Ok(p.collect())
+        )
+    }
+
+    pub fn CHANGED_PATH<'a>(input: Node<'a>) -> InterpreterResult<&'a str> {
+        Ok(input.as_str().trim_end())
+    }
+
     pub fn CONVENTIONAL_SUBJECT<'a>(input: Node<'a>) -> InterpreterResult<ConventionalSubject<'a>> {
         match_nodes!(input.children();
             [TAG((commit_type, scope, breaking)), DESCRIPTION(description)] => Ok(
@@ -286,6 +287,14 @@ impl Parser {
         )
     }
 
+    pub fn COMMIT_MESSAGE_SUBJECT<'a>(
+        input: Node<'a>,
+    ) -> InterpreterResult<ConventionalSubject<'a>> {
+        match_nodes!(input.children();
+            [CONVENTIONAL_SUBJECT(subject), EOI(_)] => Ok(subject)
+        )
+    }
+
     pub fn SUBJECT<'a>(input: Node<'a>) -> InterpreterResult<Subject<'a>> {
         match_nodes!(input.children();
             [CONVENTIONAL_SUBJECT(s)] => Ok(Subject::Conventional(s)),
@@ -301,11 +310,15 @@ impl Parser {
 
     pub fn TAG_DEC<'a>(input: Node<'a>) -> InterpreterResult<Tag<'a>> {
         match_nodes!(input.children();
-            [SCOPE(s)] => Ok(Tag::Text(s)),
+            [TAG_NAME(s)] => Ok(Tag::Text(s)),
             [CCVER_VERSION(v)] => Ok(Tag::Version(v))
         )
     }
 
+    pub fn TAG_NAME<'a>(input: Node<'a>) -> InterpreterResult<&'a str> {
+        Ok(input.as_str())
+    }
+
     pub fn BRANCH_DEC<'a>(input: Node<'a>) -> InterpreterResult<&'a str> {
         match_nodes!(input.children();
             [SCOPE(s)] => Ok(s)
@@ -509,3 +522,19 @@ impl Parser {
         Ok(())
     }
 }
+
+/// The build metadata of a version is a commit hash only when the format it
+/// is read with writes one there.
+fn build_kind(
+    format: Option<&PreTagFormat>,
+    prerelease: &[Identifier],
+    build: &[String],
+) -> BuildKind {
+    let is_hash =
+        |hash: &String, len| hash.len() == len && hash.bytes().all(|b| b.is_ascii_hexdigit());
+    match (format, prerelease, build) {
+        (Some(Sha), [], [hash]) if is_hash(hash, 40) => BuildKind::Sha,
+        (Some(ShortSha), [], [hash]) if is_hash(hash, 7) => BuildKind::ShortSha,
+        _ => BuildKind::Metadata,
+    }
+}
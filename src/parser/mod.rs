@@ -1,7 +1,7 @@
 use core::str;
 use interpreter::InterpreterResult;
 
-use crate::logs::Subject;
+use crate::logs::{ConventionalSubject, Footers, Subject};
 use crate::{logs::Logs, version::Version, version_format::VersionFormat};
 
 #[cfg(test)]
@@ -31,7 +31,38 @@ pub fn parse_version(version: &str, format: VersionFormat) -> InterpreterResult<
     cc_parse_with_data!(CCVER_VERSION, version, format)
 }
 
+/// Like [`parse_version`], but rejects anything after the version.
+#[allow(clippy::result_large_err)]
+pub fn parse_exact_version(version: &str, format: VersionFormat) -> InterpreterResult<Version> {
+    cc_parse_with_data!(EXACT_VERSION, version, format)
+}
+
 #[allow(clippy::result_large_err)]
 pub fn parse_subject(subject: &'_ str) -> InterpreterResult<Subject<'_>> {
     cc_parse!(SUBJECT, subject)
 }
+
+#[allow(clippy::result_large_err)]
+pub fn parse_body(body: &'_ str) -> InterpreterResult<(&'_ str, Footers<'_>)> {
+    cc_parse!(COMMIT_BODY, body)
+}
+
+/// Parses a commit subject that must be conventional, rejecting trailing
+/// input that [`parse_subject`] would fold into a text subject.
+#[allow(clippy::result_large_err)]
+pub fn parse_conventional_subject(subject: &'_ str) -> InterpreterResult<ConventionalSubject<'_>> {
+    cc_parse!(COMMIT_MESSAGE_SUBJECT, subject).map_err(|e| e.renamed_rules(describe_rule))
+}
+
+/// Names rules the way a commit author would, for parse errors.
+fn describe_rule(rule: &Rule) -> String {
+    match rule {
+        Rule::TAG | Rule::TYPE => "a commit type such as `feat` or `fix`".to_string(),
+        Rule::SCOPE_SECTION => "`(scope)`, `!` or `:`".to_string(),
+        Rule::SCOPE => "a scope".to_string(),
+        Rule::BREAKING_BANG => "`!`".to_string(),
+        Rule::DESCRIPTION => "a description".to_string(),
+        Rule::EOI => "the end of the subject".to_string(),
+        other => format!("{:?}", other),
+    }
+}
@@ -3,7 +3,8 @@ use eyre::Result;
 
 #[test]
 fn test_self_git_log() -> Result<()> {
-    let logs = Logs::default();
+    let raw = crate::git::formatted_logs(&std::env::current_dir()?)?;
+    let logs = Logs::from_log_str(&raw)?;
     println!("{logs:#?}");
     Ok(())
 }
@@ -1,9 +1,11 @@
+#![allow(clippy::result_large_err)]
+
 use indoc::indoc;
 
 use crate::logs::Tag;
 use crate::parser::InterpreterResult;
 use crate::parser::macros::cc_parse;
-use crate::version::{Version, VersionNumber};
+use crate::version::{BuildKind, Version, VersionNumber};
 
 #[test]
 fn test_footers() -> InterpreterResult<()> {
@@ -157,8 +159,42 @@ fn test_tag_dec() -> InterpreterResult<()> {
             major: VersionNumber::CCVer(0),
             minor: VersionNumber::CCVer(1),
             patch: VersionNumber::CCVer(1),
-            prerelease: None
+            prerelease: vec![],
+            build: vec![],
+            build_kind: BuildKind::Metadata,
         })
     );
     Ok(())
 }
+
+#[test]
+fn test_commit_body_footers() -> InterpreterResult<()> {
+    let body = indoc! {"
+        Explain the change.
+
+        BREAKING CHANGE: `--format` now takes a template
+          instead of a name
+        Refs: #12
+        Closes #13
+    "};
+
+    let (prose, footers) = cc_parse!(COMMIT_BODY, body)?;
+    assert_eq!(prose, "Explain the change.");
+    assert_eq!(
+        &footers[..],
+        [
+            (
+                "BREAKING CHANGE",
+                "`--format` now takes a template\n  instead of a name"
+            ),
+            ("Refs", "#12"),
+            ("Closes", "#13"),
+        ]
+    );
+
+    let (prose, footers) = cc_parse!(COMMIT_BODY, "Just prose: no footers here\nreally.")?;
+    assert_eq!(prose, "Just prose: no footers here\nreally.");
+    assert!(footers.is_empty());
+
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use crate::{
     parser::macros::{cc_parse_format, cc_parse_with_data},
-    version::Version,
+    version::{BuildKind, Version},
     version_format::VersionFormat,
 };
 use eyre::Result;
@@ -46,3 +46,80 @@ fn test_version_ordering() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_semver_precedence() -> Result<()> {
+    let default_config = VersionFormat::default();
+    // the example chain from semver.org §11.4
+    let chain = [
+        "1.0.0-alpha",
+        "1.0.0-alpha.1",
+        "1.0.0-alpha.beta",
+        "1.0.0-beta",
+        "1.0.0-beta.2",
+        "1.0.0-beta.11",
+        "1.0.0-rc.1",
+        "1.0.0",
+    ]
+    .map(|v| {
+        cc_parse_with_data!(CCVER_VERSION, v, default_config.clone()).map_err(eyre::Report::from)
+    })
+    .into_iter()
+    .collect::<Result<Vec<Version>, _>>()?;
+    assert!(chain.is_sorted());
+    assert!(chain.windows(2).all(|pair| pair[0] < pair[1]));
+
+    for raw in [
+        "1.0.0-rc.1+build.5",
+        "2.0.0-x.7.z.92",
+        "v1.0.0+20130313144700",
+    ] {
+        let version: Version = cc_parse_with_data!(CCVER_VERSION, raw, default_config.clone())?;
+        assert_eq!(version.to_string(), raw);
+    }
+
+    let with_build: Version =
+        cc_parse_with_data!(CCVER_VERSION, "1.0.0-rc.1+build.5", default_config.clone())?;
+    assert_eq!(with_build.build, ["build", "5"]);
+    assert_eq!(
+        with_build.cmp_precedence(&chain[6]),
+        std::cmp::Ordering::Equal
+    );
+    Ok(())
+}
+
+#[test]
+fn test_build_metadata_kind() -> Result<()> {
+    let default_config = VersionFormat::default();
+    let short_sha_config = cc_parse_format!(CCVER_VERSION_FORMAT, "vCC.CC.CC+<short-sha>")?;
+
+    let metadata: Version = cc_parse_with_data!(CCVER_VERSION, "v1.0.0+exp1234", default_config)?;
+    assert_eq!(metadata.build_kind, BuildKind::Metadata);
+    assert_eq!(
+        serde_json::to_value(&metadata)?["prerelease"],
+        serde_json::Value::Null
+    );
+
+    let short_sha: Version =
+        cc_parse_with_data!(CCVER_VERSION, "v1.0.0+1234567", short_sha_config.clone())?;
+    assert_eq!(short_sha.build_kind, BuildKind::ShortSha);
+    assert_eq!(
+        serde_json::to_value(&short_sha)?["prerelease"],
+        serde_json::json!({ "kind": "short_sha", "name": null, "number": "1234567" })
+    );
+    let not_a_sha: Version =
+        cc_parse_with_data!(CCVER_VERSION, "v1.0.0+exp1234", short_sha_config)?;
+    assert_eq!(not_a_sha.build_kind, BuildKind::Metadata);
+    Ok(())
+}
+
+#[test]
+fn test_leading_zero_identifiers() -> Result<()> {
+    let default_config = VersionFormat::default();
+    for raw in ["1.0.0-rc.01", "1.0.0-00"] {
+        assert!(cc_parse_with_data!(CCVER_VERSION, raw, default_config.clone()).is_err());
+    }
+    let version: Version = cc_parse_with_data!(CCVER_VERSION, "1.0.0-rc.0.0a", default_config)?;
+    assert_eq!(version.to_string(), "1.0.0-rc.0.0a");
+    Ok(())
+}
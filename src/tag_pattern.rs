@@ -0,0 +1,162 @@
+use std::{fmt::Display, str::FromStr};
+
+use eyre::*;
+use serde::Serialize;
+
+use crate::{
+    logs::{Decoration, LogEntry, Logs, Tag},
+    parser,
+    version::Version,
+    version_format::VersionFormat,
+};
+
+const PLACEHOLDER: &str = "{version}";
+
+/// How version tags are named: a template with a single `{version}`
+/// placeholder, such as `release-{version}` or `mylib/{version}`.
+///
+/// ```toml
+/// [tags]
+/// pattern = "mylib/{version}"
+/// ```
+///
+/// The default, `{version}`, names tags with the bare version.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TagPattern {
+    pub prefix: String,
+    pub suffix: String,
+}
+
+impl TagPattern {
+    /// The same pattern with `prefix` put in front of it.
+    pub fn prefixed(&self, prefix: &str) -> Self {
+        TagPattern {
+            prefix: format!("{}{}", prefix, self.prefix),
+            suffix: self.suffix.clone(),
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.prefix.is_empty() && self.suffix.is_empty()
+    }
+
+    /// The tag name for `version`.
+    pub fn render(&self, version: &Version) -> String {
+        format!("{}{}{}", self.prefix, version, self.suffix)
+    }
+
+    /// The version in a tag name that follows the pattern.
+    pub fn parse(&self, tag: &str, version_format: &VersionFormat) -> Option<Version> {
+        let version = tag
+            .strip_prefix(self.prefix.as_str())?
+            .strip_suffix(self.suffix.as_str())?;
+        parser::parse_version(version, version_format.clone()).ok()
+    }
+
+    /// `tag` as seen through the pattern: tags following it become version
+    /// tags and other text tags are kept. Version tags that do not follow it
+    /// are dropped.
+    pub fn retag<'a>(&self, tag: &Tag<'a>, version_format: &VersionFormat) -> Option<Tag<'a>> {
+        match tag {
+            Tag::Version(version) if self.is_default() => Some(Tag::Version(version.clone())),
+            Tag::Version(version) => self
+                .parse(&version.to_string(), version_format)
+                .map(Tag::Version),
+            Tag::Text(text) => Some(match self.parse(text, version_format) {
+                Some(version) => Tag::Version(version),
+                None => Tag::Text(text),
+            }),
+        }
+    }
+
+    /// `log_entry` with its tags passed through [`TagPattern::retag`].
+    pub fn retag_entry<'a>(
+        &self,
+        log_entry: &LogEntry<'a>,
+        version_format: &VersionFormat,
+    ) -> LogEntry<'a> {
+        LogEntry {
+            decorations: log_entry
+                .decorations
+                .iter()
+                .filter_map(|decoration| match decoration {
+                    Decoration::Tag(tag) => self.retag(tag, version_format).map(Decoration::Tag),
+                    other => Some(other.clone()),
+                })
+                .collect(),
+            ..log_entry.clone()
+        }
+    }
+
+    /// Reads every tag in `logs` through the pattern.
+    pub fn logs<'a>(&self, logs: Logs<'a>, version_format: &VersionFormat) -> Logs<'a> {
+        if self.is_default() {
+            return logs;
+        }
+        let log_entries: &[LogEntry<'a>] = &logs;
+        log_entries
+            .iter()
+            .map(|log_entry| self.retag_entry(log_entry, version_format))
+            .collect()
+    }
+}
+
+impl FromStr for TagPattern {
+    type Err = eyre::Error;
+
+    fn from_str(pattern: &str) -> Result<Self> {
+        let (prefix, suffix) = pattern
+            .split_once(PLACEHOLDER)
+            .ok_or_else(|| eyre!("tag pattern `{}` has no `{}`", pattern, PLACEHOLDER))?;
+        if suffix.contains(PLACEHOLDER) {
+            return Err(eyre!(
+                "tag pattern `{}` has more than one `{}`",
+                pattern,
+                PLACEHOLDER
+            ));
+        }
+        Ok(TagPattern {
+            prefix: prefix.to_string(),
+            suffix: suffix.to_string(),
+        })
+    }
+}
+
+impl Display for TagPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}{}", self.prefix, PLACEHOLDER, self.suffix)
+    }
+}
+
+#[cfg(test)]
+mod tag_pattern_tests {
+    use super::TagPattern;
+    use crate::{logs::Tag, version_format::VersionFormat};
+
+    #[test]
+    fn parses_and_renders_tags() {
+        let format = VersionFormat::default();
+        let pattern: TagPattern = "mylib/{version}".parse().unwrap();
+        let version = pattern.parse("mylib/v2.0.1", &format).unwrap();
+        assert_eq!(version.to_string(), "v2.0.1");
+        assert_eq!(pattern.render(&version), "mylib/v2.0.1");
+        assert_eq!(pattern.parse("other/v2.0.1", &format), None);
+        assert_eq!(pattern.to_string(), "mylib/{version}");
+
+        let release: TagPattern = "release-{version}".parse().unwrap();
+        assert!(matches!(
+            release.retag(&Tag::Text("release-1.4.0"), &format),
+            Some(Tag::Version(_))
+        ));
+        assert_eq!(release.retag(&Tag::Version(version.clone()), &format), None);
+
+        let v: TagPattern = "v{version}".parse().unwrap();
+        let Some(Tag::Version(stripped)) = v.retag(&Tag::Version(version), &format) else {
+            panic!("`v2.0.1` follows `v{{version}}`");
+        };
+        assert_eq!(v.render(&stripped), "v2.0.1");
+
+        assert!("release".parse::<TagPattern>().is_err());
+        assert!("{version}-{version}".parse::<TagPattern>().is_err());
+    }
+}
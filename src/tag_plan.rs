@@ -0,0 +1,214 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use serde::Serialize;
+
+use crate::{git::TagAnnotation, output::CommitRef, version::Version};
+
+/// What `tag` does with one commit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TagAction {
+    Create,
+    /// The commit already carries its tag or another version tag, or an
+    /// older commit in the plan takes the same tag.
+    Skip,
+    /// A tag with the same name already points at another commit.
+    Conflict {
+        commit: String,
+    },
+    /// The version leaves the line the commit's maintenance branch keeps.
+    OffLine {
+        reason: String,
+    },
+    /// The commit's `Release-As:` footer can't be followed.
+    InvalidReleaseAs {
+        reason: String,
+    },
+}
+
+impl TagAction {
+    /// Decides what to do with `tag` on `commit`, given the commits existing
+    /// tags point at and the tags already planned for older commits: a merge
+    /// that changes nothing keeps the version of its parent.
+    pub fn new(
+        commit: &str,
+        already_tagged: bool,
+        tag: &str,
+        existing: &HashMap<String, String>,
+        planned: &HashSet<String>,
+    ) -> Self {
+        match existing.get(tag) {
+            Some(target) if target != commit => TagAction::Conflict {
+                commit: target.clone(),
+            },
+            Some(_) => TagAction::Skip,
+            None if already_tagged || planned.contains(tag) => TagAction::Skip,
+            None => TagAction::Create,
+        }
+    }
+
+    /// Refuses to create a tag for a version that leaves its maintenance line.
+    pub fn within_line(self, violation: Option<&str>) -> Self {
+        match (self, violation) {
+            (TagAction::Create, Some(reason)) => TagAction::OffLine {
+                reason: reason.to_string(),
+            },
+            (action, _) => action,
+        }
+    }
+
+    /// Refuses to create a tag for a commit whose `Release-As:` footer was
+    /// ignored, as the version it computed is not the one asked for.
+    pub fn with_valid_release_as(self, error: Option<&str>) -> Self {
+        match (self, error) {
+            (TagAction::Create, Some(reason)) => TagAction::InvalidReleaseAs {
+                reason: reason.to_string(),
+            },
+            (action, _) => action,
+        }
+    }
+}
+
+/// One line of the plan `tag` follows (and prints with `--dry-run`).
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedTag {
+    pub tag: String,
+    pub version: Version,
+    pub commit: CommitRef,
+    pub action: TagAction,
+    /// Set for tags created annotated.
+    #[serde(skip)]
+    pub annotation: Option<TagAnnotation>,
+}
+
+impl PlannedTag {
+    /// Why the tag can't be created when it conflicts with an existing one,
+    /// leaves a maintenance line or ignores a `Release-As:` footer.
+    pub fn conflict(&self) -> Option<String> {
+        match &self.action {
+            TagAction::Conflict { commit } => {
+                Some(format!("Tag {} already exists on {}", self.tag, commit))
+            }
+            TagAction::OffLine { reason } | TagAction::InvalidReleaseAs { reason } => {
+                Some(format!("Tag {}: {}", self.tag, reason))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn short(hash: &str) -> &str {
+    &hash[..hash.len().min(7)]
+}
+
+impl Display for PlannedTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} ",
+            short(&self.commit.hash),
+            self.commit.branch,
+            self.version
+        )?;
+        match &self.action {
+            TagAction::Create => write!(f, "create {}", self.tag),
+            TagAction::Skip => write!(f, "skip (already tagged)"),
+            TagAction::Conflict { commit } => {
+                write!(f, "conflict ({} is on {})", self.tag, short(commit))
+            }
+            TagAction::OffLine { .. } => write!(f, "off line ({} is not created)", self.tag),
+            TagAction::InvalidReleaseAs { .. } => {
+                write!(f, "invalid Release-As ({} is not created)", self.tag)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tag_plan_tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::TagAction;
+
+    #[test]
+    fn decides_each_action() {
+        let existing = HashMap::from([("v1.0.0".to_string(), "a".repeat(40))]);
+        let a = "a".repeat(40);
+        let b = "b".repeat(40);
+        assert_eq!(
+            TagAction::new(&b, false, "v1.1.0", &existing, &HashSet::new()),
+            TagAction::Create
+        );
+        assert_eq!(
+            TagAction::new(&b, true, "v1.1.0", &existing, &HashSet::new()),
+            TagAction::Skip
+        );
+        assert_eq!(
+            TagAction::new(&a, false, "v1.0.0", &existing, &HashSet::new()),
+            TagAction::Skip
+        );
+        assert_eq!(
+            TagAction::new(&b, false, "v1.0.0", &existing, &HashSet::new()),
+            TagAction::Conflict { commit: a }
+        );
+    }
+
+    #[test]
+    fn refuses_versions_off_their_line() {
+        let b = "b".repeat(40);
+        let reason = "`fix` would release 2.0.0";
+        assert_eq!(
+            TagAction::new(&b, false, "v2.0.0", &HashMap::new(), &HashSet::new())
+                .within_line(Some(reason)),
+            TagAction::OffLine {
+                reason: reason.to_string()
+            }
+        );
+        assert_eq!(
+            TagAction::new(&b, true, "v2.0.0", &HashMap::new(), &HashSet::new())
+                .within_line(Some(reason)),
+            TagAction::Skip
+        );
+        assert_eq!(
+            TagAction::new(&b, false, "v1.0.1", &HashMap::new(), &HashSet::new()).within_line(None),
+            TagAction::Create
+        );
+    }
+
+    #[test]
+    fn refuses_commits_with_an_invalid_release_as() {
+        let b = "b".repeat(40);
+        let reason = "Release-As: 0.1.0 on bbbbbbb would go backwards from 1.0.0";
+        assert_eq!(
+            TagAction::new(&b, false, "v1.0.1", &HashMap::new(), &HashSet::new())
+                .with_valid_release_as(Some(reason)),
+            TagAction::InvalidReleaseAs {
+                reason: reason.to_string()
+            }
+        );
+        assert_eq!(
+            TagAction::new(&b, false, "v1.0.1", &HashMap::new(), &HashSet::new())
+                .with_valid_release_as(None),
+            TagAction::Create
+        );
+    }
+
+    #[test]
+    fn skips_tags_planned_for_older_commits() {
+        // a merge with a text subject on a release branch releases its
+        // parent's version again
+        let merge = "c".repeat(40);
+        let planned = HashSet::from(["v1.1.0".to_string()]);
+        assert_eq!(
+            TagAction::new(&merge, false, "v1.1.0", &HashMap::new(), &planned),
+            TagAction::Skip
+        );
+        assert_eq!(
+            TagAction::new(&merge, false, "v1.2.0", &HashMap::new(), &planned),
+            TagAction::Create
+        );
+    }
+}
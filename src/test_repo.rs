@@ -0,0 +1,105 @@
+//! Scratch directories and git repositories for tests that need the
+//! filesystem or git itself; [`MemoryBackend`](crate::backend::MemoryBackend)
+//! covers the rest.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use eyre::{Result, eyre};
+
+/// An empty directory of its own under the temporary directory, removed
+/// with everything in it when dropped.
+#[derive(Debug)]
+pub struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    /// A directory named after `name` and this process, so test runs do not
+    /// share it.
+    pub fn new(name: &str) -> ScratchDir {
+        let path = std::env::temp_dir().join(format!("ccver-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        ScratchDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A git repository in a [`ScratchDir`], on `main`, with an identity to
+/// commit as and tags left unsigned.
+#[derive(Debug)]
+pub struct TestRepo {
+    dir: ScratchDir,
+}
+
+impl TestRepo {
+    pub fn new(name: &str) -> TestRepo {
+        let repo = TestRepo {
+            dir: ScratchDir::new(name),
+        };
+        for args in [
+            &["init", "--quiet", "--initial-branch=main"][..],
+            &["config", "user.email", "ccver@example.com"],
+            &["config", "user.name", "ccver"],
+            &["config", "tag.gpgSign", "false"],
+        ] {
+            repo.git(args).unwrap();
+        }
+        repo
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Runs git in the repository and returns what it printed, trimmed, or
+    /// what it reported when it failed.
+    pub fn git(&self, args: &[&str]) -> Result<String> {
+        self.git_with(args, &[])
+    }
+
+    /// Like [`git`](TestRepo::git), committing at `date`.
+    pub fn git_at(&self, args: &[&str], date: &str) -> Result<String> {
+        self.git_with(
+            args,
+            &[("GIT_AUTHOR_DATE", date), ("GIT_COMMITTER_DATE", date)],
+        )
+    }
+
+    fn git_with(&self, args: &[&str], envs: &[(&str, &str)]) -> Result<String> {
+        let output = Command::new("git")
+            .args(args)
+            .envs(envs.iter().copied())
+            .current_dir(self.path())
+            .output()?;
+        if !output.status.success() {
+            return Err(eyre!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Makes an empty commit with `message` and returns its hash.
+    pub fn commit(&self, message: &str) -> Result<String> {
+        self.git(&["commit", "--quiet", "--allow-empty", "-m", message])?;
+        self.git(&["rev-parse", "HEAD"])
+    }
+
+    /// Writes `contents` to `path` in the work tree.
+    pub fn write(&self, path: &str, contents: &str) -> Result<()> {
+        Ok(std::fs::write(self.path().join(path), contents)?)
+    }
+}
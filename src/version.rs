@@ -3,21 +3,49 @@ use std::{
     fmt::{Display, Formatter},
 };
 
+use eyre::{Result, WrapErr as _, eyre};
+use serde::{Serialize, Serializer, ser::SerializeStruct as _};
+use tracing::{info, warn};
+
 use crate::{
-    logs::{LogEntry, Subject},
-    pattern_macros::*,
+    config::{
+        BranchClass::{Alpha, Beta, Maintenance, Named, Rc, Release},
+        Bump, CCVerConfig,
+        SubjectClass::{self, Conventional, Major, Minor, Patch, Text},
+    },
+    logs::LogEntry,
+    parser,
     version_format::{
         CalVerFormat, CalVerFormatSegment, PreTagFormat, VersionFormat, VersionNumberFormat,
     },
 };
 
+/// A version as semver.org describes it: `major.minor.patch`, an optional
+/// list of dot-separated prerelease identifiers after `-` and optional build
+/// metadata after `+`. A leading `v` is kept so versions round-trip.
 #[derive(PartialEq, Eq, Debug, Clone, Hash)]
 pub struct Version {
     pub v_prefix: bool,
     pub major: VersionNumber,
     pub minor: VersionNumber,
     pub patch: VersionNumber,
-    pub prerelease: Option<PreTag>,
+    /// Empty for releases.
+    pub prerelease: Vec<Identifier>,
+    /// Ignored for precedence.
+    pub build: Vec<String>,
+    /// What `build` holds.
+    pub build_kind: BuildKind,
+}
+
+/// Whether build metadata is the commit hash a sha or short sha format
+/// writes, as opposed to any other metadata. This is not inferred from the
+/// metadata itself: `+exp1234` is not a short sha.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Hash, Default)]
+pub enum BuildKind {
+    #[default]
+    Metadata,
+    Sha,
+    ShortSha,
 }
 
 impl Display for Version {
@@ -26,10 +54,13 @@ impl Display for Version {
             write!(f, "v")?;
         }
         write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
-        match &self.prerelease {
-            None => Ok(()),
-            Some(pre) => write!(f, "{}", pre),
+        if !self.prerelease.is_empty() {
+            write!(f, "-{}", join_identifiers(&self.prerelease))?;
+        }
+        if !self.build.is_empty() {
+            write!(f, "+{}", self.build.join("."))?;
         }
+        std::fmt::Result::Ok(())
     }
 }
 
@@ -40,75 +71,265 @@ impl Version {
             major: self.major.clone(),
             minor: self.minor.clone(),
             patch: self.patch.clone(),
-            prerelease: None,
+            prerelease: vec![],
+            build: vec![],
+            build_kind: BuildKind::Metadata,
+        }
+    }
+
+    pub fn is_prerelease(&self) -> bool {
+        !self.prerelease.is_empty()
+    }
+
+    /// The counter of a `<label>.<counter>` prerelease such as `rc.3`.
+    pub fn channel_counter(&self, label: &str) -> Option<&Identifier> {
+        match &self.prerelease[..] {
+            [Identifier::AlphaNumeric(name), counter] if name == label => Some(counter),
+            _ => None,
         }
     }
+
+    /// Orders versions by precedence exactly as semver.org §11 does: numbers
+    /// first, then a release above any of its prereleases, then prerelease
+    /// identifiers from left to right. Build metadata is ignored.
+    pub fn cmp_precedence(&self, other: &Self) -> Ordering {
+        self.major
+            .cmp(&other.major)
+            .then_with(|| self.minor.cmp(&other.minor))
+            .then_with(|| self.patch.cmp(&other.patch))
+            .then_with(
+                || match (self.prerelease.is_empty(), other.prerelease.is_empty()) {
+                    (true, true) => Ordering::Equal,
+                    (true, false) => Ordering::Greater,
+                    (false, true) => Ordering::Less,
+                    (false, false) => self.prerelease.cmp(&other.prerelease),
+                },
+            )
+    }
+
     pub fn next_version<'a>(
         &self,
         log_entry: &LogEntry<'a>,
         version_format: &VersionFormat,
+        config: &CCVerConfig,
+    ) -> Version {
+        if let Some(pinned) = self.pinned_version(log_entry, version_format) {
+            return pinned;
+        }
+        self.next_version_as(
+            self.implied_class(log_entry, config),
+            log_entry,
+            version_format,
+            config,
+        )
+    }
+
+    /// Like [`Version::release_as`], but an invalid footer is logged and
+    /// ignored, so that one bad commit in the history does not stop every
+    /// run; `peek` still rejects it in the message being written.
+    pub fn pinned_version(
+        &self,
+        log_entry: &LogEntry,
+        version_format: &VersionFormat,
+    ) -> Option<Version> {
+        self.release_as(log_entry, version_format)
+            .unwrap_or_else(|e| {
+                warn!("{}; ignoring it", e);
+                None
+            })
+    }
+
+    /// The version `log_entry`'s `Release-As:` footer pins it to, read in
+    /// `version_format` (its `v` prefix follows the format). Errors when the
+    /// value is not such a version or is not above this one, the greatest
+    /// version of the commit's parents.
+    pub fn release_as(
+        &self,
+        log_entry: &LogEntry,
+        version_format: &VersionFormat,
+    ) -> Result<Option<Version>> {
+        let Some(value) = log_entry.release_as() else {
+            return Ok(None);
+        };
+        let short_hash = &log_entry.commit_hash[..log_entry.commit_hash.len().min(7)];
+        let pinned = parser::parse_exact_version(value, version_format.clone()).map_err(|e| {
+            eyre!(
+                "Release-As: {} on {} is not a {} version: {}",
+                value,
+                short_hash,
+                version_format,
+                e
+            )
+        })?;
+        let pinned = Version {
+            v_prefix: version_format.v_prefix,
+            ..pinned
+        };
+        match pinned.cmp_precedence(self) {
+            Ordering::Less => Err(eyre!(
+                "Release-As: {} on {} would go backwards from {}",
+                value,
+                short_hash,
+                self
+            )),
+            Ordering::Equal => Err(eyre!(
+                "Release-As: {} on {} repeats the version of its parent",
+                value,
+                short_hash
+            )),
+            Ordering::Greater => Ok(Some(pinned)),
+        }
+    }
+
+    /// Errors when `next`, computed from this version for `log_entry`, would
+    /// take a maintenance branch out of its line. Commits from before the
+    /// branch reached its line are not checked.
+    pub fn check_line(
+        &self,
+        next: &Version,
+        log_entry: &LogEntry,
+        config: &CCVerConfig,
+    ) -> Result<()> {
+        let Some(line) = config.maintenance_line(log_entry.branch) else {
+            return Ok(());
+        };
+        if !line.contains(self) || line.contains(next) {
+            return Ok(());
+        }
+        Err(eyre!(
+            "`{}` ({}) would release {}, leaving the {} line maintained by `{}`; land it on a release branch instead",
+            log_entry.subject,
+            &log_entry.commit_hash[..log_entry.commit_hash.len().min(7)],
+            next,
+            line,
+            log_entry.branch
+        ))
+    }
+
+    /// `0.y.z`: anything may change at any time.
+    pub fn is_initial_development(&self) -> bool {
+        self.major == VersionNumber::CCVer(0)
+    }
+
+    /// The bump `log_entry` implies on top of this version. During initial
+    /// development a `Graduate:` footer is a major bump, and with
+    /// `initial_development` configured breaking changes bump the minor
+    /// version and features the patch version.
+    pub fn implied_class(&self, log_entry: &LogEntry, config: &CCVerConfig) -> SubjectClass {
+        let class = config.commit_class(log_entry);
+        if !self.is_initial_development() {
+            return class;
+        }
+        if log_entry.graduates() {
+            return Major;
+        }
+        match (config.initial_development, class) {
+            (true, Major) => Minor,
+            (true, Minor) => Patch,
+            (_, class) => class,
+        }
+    }
+
+    /// The bump forced on HEAD when it is computed from this version: the
+    /// `--force-*` bump, or major for `--graduate` during initial
+    /// development.
+    pub fn head_bump(&self, config: &CCVerConfig) -> Option<Bump> {
+        config
+            .force_bump
+            .or_else(|| (config.graduate && self.is_initial_development()).then_some(Bump::Major))
+    }
+
+    /// Like [`Version::next_version`], but bumps by `force` instead of the
+    /// bump implied by the subject when one is given.
+    pub fn forced_next_version<'a>(
+        &self,
+        force: Option<Bump>,
+        log_entry: &LogEntry<'a>,
+        version_format: &VersionFormat,
+        config: &CCVerConfig,
+    ) -> Version {
+        let implied = self.next_version(log_entry, version_format, config);
+        let Some(bump) = force else {
+            return implied;
+        };
+        let forced = self.next_version_as(bump.into(), log_entry, version_format, config);
+        info!(
+            commit = log_entry.commit_hash,
+            bump = ?bump,
+            implied = %implied,
+            forced = %forced,
+            "Forced {:?} bump: commits imply {} but {} was requested",
+            bump,
+            implied,
+            forced
+        );
+        forced
+    }
+
+    /// Computes the next version as if `log_entry`'s subject was of class
+    /// `subject_class`. Used to apply forced bumps.
+    pub fn next_version_as<'a>(
+        &self,
+        subject_class: SubjectClass,
+        log_entry: &LogEntry<'a>,
+        version_format: &VersionFormat,
+        config: &CCVerConfig,
     ) -> Version {
         match (
-            &log_entry.subject,
-            log_entry.branch,
+            subject_class,
+            config.branch_class(log_entry.branch),
             log_entry.parent_hashes.len() == 2,
         ) {
-            (major_subject!(), release_branches!(), _) => self.major(log_entry, version_format),
-            (minor_subject!(), release_branches!(), _) => self.minor(log_entry, version_format),
-            (patch_subject!(), release_branches!(), _) => self.patch(log_entry, version_format),
-            (Subject::Conventional(_), release_branches!(), _) => {
-                self.short_sha(log_entry, version_format)
-            }
-            (major_subject!(), rc_branches!(), _) => self
+            (Major, Release | Maintenance, _) => self.major(log_entry, version_format),
+            (Minor, Release | Maintenance, _) => self.minor(log_entry, version_format),
+            (Patch, Release | Maintenance, _) => self.patch(log_entry, version_format),
+            (Conventional, Release | Maintenance, _) => self.short_sha(log_entry, version_format),
+            (Major, Rc, _) => self
                 .major(log_entry, version_format)
                 .rc(log_entry, version_format),
-            (minor_subject!(), rc_branches!(), _) => self
+            (Minor, Rc, _) => self
                 .minor(log_entry, version_format)
                 .rc(log_entry, version_format),
-            (patch_subject!(), rc_branches!(), _) => self
+            (Patch, Rc, _) => self
                 .patch(log_entry, version_format)
                 .rc(log_entry, version_format),
-            (Subject::Conventional(_), rc_branches!(), _) => self.rc(log_entry, version_format),
-            (major_subject!(), beta_branches!(), _) => self
+            (Conventional, Rc, _) => self.rc(log_entry, version_format),
+            (Major, Beta, _) => self
                 .major(log_entry, version_format)
                 .beta(log_entry, version_format),
-            (minor_subject!(), beta_branches!(), _) => self
+            (Minor, Beta, _) => self
                 .minor(log_entry, version_format)
                 .beta(log_entry, version_format),
-            (patch_subject!(), beta_branches!(), _) => self
+            (Patch, Beta, _) => self
                 .patch(log_entry, version_format)
                 .beta(log_entry, version_format),
-            (Subject::Conventional(_), beta_branches!(), _) => self.beta(log_entry, version_format),
-            (major_subject!(), alpha_branches!(), _) => self
+            (Conventional, Beta, _) => self.beta(log_entry, version_format),
+            (Major, Alpha, _) => self
                 .major(log_entry, version_format)
                 .alpha(log_entry, version_format),
-            (minor_subject!(), alpha_branches!(), _) => self
+            (Minor, Alpha, _) => self
                 .minor(log_entry, version_format)
                 .alpha(log_entry, version_format),
-            (patch_subject!(), alpha_branches!(), _) => self
+            (Patch, Alpha, _) => self
                 .patch(log_entry, version_format)
                 .alpha(log_entry, version_format),
-            (Subject::Conventional(_), alpha_branches!(), _) => {
-                self.alpha(log_entry, version_format)
-            }
-            (major_subject!(), _, _) => self
+            (Conventional, Alpha, _) => self.alpha(log_entry, version_format),
+            (Major, Named, _) => self
                 .major(log_entry, version_format)
                 .named(log_entry, version_format),
-            (minor_subject!(), _, _) => self
+            (Minor, Named, _) => self
                 .minor(log_entry, version_format)
                 .named(log_entry, version_format),
-            (patch_subject!(), _, _) => self
+            (Patch, Named, _) => self
                 .patch(log_entry, version_format)
                 .named(log_entry, version_format),
-            (Subject::Conventional(_), _, _) => self.named(log_entry, version_format),
-            (Subject::Text(_), release_branches!(), true) => {
-                self.release(log_entry, version_format)
-            }
-            (Subject::Text(_), release_branches!(), _) => self.short_sha(log_entry, version_format),
-            (Subject::Text(_), rc_branches!(), _) => self.rc(log_entry, version_format),
-            (Subject::Text(_), beta_branches!(), _) => self.beta(log_entry, version_format),
-            (Subject::Text(_), alpha_branches!(), _) => self.alpha(log_entry, version_format),
-            (Subject::Text(_), _, _) => self.named(log_entry, version_format),
+            (Conventional, Named, _) => self.named(log_entry, version_format),
+            (Text, Release | Maintenance, true) => self.release(log_entry, version_format),
+            (Text, Release | Maintenance, _) => self.short_sha(log_entry, version_format),
+            (Text, Rc, _) => self.rc(log_entry, version_format),
+            (Text, Beta, _) => self.beta(log_entry, version_format),
+            (Text, Alpha, _) => self.alpha(log_entry, version_format),
+            (Text, Named, _) => self.named(log_entry, version_format),
         }
     }
 
@@ -118,7 +339,9 @@ impl Version {
             major: self.major.bump(commit),
             minor: self.minor.zero(commit),
             patch: self.patch.zero(commit),
-            prerelease: None,
+            prerelease: vec![],
+            build: vec![],
+            build_kind: BuildKind::Metadata,
         }
     }
 
@@ -128,7 +351,9 @@ impl Version {
             major: self.major.peek(commit),
             minor: self.minor.bump(commit),
             patch: self.minor.zero(commit),
-            prerelease: None,
+            prerelease: vec![],
+            build: vec![],
+            build_kind: BuildKind::Metadata,
         }
     }
 
@@ -138,110 +363,80 @@ impl Version {
             major: self.major.peek(commit),
             minor: self.minor.peek(commit),
             patch: self.patch.bump(commit),
-            prerelease: None,
+            prerelease: vec![],
+            build: vec![],
+            build_kind: BuildKind::Metadata,
         }
     }
 
+    /// The next build of this version, such as `1.2.0-build.3` for a dirty
+    /// worktree.
     pub fn build(&self, commit: &LogEntry, version_format: &VersionFormat) -> Self {
-        let pre_format = version_format.prerelease.as_ref().unwrap_or_default();
-
-        Version {
-            v_prefix: version_format.v_prefix,
-            major: self.major.peek(commit),
-            minor: self.minor.peek(commit),
-            patch: self.patch.peek(commit),
-            prerelease: match &self.prerelease {
-                None => Some(PreTag::Build(
-                    pre_format
-                        .version_format()
-                        .as_default_version_number(commit),
-                )),
-                Some(PreTag::Build(v)) => Some(PreTag::Build(v.bump(commit))),
-                Some(_) => Some(PreTag::Build(
-                    pre_format
-                        .version_format()
-                        .as_default_version_number(commit),
-                )),
-            },
-        }
+        self.channel("build", commit, version_format)
     }
 
     pub fn rc(&self, commit: &LogEntry, version_format: &VersionFormat) -> Self {
-        let pre_format = version_format.prerelease.as_ref().unwrap_or_default();
-        Version {
-            v_prefix: version_format.v_prefix,
-            major: self.major.peek(commit),
-            minor: self.minor.peek(commit),
-            patch: self.patch.peek(commit),
-            prerelease: match &self.prerelease {
-                Some(PreTag::Rc(v)) => Some(PreTag::Rc(v.bump(commit))),
-                _ => Some(PreTag::Rc(
-                    pre_format
-                        .version_format()
-                        .as_default_version_number(commit),
-                )),
-            },
-        }
+        self.channel("rc", commit, version_format)
     }
 
     pub fn beta(&self, commit: &LogEntry, version_format: &VersionFormat) -> Self {
-        let pre_format = version_format.prerelease.as_ref().unwrap_or_default();
-        Version {
-            v_prefix: version_format.v_prefix,
-            major: self.major.peek(commit),
-            minor: self.minor.peek(commit),
-            patch: self.patch.peek(commit),
-            prerelease: match &self.prerelease {
-                Some(PreTag::Beta(v)) => Some(PreTag::Beta(v.bump(commit))),
-                _ => Some(PreTag::Beta(
-                    pre_format
-                        .version_format()
-                        .as_default_version_number(commit),
-                )),
-            },
-        }
+        self.channel("beta", commit, version_format)
     }
 
     pub fn alpha(&self, commit: &LogEntry, version_format: &VersionFormat) -> Version {
-        let pre_format = version_format.prerelease.as_ref().unwrap_or_default();
-        Version {
-            v_prefix: version_format.v_prefix,
-            major: self.major.peek(commit),
-            minor: self.minor.peek(commit),
-            patch: self.patch.peek(commit),
-            prerelease: match &self.prerelease {
-                Some(PreTag::Alpha(v)) => Some(PreTag::Alpha(v.bump(commit))),
-                _ => Some(PreTag::Alpha(
-                    pre_format
-                        .version_format()
-                        .as_default_version_number(commit),
-                )),
-            },
-        }
+        self.channel("alpha", commit, version_format)
     }
 
     pub fn named(&self, commit: &LogEntry, version_format: &VersionFormat) -> Version {
-        let pre_format = version_format.prerelease.as_ref().unwrap_or_default();
+        let name = commit
+            .branch
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>();
+        // the counter only continues while the branch name is used as is
+        let counter = self
+            .channel_counter(commit.branch)
+            .filter(|_| name == commit.branch);
+        self.with_channel(name, counter, commit, version_format)
+    }
+
+    /// `<label>.<counter>` on the same numbers, continuing the counter when
+    /// this version is already on the channel and restarting it otherwise.
+    fn channel(&self, label: &str, commit: &LogEntry, version_format: &VersionFormat) -> Self {
+        self.with_channel(
+            label.to_string(),
+            self.channel_counter(label),
+            commit,
+            version_format,
+        )
+    }
+
+    fn with_channel(
+        &self,
+        label: String,
+        counter: Option<&Identifier>,
+        commit: &LogEntry,
+        version_format: &VersionFormat,
+    ) -> Self {
+        let counter_format = version_format
+            .prerelease
+            .as_ref()
+            .unwrap_or_default()
+            .version_format();
+        let counter = match (&counter_format, counter) {
+            (VersionNumberFormat::CCVer, Some(Identifier::Numeric(n))) => {
+                Identifier::Numeric(n + 1)
+            }
+            _ => Identifier::from(&counter_format.as_default_version_number(commit)),
+        };
         Version {
             v_prefix: version_format.v_prefix,
             major: self.major.peek(commit),
             minor: self.minor.peek(commit),
             patch: self.patch.peek(commit),
-            prerelease: match &self.prerelease {
-                Some(PreTag::Named(tag, v)) if tag.eq(commit.branch) => {
-                    Some(PreTag::Named(tag.to_string(), v.bump(commit)))
-                }
-                _ => Some(PreTag::Named(
-                    commit
-                        .branch
-                        .chars()
-                        .filter(|c| c.is_alphanumeric())
-                        .collect::<String>(),
-                    pre_format
-                        .version_format()
-                        .as_default_version_number(commit),
-                )),
-            },
+            prerelease: vec![Identifier::AlphaNumeric(label), counter],
+            build: vec![],
+            build_kind: BuildKind::Metadata,
         }
     }
 
@@ -251,58 +446,38 @@ impl Version {
             major: self.major.peek(commit),
             minor: self.minor.peek(commit),
             patch: self.patch.peek(commit),
-            prerelease: None,
+            prerelease: vec![],
+            build: vec![],
+            build_kind: BuildKind::Metadata,
         }
     }
 
+    /// The release numbers with the full commit hash as build metadata.
     pub fn sha(&self, commit: &LogEntry, version_format: &VersionFormat) -> Version {
         Version {
-            v_prefix: version_format.v_prefix,
-            major: self.major.peek(commit),
-            minor: self.minor.peek(commit),
-            patch: self.patch.peek(commit),
-            prerelease: Some(PreTag::Sha(VersionNumber::Sha(
-                commit.commit_hash.to_string(),
-            ))),
+            build: vec![commit.commit_hash.to_string()],
+            build_kind: BuildKind::Sha,
+            ..self.release(commit, version_format)
         }
     }
 
+    /// The release numbers with the short commit hash as build metadata.
     pub fn short_sha(&self, commit: &LogEntry, version_format: &VersionFormat) -> Version {
         Version {
-            v_prefix: version_format.v_prefix,
-            major: self.major.peek(commit),
-            minor: self.minor.peek(commit),
-            patch: self.patch.peek(commit),
-            prerelease: Some(PreTag::ShortSha(VersionNumber::ShortSha(
-                commit.commit_hash[0..7].to_string(),
-            ))),
+            build: vec![commit.commit_hash[0..7].to_string()],
+            build_kind: BuildKind::ShortSha,
+            ..self.release(commit, version_format)
         }
     }
 }
 
+/// Precedence first; versions that only differ in build metadata are then
+/// ordered by it so that the ordering agrees with equality.
 impl Ord for Version {
     fn cmp(&self, other: &Self) -> Ordering {
-        match self.major.cmp(&other.major) {
-            Ordering::Equal => match self.minor.cmp(&other.minor) {
-                Ordering::Equal => match self.patch.cmp(&other.patch) {
-                    Ordering::Equal => match (&self.prerelease, &other.prerelease) {
-                        (None, None) => Ordering::Equal,
-                        (Some(PreTag::Build(_)), None) => Ordering::Greater,
-                        (None, Some(PreTag::Build(_))) => Ordering::Less,
-                        (Some(PreTag::Build(a)), Some(PreTag::Build(b))) => a.cmp(b),
-                        (Some(_), None) => Ordering::Less,
-                        (None, Some(_)) => Ordering::Greater,
-                        (Some(a), Some(b)) => match a.partial_cmp(b) {
-                            Some(ord) => ord,
-                            None => Ordering::Equal,
-                        },
-                    },
-                    ord => ord,
-                },
-                ord => ord,
-            },
-            ord => ord,
-        }
+        self.cmp_precedence(other)
+            .then_with(|| self.build.cmp(&other.build))
+            .then_with(|| self.build_kind.cmp(&other.build_kind))
     }
 }
 
@@ -312,85 +487,105 @@ impl PartialOrd for Version {
     }
 }
 
+/// A dot-separated prerelease identifier. Digits are numeric; anything else
+/// is kept as text so tags round-trip.
 #[derive(PartialEq, Eq, Debug, Clone, Hash)]
-pub enum PreTag {
-    Rc(VersionNumber),
-    Beta(VersionNumber),
-    Alpha(VersionNumber),
-    Build(VersionNumber),
-    Named(String, VersionNumber),
-    Sha(VersionNumber),
-    ShortSha(VersionNumber),
+pub enum Identifier {
+    Numeric(u64),
+    AlphaNumeric(String),
 }
 
-impl Display for PreTag {
-    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        match self {
-            PreTag::Rc(v) => write!(f, "-rc.{}", v),
-            PreTag::Beta(v) => write!(f, "-beta.{}", v),
-            PreTag::Alpha(v) => write!(f, "-alpha.{}", v),
-            PreTag::Build(v) => write!(f, "-build.{}", v),
-            PreTag::Named(tag, v) => write!(f, "-{}.{}", tag, v),
-            PreTag::Sha(s) => write!(f, "+{}", s),
-            PreTag::ShortSha(s) => write!(f, "+{}", s),
+impl Identifier {
+    /// Reads an identifier, rejecting numeric ones with a leading zero as
+    /// semver.org §9 does.
+    pub fn parse(identifier: &str) -> Result<Self> {
+        if !identifier.bytes().all(|b| b.is_ascii_digit()) {
+            return Ok(Identifier::AlphaNumeric(identifier.to_string()));
+        }
+        if identifier.len() > 1 && identifier.starts_with('0') {
+            return Err(eyre!(
+                "Numeric identifier `{}` must not have a leading zero",
+                identifier
+            ));
         }
+        identifier
+            .parse()
+            .map(Identifier::Numeric)
+            .wrap_err_with(|| format!("Numeric identifier `{}` is too large", identifier))
     }
 }
 
-impl PartialOrd for PreTag {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match self {
-            PreTag::Rc(v) => match other {
-                PreTag::Rc(v2) => v.partial_cmp(v2),
-                PreTag::Alpha(_) | PreTag::Beta(_) | PreTag::Build(_) => Some(Ordering::Greater),
-                _ => None,
-            },
-            PreTag::Beta(v) => match other {
-                PreTag::Rc(_) => Some(Ordering::Less),
-                PreTag::Beta(v2) => v.partial_cmp(v2),
-                PreTag::Alpha(_) | PreTag::Build(_) => Some(Ordering::Greater),
-                _ => None,
-            },
-            PreTag::Alpha(v) => match other {
-                PreTag::Rc(_) | PreTag::Beta(_) => Some(Ordering::Less),
-                PreTag::Alpha(v2) => v.partial_cmp(v2),
-                PreTag::Build(_) => Some(Ordering::Greater),
-                _ => None,
-            },
-            PreTag::Build(v) => match other {
-                PreTag::Rc(_) | PreTag::Beta(_) | PreTag::Alpha(_) => Some(Ordering::Less),
-                PreTag::Build(v2) => v.partial_cmp(v2),
-                _ => None,
-            },
-            PreTag::Named(tag, v) => match other {
-                PreTag::Named(tag2, v2) => {
-                    if tag == tag2 {
-                        v.partial_cmp(v2)
-                    } else {
-                        None
-                    }
-                }
-                _ => None,
+/// Counters and calendar segments become numbers, dropping the zeros that
+/// pad them; hashes stay text.
+impl From<&VersionNumber> for Identifier {
+    fn from(number: &VersionNumber) -> Self {
+        let rendered = number.to_string();
+        match number {
+            VersionNumber::Sha(_) | VersionNumber::ShortSha(_) => {
+                Identifier::AlphaNumeric(rendered)
+            }
+            _ => match rendered.parse() {
+                std::result::Result::Ok(n) => Identifier::Numeric(n),
+                Err(_) => Identifier::AlphaNumeric(rendered),
             },
-            _ => None,
         }
     }
 }
 
-impl From<PreTag> for PreTagFormat {
-    fn from(val: PreTag) -> Self {
-        match val {
-            PreTag::Rc(v) => PreTagFormat::Rc(v.into()),
-            PreTag::Beta(v) => PreTagFormat::Beta(v.into()),
-            PreTag::Alpha(v) => PreTagFormat::Alpha(v.into()),
-            PreTag::Build(v) => PreTagFormat::Build(v.into()),
-            PreTag::Named(tag, v) => PreTagFormat::Named(tag, v.into()),
-            PreTag::Sha(_) => PreTagFormat::Sha,
-            PreTag::ShortSha(_) => PreTagFormat::ShortSha,
+impl Display for Identifier {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Identifier::Numeric(n) => write!(f, "{}", n),
+            Identifier::AlphaNumeric(s) => write!(f, "{}", s),
         }
     }
 }
 
+/// Numeric identifiers compare numerically and sort before alphanumeric
+/// ones, which compare in ASCII order (semver.org §11.4.1-3).
+impl Ord for Identifier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Identifier::Numeric(a), Identifier::Numeric(b)) => a.cmp(b),
+            (Identifier::Numeric(_), Identifier::AlphaNumeric(_)) => Ordering::Less,
+            (Identifier::AlphaNumeric(_), Identifier::Numeric(_)) => Ordering::Greater,
+            (Identifier::AlphaNumeric(a), Identifier::AlphaNumeric(b)) => a.cmp(b),
+        }
+    }
+}
+
+impl PartialOrd for Identifier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn join_identifiers(identifiers: &[Identifier]) -> String {
+    identifiers
+        .iter()
+        .map(Identifier::to_string)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// The prerelease format a version was written with, for inferring the
+/// format from existing tags.
+fn pre_tag_format(version: &Version) -> Option<PreTagFormat> {
+    match (&version.prerelease[..], version.build_kind) {
+        ([], BuildKind::Sha) => Some(PreTagFormat::Sha),
+        ([], BuildKind::ShortSha) => Some(PreTagFormat::ShortSha),
+        ([], BuildKind::Metadata) => None,
+        ([Identifier::AlphaNumeric(label), _], _) => Some(match label.as_str() {
+            "rc" => PreTagFormat::Rc(VersionNumberFormat::CCVer),
+            "beta" => PreTagFormat::Beta(VersionNumberFormat::CCVer),
+            "alpha" => PreTagFormat::Alpha(VersionNumberFormat::CCVer),
+            "build" => PreTagFormat::Build(VersionNumberFormat::CCVer),
+            name => PreTagFormat::Named(name.to_string(), VersionNumberFormat::CCVer),
+        }),
+        _ => Some(PreTagFormat::default()),
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
 pub enum VersionNumber {
     CCVer(usize),
@@ -401,12 +596,13 @@ pub enum VersionNumber {
 
 impl From<Version> for VersionFormat {
     fn from(val: Version) -> Self {
+        let prerelease = pre_tag_format(&val);
         VersionFormat {
             v_prefix: val.v_prefix,
             major: val.major.into(),
             minor: val.minor.into(),
             patch: val.patch.into(),
-            prerelease: val.prerelease.map(|p| p.into()),
+            prerelease,
         }
     }
 }
@@ -461,58 +657,43 @@ impl VersionNumber {
     }
 }
 
+impl VersionNumber {
+    /// Where numbers of this kind sort among the other kinds.
+    fn kind_rank(&self) -> u8 {
+        match self {
+            VersionNumber::CCVer(_) => 0,
+            VersionNumber::CalVer(..) => 1,
+            VersionNumber::Sha(_) => 2,
+            VersionNumber::ShortSha(_) => 3,
+        }
+    }
+}
+
+/// Numbers of the same kind compare by value. Calendar versions compare by
+/// their dates whatever their formats, which only break ties. Numbers of
+/// different kinds cannot be compared meaningfully, so they sort by kind.
 impl Ord for VersionNumber {
     fn cmp(&self, other: &Self) -> Ordering {
-        match self {
-            VersionNumber::CCVer(ver) => match other {
-                VersionNumber::CCVer(ver2) => ver.cmp(ver2),
-                _ => panic!("Cannot compare CCVer Version Number with CalVer Version Number"),
-            },
-            VersionNumber::CalVer(format, date) => match other {
-                VersionNumber::CalVer(format2, date2) => {
-                    if format.iter().eq(format2.iter()) {
-                        date.cmp(date2)
-                    } else {
-                        // TODO: Implement a link between all the calender versions in a version so that date segments can be compared without regard to format
-                        panic!("Cannot compare CalVer Version Number with different formats")
-                    }
-                }
-                _ => panic!("Cannot compare CCVer Version Number with CalVer Version Number"),
-            },
-            VersionNumber::Sha(s) => match other {
-                VersionNumber::Sha(s2) => s.cmp(s2),
-                _ => panic!("Cannot compare Sha Version Number with non Sha Version Number"),
-            },
-            VersionNumber::ShortSha(s) => match other {
-                VersionNumber::ShortSha(s2) => s.cmp(s2),
-                _ => panic!(
-                    "Cannot compare ShortSha Version Number with non ShortSha Version Number"
-                ),
-            },
+        match (self, other) {
+            (VersionNumber::CCVer(ver), VersionNumber::CCVer(ver2)) => ver.cmp(ver2),
+            (VersionNumber::CalVer(format, date), VersionNumber::CalVer(format2, date2)) => {
+                date.cmp(date2).then_with(|| {
+                    let segments = |format: &CalVerFormat| {
+                        format.iter().map(|seg| *seg as u8).collect::<Vec<_>>()
+                    };
+                    segments(format).cmp(&segments(format2))
+                })
+            }
+            (VersionNumber::Sha(s), VersionNumber::Sha(s2))
+            | (VersionNumber::ShortSha(s), VersionNumber::ShortSha(s2)) => s.cmp(s2),
+            _ => self.kind_rank().cmp(&other.kind_rank()),
         }
     }
 }
 
 impl PartialOrd for VersionNumber {
-    #[allow(clippy::non_canonical_partial_ord_impl)]
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match self {
-            VersionNumber::CCVer(ver) => match other {
-                VersionNumber::CCVer(ver2) => Some(ver.cmp(ver2)),
-                _ => None,
-            },
-            VersionNumber::CalVer(format, date) => match other {
-                VersionNumber::CalVer(format2, date2) => {
-                    if format.iter().eq(format2.iter()) {
-                        date.partial_cmp(date2)
-                    } else {
-                        None
-                    }
-                }
-                _ => None,
-            },
-            VersionNumber::Sha(_) | VersionNumber::ShortSha(_) => None,
-        }
+        Some(self.cmp(other))
     }
 }
 
@@ -547,3 +728,253 @@ impl Display for VersionNumber {
         }
     }
 }
+
+/// `{"version", "v_prefix", "major", "minor", "patch", "prerelease",
+/// "prerelease_identifiers", "build_metadata"}`, where `version` is the
+/// rendered string and `prerelease` summarizes the channel (see [`Channel`]).
+impl Serialize for Version {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Version", 8)?;
+        state.serialize_field("version", &self.to_string())?;
+        state.serialize_field("v_prefix", &self.v_prefix)?;
+        state.serialize_field("major", &self.major)?;
+        state.serialize_field("minor", &self.minor)?;
+        state.serialize_field("patch", &self.patch)?;
+        state.serialize_field("prerelease", &Channel(self))?;
+        state.serialize_field("prerelease_identifiers", &self.prerelease)?;
+        state.serialize_field("build_metadata", &self.build)?;
+        state.end()
+    }
+}
+
+struct Channel<'a>(&'a Version);
+
+/// `null` for releases, otherwise `{"kind", "name", "number"}`: `kind` is
+/// `rc`, `beta`, `alpha`, `build` or `named` for `<label>.<counter>`
+/// prereleases, `sha` or `short_sha` for a release carrying only a commit
+/// hash as build metadata, and `other` for any other prerelease. `name` is
+/// only set for `named`.
+impl Serialize for Channel<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let version = self.0;
+        // hashes stay strings, even when they are all digits
+        let sha = || Some(Identifier::AlphaNumeric(version.build.join(".")));
+        let (kind, name, number) = match (&version.prerelease[..], version.build_kind) {
+            ([], BuildKind::Sha) => ("sha", None, sha()),
+            ([], BuildKind::ShortSha) => ("short_sha", None, sha()),
+            ([], BuildKind::Metadata) => return serializer.serialize_none(),
+            ([Identifier::AlphaNumeric(label), counter], _) => match label.as_str() {
+                kind @ ("rc" | "beta" | "alpha" | "build") => (kind, None, Some(counter.clone())),
+                name => ("named", Some(name), Some(counter.clone())),
+            },
+            _ => ("other", None, None),
+        };
+        let mut state = serializer.serialize_struct("Channel", 3)?;
+        state.serialize_field("kind", kind)?;
+        state.serialize_field("name", &name)?;
+        state.serialize_field("number", &number)?;
+        state.end()
+    }
+}
+
+impl Serialize for Identifier {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Identifier::Numeric(n) => serializer.serialize_u64(*n),
+            Identifier::AlphaNumeric(s) => serializer.serialize_str(s),
+        }
+    }
+}
+
+/// Counters serialize as JSON numbers; calendar segments and hashes as their
+/// rendered strings so leading zeros survive.
+impl Serialize for VersionNumber {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            VersionNumber::CCVer(v) => serializer.serialize_u64(*v as u64),
+            _ => serializer.collect_str(self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod version_tests {
+    use crate::{
+        config::{Bump, CCVerConfig},
+        logs::PeekLogEntry as _,
+        version_format::VersionFormat,
+    };
+
+    #[test]
+    fn test_forced_bump_overrides_subject() {
+        let format = VersionFormat::default();
+        let config = CCVerConfig::default();
+        let entry = "docs: update readme"
+            .into_peek_log_entry(crate::logs::PEEK_COMMIT_HASH, "main")
+            .unwrap();
+        let base = format.as_default_version(&entry).no_pre();
+
+        let implied = base.forced_next_version(None, &entry, &format, &config);
+        assert!(implied.to_string().starts_with("v0.0.0+"));
+
+        let forced = base.forced_next_version(Some(Bump::Major), &entry, &format, &config);
+        assert_eq!(forced.to_string(), "v1.0.0");
+
+        let develop = "docs: update readme"
+            .into_peek_log_entry(crate::logs::PEEK_COMMIT_HASH, "develop")
+            .unwrap();
+        let forced = base.forced_next_version(Some(Bump::Minor), &develop, &format, &config);
+        assert_eq!(forced.to_string(), "v0.1.0-beta.0");
+    }
+
+    #[test]
+    fn test_initial_development() {
+        let format = VersionFormat::default();
+        let config = CCVerConfig {
+            initial_development: true,
+            ..CCVerConfig::default()
+        };
+        let entry = |message: &'static str| {
+            message
+                .into_peek_log_entry(crate::logs::PEEK_COMMIT_HASH, "main")
+                .unwrap()
+        };
+        let base = format.as_default_version(&entry("chore: init")).no_pre();
+
+        let breaking = base.next_version(&entry("feat!: drop api"), &format, &config);
+        assert_eq!(breaking.to_string(), "v0.1.0");
+        let feature = breaking.next_version(&entry("feat: add"), &format, &config);
+        assert_eq!(feature.to_string(), "v0.1.1");
+        let literal =
+            breaking.next_version(&entry("feat!: drop api"), &format, &CCVerConfig::default());
+        assert_eq!(literal.to_string(), "v1.0.0");
+
+        let graduated = feature.next_version(
+            &entry("chore: stabilize\n\nGraduate: 1.0.0"),
+            &format,
+            &config,
+        );
+        assert_eq!(graduated.to_string(), "v1.0.0");
+        for message in [
+            "chore: stabilize\n\nGraduate: no",
+            "chore: stabilize\n\nGraduate: 2.0.0",
+        ] {
+            let kept = feature.next_version(&entry(message), &format, &config);
+            assert_eq!(kept.to_string(), "v0.1.1+0000000", "{}", message);
+        }
+        let major = graduated.next_version(&entry("feat!: drop api"), &format, &config);
+        assert_eq!(major.to_string(), "v2.0.0");
+
+        let flag = CCVerConfig {
+            graduate: true,
+            ..config.clone()
+        };
+        assert_eq!(feature.head_bump(&flag), Some(Bump::Major));
+        assert_eq!(graduated.head_bump(&flag), None);
+    }
+
+    #[test]
+    fn test_version_numbers_are_totally_ordered() {
+        use std::sync::Arc;
+
+        use chrono::TimeZone as _;
+
+        use crate::{version::VersionNumber, version_format::CalVerFormatSegment::*};
+
+        let date = |year| chrono::Utc.with_ymd_and_hms(year, 5, 1, 0, 0, 0).unwrap();
+        let year = VersionNumber::CalVer(Arc::new([Year4]), date(2024));
+        let month = VersionNumber::CalVer(Arc::new([Year4, Month]), date(2024));
+        let later = VersionNumber::CalVer(Arc::new([Year2]), date(2025));
+        assert!(year < month && month < later);
+
+        let mut numbers = vec![
+            VersionNumber::ShortSha("abc".to_string()),
+            later.clone(),
+            VersionNumber::Sha("abc".to_string()),
+            VersionNumber::CCVer(2),
+            year.clone(),
+        ];
+        numbers.sort();
+        assert_eq!(
+            numbers,
+            [
+                VersionNumber::CCVer(2),
+                year,
+                later,
+                VersionNumber::Sha("abc".to_string()),
+                VersionNumber::ShortSha("abc".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_release_as() {
+        let format = VersionFormat::default();
+        let config = CCVerConfig::default();
+        let entry = |message: &'static str| {
+            message
+                .into_peek_log_entry(crate::logs::PEEK_COMMIT_HASH, "develop")
+                .unwrap()
+        };
+        let base = format.as_default_version(&entry("chore: init")).no_pre();
+
+        let pinned = entry("fix: typo\n\nRelease-As: 3.0.0");
+        assert_eq!(
+            base.next_version(&pinned, &format, &config).to_string(),
+            "v3.0.0"
+        );
+        let next = base
+            .release_as(&pinned, &format)
+            .unwrap()
+            .unwrap()
+            .next_version(&entry("feat: add"), &format, &config);
+        assert_eq!(next.to_string(), "v3.1.0-beta.0");
+
+        let backwards = entry("fix: typo\n\nrelease-as: v2.0.0");
+        assert!(next.release_as(&backwards, &format).is_err());
+        // an invalid footer in the history is ignored
+        assert_eq!(
+            next.next_version(&backwards, &format, &config).to_string(),
+            "v3.1.1-beta.0"
+        );
+        let repeated = entry("fix: typo\n\nRelease-As: 3.0.0");
+        let three = base.pinned_version(&pinned, &format).unwrap();
+        assert!(three.release_as(&repeated, &format).is_err());
+        assert_eq!(three.pinned_version(&repeated, &format), None);
+        assert!(
+            base.release_as(&entry("fix: typo\n\nRelease-As: 3.0"), &format)
+                .is_err()
+        );
+        assert!(
+            base.release_as(&entry("fix: typo\n\nRelease-As: 3.0.0 soon"), &format)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_version_json() {
+        let format = VersionFormat::default();
+        let entry = "feat: add"
+            .into_peek_log_entry(crate::logs::PEEK_COMMIT_HASH, "develop")
+            .unwrap();
+        let version = format.as_default_version(&entry).no_pre().next_version(
+            &entry,
+            &format,
+            &CCVerConfig::default(),
+        );
+
+        assert_eq!(
+            serde_json::to_value(&version).unwrap(),
+            serde_json::json!({
+                "version": "v0.1.0-beta.0",
+                "v_prefix": true,
+                "major": 0,
+                "minor": 1,
+                "patch": 0,
+                "prerelease": { "kind": "beta", "name": null, "number": 0 },
+                "prerelease_identifiers": ["beta", 0],
+                "build_metadata": [],
+            })
+        );
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     fmt::{Display, Formatter},
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct VersionFormat {
     pub v_prefix: bool,
     pub major: VersionNumberFormat,
@@ -26,20 +26,28 @@ impl Default for VersionFormat {
 
 impl VersionFormat {
     pub fn as_default_version(&self, commit: &LogEntry) -> Version {
+        let (prerelease, build) = self
+            .prerelease
+            .as_ref()
+            .map(|ptf| ptf.as_default_identifiers(commit))
+            .unwrap_or_default();
         Version {
             v_prefix: self.v_prefix,
             major: self.major.as_default_version_number(commit),
             minor: self.minor.as_default_version_number(commit),
             patch: self.patch.as_default_version_number(commit),
-            prerelease: self
+            prerelease,
+            build,
+            build_kind: self
                 .prerelease
                 .as_ref()
-                .map(|ptf| ptf.as_default_pre_tag(commit)),
+                .map(PreTagFormat::build_kind)
+                .unwrap_or_default(),
         }
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub enum VersionNumberFormat {
     #[default]
     CCVer,
@@ -97,7 +105,7 @@ use std::sync::Arc;
 
 use crate::{
     logs::LogEntry,
-    version::{PreTag, Version, VersionNumber},
+    version::{BuildKind, Identifier, Version, VersionNumber},
 };
 
 impl Ord for CalVerFormatSegment {
@@ -146,7 +154,7 @@ impl Ord for CalVerFormatSegment {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PreTagFormat {
     Rc(VersionNumberFormat),
     Beta(VersionNumberFormat),
@@ -189,21 +197,33 @@ impl Default for &PreTagFormat {
 }
 
 impl PreTagFormat {
-    pub fn as_default_pre_tag(&self, commit: &LogEntry) -> PreTag {
+    /// The prerelease identifiers and build metadata of the first version
+    /// written with this format.
+    pub fn as_default_identifiers(&self, commit: &LogEntry) -> (Vec<Identifier>, Vec<String>) {
+        let channel = |label: &str, vf: &VersionNumberFormat| {
+            let counter = Identifier::from(&vf.as_default_version_number(commit));
+            (
+                vec![Identifier::AlphaNumeric(label.to_string()), counter],
+                vec![],
+            )
+        };
         match self {
-            PreTagFormat::Rc(vf) => PreTag::Rc(vf.as_default_version_number(commit)),
-            PreTagFormat::Beta(vf) => PreTag::Beta(vf.as_default_version_number(commit)),
-            PreTagFormat::Alpha(vf) => PreTag::Alpha(vf.as_default_version_number(commit)),
-            PreTagFormat::Build(vf) => PreTag::Build(vf.as_default_version_number(commit)),
-            PreTagFormat::Named(name, vf) => {
-                PreTag::Named(name.clone(), vf.as_default_version_number(commit))
-            }
-            PreTagFormat::Sha => {
-                PreTag::Sha(VersionNumber::ShortSha(commit.commit_hash.to_string()))
-            }
-            PreTagFormat::ShortSha => PreTag::ShortSha(VersionNumber::ShortSha(
-                commit.commit_hash[0..7].to_string(),
-            )),
+            PreTagFormat::Rc(vf) => channel("rc", vf),
+            PreTagFormat::Beta(vf) => channel("beta", vf),
+            PreTagFormat::Alpha(vf) => channel("alpha", vf),
+            PreTagFormat::Build(vf) => channel("build", vf),
+            PreTagFormat::Named(name, vf) => channel(name, vf),
+            PreTagFormat::Sha => (vec![], vec![commit.commit_hash.to_string()]),
+            PreTagFormat::ShortSha => (vec![], vec![commit.commit_hash[0..7].to_string()]),
+        }
+    }
+
+    /// What the build metadata of versions written with this format holds.
+    pub fn build_kind(&self) -> BuildKind {
+        match self {
+            PreTagFormat::Sha => BuildKind::Sha,
+            PreTagFormat::ShortSha => BuildKind::ShortSha,
+            _ => BuildKind::Metadata,
         }
     }
 
@@ -218,26 +238,14 @@ impl PreTagFormat {
             PreTagFormat::ShortSha => VersionNumberFormat::ShortSha,
         }
     }
-
-    pub fn parse(&self, data: &str) -> PreTag {
-        match self {
-            PreTagFormat::Rc(vf) => PreTag::Rc(vf.parse(data)),
-            PreTagFormat::Beta(vf) => PreTag::Beta(vf.parse(data)),
-            PreTagFormat::Alpha(vf) => PreTag::Alpha(vf.parse(data)),
-            PreTagFormat::Build(vf) => PreTag::Build(vf.parse(data)),
-            PreTagFormat::Named(name, vf) => PreTag::Named(name.clone(), vf.parse(data)),
-            PreTagFormat::Sha => PreTag::Sha(VersionNumber::Sha(data.to_string())),
-            PreTagFormat::ShortSha => {
-                PreTag::ShortSha(VersionNumber::ShortSha(data[0..7].to_string()))
-            }
-        }
-    }
 }
 
 impl VersionNumberFormat {
-    pub fn parse(&self, data: &str) -> VersionNumber {
+    /// Reads a version number written in this format, or `None` when `data`
+    /// is not one.
+    pub fn parse(&self, data: &str) -> Option<VersionNumber> {
         match self {
-            VersionNumberFormat::CCVer => VersionNumber::CCVer(usize::from_str(data).unwrap()),
+            VersionNumberFormat::CCVer => usize::from_str(data).ok().map(VersionNumber::CCVer),
             VersionNumberFormat::CalVer(calendar_parts) => {
                 let format_str: String = calendar_parts
                     .iter()
@@ -255,11 +263,13 @@ impl VersionNumberFormat {
                     .collect::<Vec<&str>>()
                     .join("");
 
-                let date = chrono::DateTime::parse_from_str(data, &format_str).unwrap();
-                VersionNumber::CalVer(calendar_parts.clone(), date.to_utc())
+                let date = chrono::DateTime::parse_from_str(data, &format_str).ok()?;
+                Some(VersionNumber::CalVer(calendar_parts.clone(), date.to_utc()))
             }
-            VersionNumberFormat::Sha => VersionNumber::Sha(data.to_string()),
-            VersionNumberFormat::ShortSha => VersionNumber::ShortSha(data[0..7].to_string()),
+            VersionNumberFormat::Sha => Some(VersionNumber::Sha(data.to_string())),
+            VersionNumberFormat::ShortSha => data
+                .get(0..7)
+                .map(|short| VersionNumber::ShortSha(short.to_string())),
         }
     }
 }